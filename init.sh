@@ -12,4 +12,3 @@ apt-get install -qq -y --no-install-recommends \
 rm -rf /var/lib/apt/lists/*
 
 mkdir /var/discordtts
-echo '{"voice_settings":{}}' > /var/discordtts/state.json
@@ -0,0 +1,288 @@
+//! Token-protected HTTP API for operators.
+
+use std::collections::BTreeMap;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+use axum::{
+    Json, Router,
+    extract::{Path, Request, State},
+    http::{StatusCode, header},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+};
+use serde::Serialize;
+use serenity::model::id::{ChannelId, GuildId, UserId};
+use songbird::Songbird;
+
+use crate::db::{INMEMORY_DB, PERSISTENT_DB};
+use crate::model::{TtsParams, TtsStyle};
+use crate::tts::{CharacterView, TtsServices};
+
+struct AdminState {
+    tts_services: TtsServices,
+    songbird: Arc<Songbird>,
+}
+
+type ApiResult<T> = Result<T, (StatusCode, String)>;
+
+#[derive(Serialize)]
+struct SessionView {
+    guild_id: GuildId,
+    text_channel_id: ChannelId,
+    voice_channel_id: Option<ChannelId>,
+    queue_length: usize,
+}
+
+#[derive(Serialize)]
+struct ServiceView {
+    down: bool,
+    characters: Vec<CharacterView>,
+}
+
+#[derive(Serialize)]
+struct ServicesView {
+    services: BTreeMap<String, ServiceView>,
+    /// Services which are not reachable yet and retried in background.
+    pending: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct RefreshView {
+    added: usize,
+    removed: Vec<String>,
+}
+
+/// Compares in constant time so that the token cannot be guessed from the response time.
+fn token_matches(expected: &str, actual: &str) -> bool {
+    expected.len() == actual.len()
+        && expected
+            .bytes()
+            .zip(actual.bytes())
+            .fold(0, |acc, (a, b)| acc | (a ^ b))
+            == 0
+}
+
+/// Rejects requests without `Authorization: Bearer <token>`.
+pub async fn require_token(
+    State(token): State<Arc<str>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let authorized = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .is_some_and(|actual| token_matches(&token, actual));
+
+    if !authorized {
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+
+    next.run(request).await
+}
+
+async fn sessions(State(state): State<Arc<AdminState>>) -> Json<Vec<SessionView>> {
+    let mut sessions = vec![];
+
+    for (guild_id, text_channel_id) in INMEMORY_DB.get_instances() {
+        let (voice_channel_id, queue_length) = match state.songbird.get(guild_id) {
+            Some(call) => {
+                let call = call.lock().await;
+                let channel = call.current_channel().map(|id| ChannelId::new(id.0.get()));
+
+                (channel, call.queue().len())
+            }
+            None => (None, 0),
+        };
+
+        sessions.push(SessionView {
+            guild_id,
+            text_channel_id,
+            voice_channel_id,
+            queue_length,
+        });
+    }
+
+    Json(sessions)
+}
+
+async fn services(State(state): State<Arc<AdminState>>) -> Json<ServicesView> {
+    let services = state
+        .tts_services
+        .styles()
+        .await
+        .into_iter()
+        .map(|(service_id, characters)| {
+            let view = ServiceView {
+                down: state.tts_services.is_down(&service_id),
+                characters,
+            };
+
+            (service_id, view)
+        })
+        .collect();
+
+    Json(ServicesView {
+        services,
+        pending: state.tts_services.pending(),
+    })
+}
+
+async fn refresh(
+    State(state): State<Arc<AdminState>>,
+    Path(service_id): Path<String>,
+) -> ApiResult<Json<RefreshView>> {
+    let mut report = state.tts_services.refresh_service(&service_id).await;
+    report.log();
+
+    if let Some((_, e)) = report.failed.pop() {
+        return Err((StatusCode::BAD_GATEWAY, format!("{e:#}")));
+    }
+
+    if report.refreshed == 0 {
+        return Err((
+            StatusCode::NOT_FOUND,
+            format!("'{service_id}' is not registered"),
+        ));
+    }
+
+    Ok(Json(RefreshView {
+        added: report.added,
+        removed: report
+            .removed
+            .into_iter()
+            .map(|(_, style_id)| style_id)
+            .collect(),
+    }))
+}
+
+async fn leave(
+    State(state): State<Arc<AdminState>>,
+    Path(guild_id): Path<GuildId>,
+) -> ApiResult<StatusCode> {
+    state
+        .songbird
+        .leave(guild_id)
+        .await
+        .map_err(|_| (StatusCode::NOT_FOUND, "Not in a voice channel".to_string()))?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn get_style(Path(user_id): Path<UserId>) -> ApiResult<Json<TtsStyle>> {
+    PERSISTENT_DB
+        .get_voice_setting(user_id)
+        .map(Json)
+        .ok_or((StatusCode::NOT_FOUND, "No style is stored".to_string()))
+}
+
+/// Rejects parameters out of range, which the backends would not play.
+pub fn validate_params(params: &TtsParams) -> ApiResult<()> {
+    params
+        .validate()
+        .map_err(|e| (StatusCode::UNPROCESSABLE_ENTITY, e))
+}
+
+async fn put_style(
+    State(state): State<Arc<AdminState>>,
+    Path(user_id): Path<UserId>,
+    Json(style): Json<TtsStyle>,
+) -> ApiResult<Json<TtsStyle>> {
+    validate_params(&style.params)?;
+
+    if !state
+        .tts_services
+        .is_available(&style.service_id, &style.style_id)
+        .await
+    {
+        return Err((
+            StatusCode::UNPROCESSABLE_ENTITY,
+            format!("{}/{} is not available", style.service_id, style.style_id),
+        ));
+    }
+
+    PERSISTENT_DB.store_style_id(user_id, &style);
+
+    Ok(Json(style))
+}
+
+/// Serves the API on `addr`. Every request needs `Authorization: Bearer <token>`.
+pub async fn spawn(
+    addr: SocketAddr,
+    token: String,
+    tts_services: TtsServices,
+    songbird: Arc<Songbird>,
+) -> Result<()> {
+    let state = Arc::new(AdminState {
+        tts_services,
+        songbird,
+    });
+
+    let app = Router::new()
+        .route("/sessions", get(sessions))
+        .route("/guilds/{guild_id}/leave", post(leave))
+        .route("/services", get(services))
+        .route("/services/{service_id}/refresh", post(refresh))
+        .route("/users/{user_id}/style", get(get_style).put(put_style))
+        .layer(middleware::from_fn_with_state(
+            Arc::from(token),
+            require_token,
+        ))
+        .with_state(state);
+
+    let listener = tokio::net::TcpListener::bind(addr)
+        .await
+        .with_context(|| format!("Failed to listen on {addr}"))?;
+
+    tracing::info!("Serving the admin API on {addr}");
+
+    tokio::spawn(async move {
+        if let Err(e) = axum::serve(listener, app).await {
+            tracing::warn!("Admin API stopped: {e}");
+        }
+    });
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_token_matches() {
+        assert!(token_matches("secret", "secret"));
+        assert!(!token_matches("secret", "secreT"));
+        assert!(!token_matches("secret", "secret2"));
+        assert!(!token_matches("secret", ""));
+    }
+
+    #[test]
+    fn test_validate_params() {
+        assert!(validate_params(&TtsParams::default()).is_ok());
+        assert!(
+            validate_params(&TtsParams {
+                speed: Some(1.5),
+                volume: Some(0.0),
+                ..Default::default()
+            })
+            .is_ok()
+        );
+
+        for speed in [0.0, -1.0, f32::NAN, f32::INFINITY, 1e9] {
+            let params = TtsParams {
+                speed: Some(speed),
+                ..Default::default()
+            };
+
+            assert_eq!(
+                validate_params(&params).unwrap_err().0,
+                StatusCode::UNPROCESSABLE_ENTITY
+            );
+        }
+    }
+}
@@ -1,15 +1,17 @@
 use std::collections::HashMap;
-use std::io::Cursor;
 use std::sync::Arc;
 
 use anyhow::{Context, Result};
 use async_trait::async_trait;
-use hound::{WavReader, WavWriter};
 use reqwest::header::{HeaderMap, HeaderName};
 use serde::Deserialize;
 use tap::Tap;
 
-use crate::tts::{CharacterView, StyleView, TtsService, split_long_text};
+use crate::audio::Audio;
+use crate::model::TtsParams;
+use crate::tts::{
+    Capabilities, CharacterView, NativeParams, StyleView, TtsService, split_long_text,
+};
 
 mod api;
 
@@ -37,7 +39,7 @@ fn default_max_chars() -> usize {
     500
 }
 
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Deserialize, Debug, Clone, PartialEq)]
 pub struct VoiceConfig {
     pub voice_id: String,
     #[serde(default = "default_speed")]
@@ -48,7 +50,7 @@ pub struct VoiceConfig {
     pub volume: f32,
 }
 
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Deserialize, Debug, Clone, PartialEq)]
 pub struct Setting {
     pub url: reqwest::Url,
     #[serde(default = "default_headers")]
@@ -107,7 +109,7 @@ impl AndroidTTS {
 
 #[async_trait]
 impl TtsService for AndroidTTS {
-    async fn tts(&self, style_id: &str, text: &str) -> Result<Vec<u8>> {
+    async fn tts(&self, style_id: &str, text: &str, params: &TtsParams) -> Result<Audio> {
         let api_tts = self.inner.url.clone().tap_mut(|u| {
             u.path_segments_mut().unwrap().push("api").push("tts");
         });
@@ -122,14 +124,17 @@ impl TtsService for AndroidTTS {
             (Some(voice_config.voice_id.as_str()), speed, pitch, volume)
         };
 
+        let speed = speed * params.speed.unwrap_or(1.0);
+        let pitch = pitch * params.pitch.unwrap_or(1.0);
+        let volume = volume * params.volume.unwrap_or(1.0);
+
         let parts = if self.inner.max_chars == 0 {
             vec![text.to_string()]
         } else {
             split_long_text(text, self.inner.max_chars)
         };
 
-        let mut all_samples: Vec<i16> = Vec::new();
-        let mut sample_rate = None;
+        let mut parts_audio = vec![];
 
         for part in parts {
             let query = api::TtsRequest {
@@ -157,38 +162,27 @@ impl TtsService for AndroidTTS {
                 .await
                 .context("Failed to post /api/tts (body)")?;
 
-            let mut reader = WavReader::new(Cursor::new(wav_data))
-                .with_context(|| "Failed to read as wav file")?;
-            let spec = reader.spec();
-            if sample_rate.is_none() {
-                sample_rate = Some(spec.sample_rate);
-            }
-
-            for sample in reader.samples::<i16>() {
-                all_samples.push(sample?);
-            }
+            parts_audio.push(Audio::from_wav(&wav_data)?);
         }
 
-        let spec = hound::WavSpec {
-            channels: 1,
-            sample_rate: sample_rate.unwrap_or(24000),
-            bits_per_sample: 16,
-            sample_format: hound::SampleFormat::Int,
-        };
-        let mut cursor = Cursor::new(Vec::new());
-        {
-            let mut writer =
-                WavWriter::new(&mut cursor, spec).with_context(|| "Failed to create wav")?;
-            for sample in all_samples {
-                let sample = f32::from(sample) * volume;
-                let sample = sample.min(f32::from(i16::MAX)).max(f32::from(i16::MIN));
-                #[allow(clippy::cast_possible_truncation)]
-                writer.write_sample(sample as i16)?;
-            }
-            writer.finalize()?;
+        Ok(Audio::concat(parts_audio)?.gain(volume))
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            params: NativeParams {
+                speed: true,
+                pitch: true,
+                intonation: false,
+                volume: true,
+            },
+            ssml: false,
+            per_style_volume: false,
         }
+    }
 
-        Ok(cursor.into_inner())
+    fn max_chars(&self) -> Option<usize> {
+        (self.inner.max_chars != 0).then_some(self.inner.max_chars)
     }
 
     async fn styles(&self) -> Result<Vec<CharacterView>> {
@@ -199,6 +193,8 @@ impl TtsService for AndroidTTS {
                 icon: vec![],
                 name: "Default".to_string(),
                 id: "default".to_string(),
+                language: None,
+                gender: None,
             });
         }
 
@@ -207,6 +203,8 @@ impl TtsService for AndroidTTS {
                 icon: vec![],
                 name: name.clone(),
                 id: name.clone(),
+                language: None,
+                gender: None,
             });
         }
 
@@ -214,6 +212,8 @@ impl TtsService for AndroidTTS {
             name: "Android TTS".to_string(),
             policy: "Android TTS Engine".to_string(),
             styles,
+            language: None,
+            gender: None,
         }])
     }
 }
@@ -0,0 +1,269 @@
+use std::io::Cursor;
+
+use anyhow::{Context, Result};
+
+/// Interleaved 16-bit PCM audio returned by the backends.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Audio {
+    pub samples: Vec<i16>,
+    pub sample_rate: u32,
+    pub channels: u16,
+}
+
+impl Audio {
+    pub fn silence() -> Self {
+        Self {
+            samples: vec![],
+            sample_rate: 24000,
+            channels: 1,
+        }
+    }
+
+    pub fn from_wav(data: &[u8]) -> Result<Self> {
+        let mut reader =
+            hound::WavReader::new(Cursor::new(data)).context("Failed to read as wav file")?;
+        let spec = reader.spec();
+
+        #[allow(clippy::cast_possible_truncation)]
+        let samples = match (spec.sample_format, spec.bits_per_sample) {
+            (hound::SampleFormat::Int, 16) => reader.samples::<i16>().collect::<Result<_, _>>()?,
+            (hound::SampleFormat::Int, bits) => reader
+                .samples::<i32>()
+                .map(|s| {
+                    s.map(|s| {
+                        if bits > 16 {
+                            (s >> (bits - 16)) as i16
+                        } else {
+                            (s << (16 - bits)) as i16
+                        }
+                    })
+                })
+                .collect::<Result<_, _>>()?,
+            (hound::SampleFormat::Float, _) => reader
+                .samples::<f32>()
+                .map(|s| s.map(|s| (s * f32::from(i16::MAX)) as i16))
+                .collect::<Result<_, _>>()?,
+        };
+
+        Ok(Self {
+            samples,
+            sample_rate: spec.sample_rate,
+            channels: spec.channels,
+        })
+    }
+
+    pub fn from_mp3(data: Vec<u8>) -> Result<Self> {
+        use symphonia::core::codecs::audio::AudioDecoderOptions;
+        use symphonia::core::formats::FormatOptions;
+        use symphonia::core::formats::TrackType;
+        use symphonia::core::formats::probe::Hint;
+        use symphonia::core::io::{MediaSourceStream, MediaSourceStreamOptions};
+        use symphonia::core::meta::MetadataOptions;
+
+        let mss = MediaSourceStream::new(
+            Box::new(Cursor::new(data)),
+            MediaSourceStreamOptions::default(),
+        );
+        let mut hint = Hint::new();
+        hint.with_extension("mp3");
+
+        let mut format = symphonia::default::get_probe().probe(
+            &hint,
+            mss,
+            FormatOptions::default(),
+            MetadataOptions::default(),
+        )?;
+
+        let track = format
+            .default_track(TrackType::Audio)
+            .context("No audio track found")?;
+
+        let mut decoder = symphonia::default::get_codecs().make_audio_decoder(
+            track
+                .codec_params
+                .as_ref()
+                .and_then(|params| params.audio())
+                .context("Not an audio track")?,
+            &AudioDecoderOptions::default(),
+        )?;
+
+        let track_id = track.id;
+        let mut audio: Option<Self> = None;
+
+        while let Some(packet) = format.next_packet()? {
+            if packet.track_id != track_id {
+                continue;
+            }
+
+            let decoded = match decoder.decode(&packet) {
+                Ok(decoded) => decoded,
+                Err(symphonia::core::errors::Error::DecodeError(_)) => continue,
+                Err(e) => return Err(e.into()),
+            };
+
+            let audio = audio.get_or_insert_with(|| Self {
+                samples: vec![],
+                sample_rate: decoded.spec().rate(),
+                #[allow(clippy::cast_possible_truncation)]
+                channels: decoded.spec().channels().count() as u16,
+            });
+
+            let offset = audio.samples.len();
+            audio
+                .samples
+                .resize(offset + decoded.samples_interleaved(), 0);
+            decoded.copy_to_slice_interleaved(&mut audio.samples[offset..]);
+        }
+
+        Ok(audio.unwrap_or_else(Self::silence))
+    }
+
+    pub fn to_wav(&self) -> Result<Vec<u8>> {
+        let spec = hound::WavSpec {
+            channels: self.channels,
+            sample_rate: self.sample_rate,
+            bits_per_sample: 16,
+            sample_format: hound::SampleFormat::Int,
+        };
+
+        let mut cursor = Cursor::new(Vec::new());
+        let mut writer = hound::WavWriter::new(&mut cursor, spec)?;
+
+        for &sample in &self.samples {
+            writer.write_sample(sample)?;
+        }
+
+        writer.finalize()?;
+        Ok(cursor.into_inner())
+    }
+
+    /// Joins audio split by the backend. Every part must have the same format.
+    pub fn concat(parts: impl IntoIterator<Item = Self>) -> Result<Self> {
+        let mut parts = parts.into_iter();
+
+        let Some(mut audio) = parts.next() else {
+            return Ok(Self::silence());
+        };
+
+        for part in parts {
+            if part.sample_rate != audio.sample_rate || part.channels != audio.channels {
+                anyhow::bail!(
+                    "Format mismatch: {}Hz {}ch and {}Hz {}ch",
+                    audio.sample_rate,
+                    audio.channels,
+                    part.sample_rate,
+                    part.channels,
+                );
+            }
+
+            audio.samples.extend(part.samples);
+        }
+
+        Ok(audio)
+    }
+
+    #[must_use]
+    pub fn gain(mut self, gain: f32) -> Self {
+        #[allow(clippy::float_cmp)]
+        if gain == 1.0 {
+            return self;
+        }
+
+        for sample in &mut self.samples {
+            let v = f32::from(*sample) * gain;
+
+            #[allow(clippy::cast_possible_truncation)]
+            {
+                *sample = v.clamp(f32::from(i16::MIN), f32::from(i16::MAX)) as i16;
+            }
+        }
+
+        self
+    }
+
+    /// Averages the channels.
+    #[must_use]
+    pub fn into_mono(self) -> Self {
+        if self.channels <= 1 {
+            return self;
+        }
+
+        let samples = self
+            .samples
+            .chunks(usize::from(self.channels))
+            .map(|frame| {
+                let sum: i32 = frame.iter().copied().map(i32::from).sum();
+
+                #[allow(clippy::cast_possible_truncation)]
+                #[allow(clippy::cast_possible_wrap)]
+                {
+                    (sum / frame.len() as i32) as i16
+                }
+            })
+            .collect();
+
+        Self {
+            samples,
+            sample_rate: self.sample_rate,
+            channels: 1,
+        }
+    }
+
+    /// Duration in seconds.
+    pub fn duration(&self) -> f64 {
+        #[allow(clippy::cast_precision_loss)]
+        let frames = (self.samples.len() / usize::from(self.channels.max(1))) as f64;
+        frames / f64::from(self.sample_rate)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn audio(samples: Vec<i16>, sample_rate: u32) -> Audio {
+        Audio {
+            samples,
+            sample_rate,
+            channels: 1,
+        }
+    }
+
+    #[test]
+    fn test_wav_roundtrip() {
+        let original = Audio {
+            samples: vec![1, -2, 3, -4],
+            sample_rate: 44100,
+            channels: 2,
+        };
+
+        let wav = original.to_wav().unwrap();
+        assert_eq!(Audio::from_wav(&wav).unwrap(), original);
+    }
+
+    #[test]
+    fn test_concat() {
+        let joined = Audio::concat([audio(vec![1, 2], 24000), audio(vec![3], 24000)]).unwrap();
+        assert_eq!(joined, audio(vec![1, 2, 3], 24000));
+
+        assert!(Audio::concat([audio(vec![1], 24000), audio(vec![2], 48000)]).is_err());
+        assert_eq!(Audio::concat([]).unwrap(), Audio::silence());
+    }
+
+    #[test]
+    fn test_into_mono() {
+        let stereo = Audio {
+            samples: vec![100, 300, -100, -300],
+            sample_rate: 48000,
+            channels: 2,
+        };
+
+        assert_eq!(stereo.into_mono(), audio(vec![200, -200], 48000));
+    }
+
+    #[test]
+    fn test_gain() {
+        let audio = audio(vec![1000, -1000, 30000], 24000).gain(2.0);
+        assert_eq!(audio.samples, vec![2000, -2000, i16::MAX]);
+    }
+}
@@ -13,6 +13,8 @@ use tokio_tungstenite::{connect_async, tungstenite::protocol::Message};
 use tungstenite::client::IntoClientRequest;
 use uuid::Uuid;
 
+use crate::audio::Audio;
+
 #[allow(clippy::unreadable_literal)]
 const WIN_EPOCH: u64 = 11644473600;
 const TRUSTED_CLIENT_TOKEN: &str = "6A5AA1D4EAFF4E9FB37E23D68491D6F4";
@@ -94,27 +96,7 @@ pub async fn list_voices() -> Result<Vec<Voice>> {
     Ok(resp.json().await?)
 }
 
-fn create_empty_wav() -> Result<Vec<u8>> {
-    use std::io::Cursor;
-
-    let spec = hound::WavSpec {
-        channels: 1,
-        sample_rate: 24000,
-        bits_per_sample: 16,
-        sample_format: hound::SampleFormat::Int,
-    };
-    let mut wav_cursor = Cursor::new(Vec::new());
-    let wav_writer = hound::WavWriter::new(&mut wav_cursor, spec)?;
-    wav_writer.finalize()?;
-    Ok(wav_cursor.into_inner())
-}
-
-pub async fn get_audio_bytes(
-    text: &str,
-    voice: &str,
-    locale: &str,
-    volume: f32,
-) -> Result<Vec<u8>> {
+pub async fn get_audio(text: &str, voice: &str, locale: &str, volume: f32) -> Result<Audio> {
     let parts = crate::tts::split_long_text(text, BING_SPEECH_MAX_CHARS);
 
     let futures: Vec<_> = parts
@@ -130,10 +112,10 @@ pub async fn get_audio_bytes(
     }
 
     if combined_audio.is_empty() {
-        return create_empty_wav();
+        return Ok(Audio::silence());
     }
 
-    crate::tts::convert_mp3_to_wav(combined_audio, volume)
+    Ok(Audio::from_mp3(combined_audio)?.gain(volume))
 }
 
 async fn fetch_audio_part(part: String, voice: String, locale: String) -> Result<Vec<u8>> {
@@ -4,10 +4,12 @@ use anyhow::Result;
 use async_trait::async_trait;
 use serde::Deserialize;
 
-use crate::tts::{CharacterView, StyleView, TtsService};
+use crate::audio::Audio;
+use crate::model::TtsParams;
+use crate::tts::{CharacterView, Gender, StyleView, TtsService};
 
 mod bing_speech_tts;
-use bing_speech_tts::{get_audio_bytes, list_voices};
+use bing_speech_tts::{get_audio, list_voices};
 
 fn default_master_volume() -> f32 {
     1.0
@@ -43,7 +45,7 @@ impl Language {
     }
 }
 
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Deserialize, Debug, Clone, PartialEq)]
 pub struct Setting {
     #[serde(default = "default_master_volume")]
     pub master_volume: f32,
@@ -85,11 +87,11 @@ fn parse_friendly_name(friendly_name: &str) -> String {
 
 #[async_trait]
 impl TtsService for BingSpeech {
-    async fn tts(&self, style_id: &str, text: &str) -> Result<Vec<u8>> {
+    async fn tts(&self, style_id: &str, text: &str, _params: &TtsParams) -> Result<Audio> {
         let (locale, voice) = style_id
             .split_once('/')
             .ok_or_else(|| anyhow::anyhow!("Invalid style_id format: {style_id}"))?;
-        get_audio_bytes(text, voice, locale, self.inner.master_volume).await
+        get_audio(text, voice, locale, self.inner.master_volume).await
     }
 
     async fn styles(&self) -> Result<Vec<CharacterView>> {
@@ -105,6 +107,8 @@ impl TtsService for BingSpeech {
                     name: parse_friendly_name(&voice.friendly_name),
                     id: format!("{}/{}", voice.locale, voice.short_name),
                     icon: vec![],
+                    language: Some(voice.locale.clone()),
+                    gender: Gender::parse(&voice.gender),
                 })
                 .collect();
 
@@ -114,6 +118,8 @@ impl TtsService for BingSpeech {
                     name: language.name.clone(),
                     policy: "Microsoft Services Agreement".to_string(),
                     styles,
+                    language: Some(language.code.clone()),
+                    gender: None,
                 });
             }
         }
@@ -148,7 +154,7 @@ languages = [
 
     #[test]
     fn languages_default_when_unset() {
-        let toml = r#""#;
+        let toml = "";
         let setting: Setting = toml::from_str(toml).unwrap();
         assert_eq!(setting.languages.len(), 10);
         assert_eq!(
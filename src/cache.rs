@@ -0,0 +1,494 @@
+use std::fs::FileTimes;
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant, SystemTime};
+
+use lru::LruCache;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tokio::sync::Mutex;
+
+use crate::audio::Audio;
+use crate::model::TtsStyle;
+
+/// Makes the names of the temporary files unique within the process.
+static TMP_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+fn default_max_entries() -> usize {
+    256
+}
+
+fn default_max_bytes() -> usize {
+    64 * 1024 * 1024
+}
+
+fn default_ttl() -> u64 {
+    24 * 60 * 60
+}
+
+fn default_disk_max_bytes() -> u64 {
+    512 * 1024 * 1024
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct CacheConfig {
+    #[serde(default = "default_max_entries")]
+    pub max_entries: usize,
+    /// Upper bound of the in-memory cache in bytes.
+    #[serde(default = "default_max_bytes")]
+    pub max_bytes: usize,
+    /// Lifetime of an entry in seconds.
+    #[serde(default = "default_ttl")]
+    pub ttl: u64,
+    /// Also keep the synthesized audio in a directory next to the persistent state.
+    #[serde(default)]
+    pub disk: bool,
+    #[serde(default = "default_disk_max_bytes")]
+    pub disk_max_bytes: u64,
+}
+
+struct MemoryEntry {
+    audio: Audio,
+    created_at: Instant,
+}
+
+struct MemoryCache {
+    entries: LruCache<String, MemoryEntry>,
+    bytes: usize,
+}
+
+/// A bounded cache of synthesized audio, keyed by style, parameters and normalized text.
+pub struct TtsCache {
+    memory: Mutex<MemoryCache>,
+    max_bytes: usize,
+    ttl: Duration,
+    disk_dir: Option<PathBuf>,
+    disk_max_bytes: u64,
+    /// Running total of the files on disk, so that the directory is only scanned when it is
+    /// over `disk_max_bytes`. `None` until the first scan.
+    disk_bytes: Mutex<Option<u64>>,
+}
+
+impl std::fmt::Debug for TtsCache {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TtsCache")
+            .field("max_bytes", &self.max_bytes)
+            .field("ttl", &self.ttl)
+            .field("disk_dir", &self.disk_dir)
+            .finish_non_exhaustive()
+    }
+}
+
+fn size_of_audio(audio: &Audio) -> usize {
+    audio.samples.len() * size_of::<i16>()
+}
+
+fn normalize_text(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Starts every key of the service, so that its entries can be dropped alone.
+fn service_prefix(service_id: &str) -> String {
+    let hash = hex::encode(Sha256::digest(service_id.as_bytes()));
+    format!("{}-", &hash[..16])
+}
+
+pub fn cache_key(style: &TtsStyle, text: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(serde_json::to_vec(style).unwrap());
+    hasher.update([0]);
+    hasher.update(normalize_text(text).as_bytes());
+    service_prefix(&style.service_id) + &hex::encode(hasher.finalize())
+}
+
+impl TtsCache {
+    pub fn new(config: &CacheConfig, disk_dir: Option<PathBuf>) -> Self {
+        let disk_dir = disk_dir.filter(|_| config.disk);
+
+        if let Some(dir) = &disk_dir
+            && let Err(e) = std::fs::create_dir_all(dir)
+        {
+            tracing::warn!("Failed to create cache directory {}: {e}", dir.display());
+        }
+
+        Self {
+            memory: Mutex::new(MemoryCache {
+                entries: LruCache::new(
+                    NonZeroUsize::new(config.max_entries).unwrap_or(NonZeroUsize::MIN),
+                ),
+                bytes: 0,
+            }),
+            max_bytes: config.max_bytes,
+            ttl: Duration::from_secs(config.ttl),
+            disk_dir,
+            disk_max_bytes: config.disk_max_bytes,
+            disk_bytes: Mutex::new(None),
+        }
+    }
+
+    pub async fn get(&self, key: &str) -> Option<Audio> {
+        {
+            let mut memory = self.memory.lock().await;
+
+            match memory.entries.get(key) {
+                Some(entry) if entry.created_at.elapsed() < self.ttl => {
+                    return Some(entry.audio.clone());
+                }
+                Some(_) => {
+                    let entry = memory.entries.pop(key).unwrap();
+                    memory.bytes -= size_of_audio(&entry.audio);
+                }
+                None => {}
+            }
+        }
+
+        let audio = self.get_from_disk(key).await?;
+        self.put_to_memory(key, audio.clone()).await;
+        Some(audio)
+    }
+
+    pub async fn put(&self, key: &str, audio: Audio) {
+        self.put_to_disk(key, &audio).await;
+        self.put_to_memory(key, audio).await;
+    }
+
+    /// Drops the entries synthesized by the service, including the files on disk.
+    pub async fn clear_service(&self, service_id: &str) {
+        self.remove_matching(&service_prefix(service_id)).await;
+    }
+
+    /// Drops the entries whose key starts with `prefix`.
+    async fn remove_matching(&self, prefix: &str) {
+        {
+            let mut memory = self.memory.lock().await;
+
+            let keys: Vec<_> = memory
+                .entries
+                .iter()
+                .filter(|(key, _)| key.starts_with(prefix))
+                .map(|(key, _)| key.clone())
+                .collect();
+
+            for key in keys {
+                let entry = memory.entries.pop(&key).unwrap();
+                memory.bytes -= size_of_audio(&entry.audio);
+            }
+        }
+
+        let Some(dir) = &self.disk_dir else {
+            return;
+        };
+
+        let mut disk_bytes = self.disk_bytes.lock().await;
+
+        let result = async {
+            let mut removed = 0;
+            let mut read_dir = tokio::fs::read_dir(dir).await?;
+
+            while let Some(entry) = read_dir.next_entry().await? {
+                let path = entry.path();
+
+                if path.extension().is_some_and(|ext| ext == "wav")
+                    && entry.file_name().to_string_lossy().starts_with(prefix)
+                {
+                    removed += entry.metadata().await?.len();
+                    tokio::fs::remove_file(&path).await?;
+                }
+            }
+
+            std::io::Result::Ok(removed)
+        }
+        .await;
+
+        *disk_bytes = match result {
+            Ok(removed) => disk_bytes.map(|total| total.saturating_sub(removed)),
+            Err(e) => {
+                tracing::warn!("Failed to clear cache files: {e}");
+                None
+            }
+        };
+    }
+
+    async fn put_to_memory(&self, key: &str, audio: Audio) {
+        let size = size_of_audio(&audio);
+
+        if size > self.max_bytes {
+            return;
+        }
+
+        let mut memory = self.memory.lock().await;
+        memory.bytes += size;
+
+        if let Some((_key, old)) = memory.entries.push(
+            key.to_string(),
+            MemoryEntry {
+                audio,
+                created_at: Instant::now(),
+            },
+        ) {
+            memory.bytes -= size_of_audio(&old.audio);
+        }
+
+        while memory.bytes > self.max_bytes {
+            let Some((_key, old)) = memory.entries.pop_lru() else {
+                break;
+            };
+            memory.bytes -= size_of_audio(&old.audio);
+        }
+    }
+
+    async fn get_from_disk(&self, key: &str) -> Option<Audio> {
+        let path = self.disk_dir.as_ref()?.join(format!("{key}.wav"));
+        let metadata = tokio::fs::metadata(&path).await.ok()?;
+        let modified = metadata.modified().ok()?;
+
+        if modified.elapsed().unwrap_or_default() >= self.ttl {
+            if tokio::fs::remove_file(&path).await.is_ok() {
+                self.remove_disk_bytes(metadata.len()).await;
+            }
+            return None;
+        }
+
+        let wav = tokio::fs::read(&path).await.ok()?;
+
+        // The files are evicted by the access time, while the TTL is counted from the write.
+        if let Ok(file) = tokio::fs::File::open(&path).await {
+            let times = FileTimes::new().set_accessed(SystemTime::now());
+            file.into_std().await.set_times(times).ok();
+        }
+
+        Audio::from_wav(&wav).ok()
+    }
+
+    async fn put_to_disk(&self, key: &str, audio: &Audio) {
+        let Some(dir) = &self.disk_dir else {
+            return;
+        };
+
+        let Ok(wav) = audio.to_wav() else {
+            return;
+        };
+
+        // Write to a temporary file first so that readers never see a partial file. The name is
+        // unique, since the same phrase may be put concurrently.
+        let tmp = dir.join(format!(
+            "{key}.{}-{}.tmp",
+            std::process::id(),
+            TMP_COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        let path = dir.join(format!("{key}.wav"));
+
+        if let Err(e) = tokio::fs::write(&tmp, &wav).await {
+            tracing::warn!("Failed to write cache file {}: {e}", tmp.display());
+            tokio::fs::remove_file(&tmp).await.ok();
+            return;
+        }
+
+        // Renamed under the lock, so that the size of a replaced file is subtracted once.
+        let mut disk_bytes = self.disk_bytes.lock().await;
+        let replaced = tokio::fs::metadata(&path).await.map_or(0, |m| m.len());
+
+        if let Err(e) = tokio::fs::rename(&tmp, &path).await {
+            tracing::warn!("Failed to write cache file {}: {e}", path.display());
+            tokio::fs::remove_file(&tmp).await.ok();
+            return;
+        }
+
+        if let Some(total) = &mut *disk_bytes {
+            *total = (*total + wav.len() as u64).saturating_sub(replaced);
+
+            if *total <= self.disk_max_bytes {
+                return;
+            }
+        }
+
+        *disk_bytes = match Self::evict_disk(dir, self.ttl, self.disk_max_bytes).await {
+            Ok(total) => Some(total),
+            Err(e) => {
+                tracing::warn!("Failed to evict cache files: {e}");
+                None
+            }
+        };
+    }
+
+    async fn remove_disk_bytes(&self, len: u64) {
+        if let Some(total) = &mut *self.disk_bytes.lock().await {
+            *total = total.saturating_sub(len);
+        }
+    }
+
+    /// Removes expired files, then the least recently used ones until the directory fits in
+    /// `max_bytes`. Returns the size of the remaining files.
+    async fn evict_disk(dir: &Path, ttl: Duration, max_bytes: u64) -> std::io::Result<u64> {
+        let mut files = vec![];
+        let mut read_dir = tokio::fs::read_dir(dir).await?;
+
+        while let Some(entry) = read_dir.next_entry().await? {
+            let path = entry.path();
+
+            if path.extension().is_none_or(|ext| ext != "wav") {
+                continue;
+            }
+
+            let metadata = entry.metadata().await?;
+            let modified = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+
+            if modified.elapsed().unwrap_or_default() >= ttl {
+                tokio::fs::remove_file(&path).await?;
+                continue;
+            }
+
+            let accessed = metadata.accessed().unwrap_or(modified);
+            files.push((accessed, metadata.len(), path));
+        }
+
+        let mut total: u64 = files.iter().map(|(_, len, _)| len).sum();
+        files.sort_by_key(|(accessed, _, _)| *accessed);
+
+        for (_, len, path) in files {
+            if total <= max_bytes {
+                break;
+            }
+
+            tokio::fs::remove_file(&path).await?;
+            total -= len;
+        }
+
+        Ok(total)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::TtsParams;
+
+    fn audio(bytes: usize) -> Audio {
+        Audio {
+            samples: vec![0; bytes / 2],
+            ..Audio::silence()
+        }
+    }
+
+    fn config() -> CacheConfig {
+        toml::from_str("").unwrap()
+    }
+
+    fn style(style_id: &str, params: TtsParams) -> TtsStyle {
+        TtsStyle {
+            service_id: "VOICEVOX".to_string(),
+            style_id: style_id.to_string(),
+            params,
+        }
+    }
+
+    #[test]
+    fn test_cache_key() {
+        let default = style("0", TtsParams::default());
+        let fast = style(
+            "0",
+            TtsParams {
+                speed: Some(1.5),
+                ..TtsParams::default()
+            },
+        );
+
+        assert_eq!(
+            cache_key(&default, "おはよう"),
+            cache_key(&default, " おはよう\n")
+        );
+        assert_ne!(
+            cache_key(&default, "おはよう"),
+            cache_key(&style("1", TtsParams::default()), "おはよう")
+        );
+        assert_ne!(
+            cache_key(&default, "おはよう"),
+            cache_key(&fast, "おはよう")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_memory_limits() {
+        let cache = TtsCache::new(
+            &CacheConfig {
+                max_entries: 2,
+                max_bytes: 10,
+                ..config()
+            },
+            None,
+        );
+
+        cache.put("a", audio(4)).await;
+        cache.put("b", audio(4)).await;
+        assert!(cache.get("a").await.is_some());
+
+        // "b" is the least recently used one
+        cache.put("c", audio(4)).await;
+        assert!(cache.get("b").await.is_none());
+        assert!(cache.get("a").await.is_some());
+
+        // exceeds max_bytes
+        cache.put("d", audio(8)).await;
+        assert!(cache.get("a").await.is_none());
+        assert!(cache.get("c").await.is_none());
+        assert!(cache.get("d").await.is_some());
+
+        cache.put("e", audio(12)).await;
+        assert!(cache.get("e").await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_ttl() {
+        let cache = TtsCache::new(&CacheConfig { ttl: 0, ..config() }, None);
+
+        cache.put("a", audio(4)).await;
+        assert!(cache.get("a").await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_disk_limits() {
+        let dir = std::env::temp_dir().join(format!("cache-{}", uuid::Uuid::new_v4()));
+        let wav_len = audio(4000).to_wav().unwrap().len() as u64;
+
+        let cache = TtsCache::new(
+            &CacheConfig {
+                // Read from the disk every time.
+                max_bytes: 0,
+                disk: true,
+                disk_max_bytes: wav_len * 2,
+                ..config()
+            },
+            Some(dir.clone()),
+        );
+
+        let disk_size = || {
+            std::fs::read_dir(&dir)
+                .unwrap()
+                .map(|entry| entry.unwrap().metadata().unwrap().len())
+                .sum::<u64>()
+        };
+
+        cache.put("a", audio(4000)).await;
+        tokio::join!(cache.put("a", audio(4000)), cache.put("a", audio(4000)));
+        cache.put("b", audio(4000)).await;
+        assert_eq!(*cache.disk_bytes.lock().await, Some(wav_len * 2));
+        assert_eq!(disk_size(), wav_len * 2);
+
+        // The least recently used one is evicted. File times are coarser than the clock.
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(cache.get("a").await.is_some());
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        cache.put("c", audio(4000)).await;
+        assert_eq!(*cache.disk_bytes.lock().await, Some(wav_len * 2));
+        assert_eq!(disk_size(), wav_len * 2);
+        assert!(cache.get("a").await.is_some());
+        assert!(cache.get("b").await.is_none());
+
+        cache.remove_matching("").await;
+        assert_eq!(*cache.disk_bytes.lock().await, Some(0));
+        assert_eq!(disk_size(), 0);
+
+        std::fs::remove_dir(&dir).unwrap();
+    }
+}
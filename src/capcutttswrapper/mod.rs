@@ -10,7 +10,9 @@ use reqwest::{
 use serde::Deserialize;
 use tap::Tap;
 
-use crate::tts::{CharacterView, StyleView, TtsService};
+use crate::audio::Audio;
+use crate::model::TtsParams;
+use crate::tts::{CharacterView, StyleView, TtsService, probe};
 
 mod api;
 
@@ -22,7 +24,7 @@ fn default_headers() -> HashMap<String, String> {
     HashMap::new()
 }
 
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Deserialize, Debug, Clone, PartialEq)]
 pub struct Setting {
     pub url: reqwest::Url,
     #[serde(default = "default_headers")]
@@ -45,7 +47,7 @@ struct CapCutTTSWrapperInner {
 
 #[async_trait]
 impl TtsService for CapCutTTSWrapper {
-    async fn tts(&self, style_id: &str, text: &str) -> Result<Vec<u8>> {
+    async fn tts(&self, style_id: &str, text: &str, _params: &TtsParams) -> Result<Audio> {
         let url = self.inner.host.clone().tap_mut(|u| {
             u.path_segments_mut().unwrap().push("v2").push("synthesize");
         });
@@ -68,7 +70,15 @@ impl TtsService for CapCutTTSWrapper {
             .await
             .context("Failed to post /v2/synthesize (body)")?;
 
-        crate::tts::convert_mp3_to_wav(resp.to_vec(), self.inner.master_volume)
+        Ok(Audio::from_mp3(resp.to_vec())?.gain(self.inner.master_volume))
+    }
+
+    async fn health_check(&self) -> Option<Result<()>> {
+        let url = self.inner.host.clone().tap_mut(|u| {
+            u.path_segments_mut().unwrap().push("v2").push("speakers");
+        });
+
+        Some(probe(&self.inner.client, url).await)
     }
 
     async fn styles(&self) -> Result<Vec<CharacterView>> {
@@ -99,7 +109,11 @@ impl TtsService for CapCutTTSWrapper {
                         icon: vec![],
                         id: speaker.id,
                         name: "default".to_string(),
+                        language: None,
+                        gender: None,
                     }],
+                    language: None,
+                    gender: None,
                 })
             })
             .collect()
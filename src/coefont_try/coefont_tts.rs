@@ -1,10 +1,10 @@
-use std::io::Cursor;
-
 use anyhow::{Context, Result};
 use futures::future::join_all;
 use reqwest::header::{CONTENT_TYPE, HeaderMap, HeaderValue, ORIGIN, REFERER, USER_AGENT};
 use serde::{Deserialize, Serialize};
 
+use crate::audio::Audio;
+
 #[derive(Debug, Clone)]
 pub struct CoefontVoice {
     pub name: &'static str,
@@ -89,7 +89,7 @@ struct TtsResponse {
 
 const COEFONT_MAX_CHARS: usize = 30;
 
-pub async fn get_audio_bytes(text: &str, voice_id: &str, volume: f32) -> Result<Vec<u8>> {
+pub async fn get_audio(text: &str, voice_id: &str, volume: f32) -> Result<Audio> {
     let parts = crate::tts::split_long_text(text, COEFONT_MAX_CHARS);
 
     let futures: Vec<_> = parts
@@ -99,72 +99,12 @@ pub async fn get_audio_bytes(text: &str, voice_id: &str, volume: f32) -> Result<
 
     let results = join_all(futures).await;
 
-    let mut wav_parts = Vec::new();
+    let mut audio_parts = Vec::new();
     for result in results {
-        wav_parts.push(result?);
-    }
-
-    if wav_parts.is_empty() {
-        return create_empty_wav();
-    }
-
-    let combined_wav = concatenate_wav_files(wav_parts)?;
-    apply_volume_to_wav(combined_wav, volume)
-}
-
-fn concatenate_wav_files(wav_files: Vec<Vec<u8>>) -> Result<Vec<u8>> {
-    use std::io::Cursor;
-
-    if wav_files.is_empty() {
-        return create_empty_wav();
-    }
-
-    if wav_files.len() == 1 {
-        return Ok(wav_files.into_iter().next().unwrap());
-    }
-
-    let first_reader = hound::WavReader::new(Cursor::new(&wav_files[0]))?;
-    let spec = first_reader.spec();
-
-    let mut all_samples = Vec::new();
-
-    for wav_data in wav_files {
-        let mut reader = hound::WavReader::new(Cursor::new(&wav_data))?;
-        let samples: Vec<i16> = reader.samples::<i16>().collect::<Result<Vec<_>, _>>()?;
-        all_samples.extend(samples);
-    }
-
-    let mut wav_cursor = Cursor::new(Vec::new());
-    let mut wav_writer = hound::WavWriter::new(&mut wav_cursor, spec)?;
-    for sample in all_samples {
-        wav_writer.write_sample(sample)?;
+        audio_parts.push(Audio::from_wav(&result?)?);
     }
-    wav_writer.finalize()?;
-    Ok(wav_cursor.into_inner())
-}
-
-fn apply_volume_to_wav(wav_data: Vec<u8>, volume: f32) -> Result<Vec<u8>> {
-    if (volume - 1.0).abs() < 0.01 {
-        return Ok(wav_data);
-    }
-
-    let mut reader = hound::WavReader::new(Cursor::new(&wav_data))?;
-    let spec = reader.spec();
 
-    #[allow(clippy::cast_possible_truncation)]
-    let samples: Vec<i16> = reader
-        .samples::<i16>()
-        .map(|s| s.unwrap_or(0))
-        .map(|s| (f32::from(s) * volume).clamp(f32::from(i16::MIN), f32::from(i16::MAX)) as i16)
-        .collect();
-
-    let mut wav_cursor = Cursor::new(Vec::new());
-    let mut wav_writer = hound::WavWriter::new(&mut wav_cursor, spec)?;
-    for sample in samples {
-        wav_writer.write_sample(sample)?;
-    }
-    wav_writer.finalize()?;
-    Ok(wav_cursor.into_inner())
+    Ok(Audio::concat(audio_parts)?.gain(volume))
 }
 
 async fn fetch_audio_part(text: String, voice_id: String) -> Result<Vec<u8>> {
@@ -217,18 +157,3 @@ async fn fetch_audio_part(text: String, voice_id: String) -> Result<Vec<u8>> {
 
     Ok(audio_bytes.to_vec())
 }
-
-fn create_empty_wav() -> Result<Vec<u8>> {
-    use std::io::Cursor;
-
-    let spec = hound::WavSpec {
-        channels: 1,
-        sample_rate: 24000,
-        bits_per_sample: 16,
-        sample_format: hound::SampleFormat::Int,
-    };
-    let mut wav_cursor = Cursor::new(Vec::new());
-    let wav_writer = hound::WavWriter::new(&mut wav_cursor, spec)?;
-    wav_writer.finalize()?;
-    Ok(wav_cursor.into_inner())
-}
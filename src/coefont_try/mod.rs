@@ -4,16 +4,18 @@ use anyhow::Result;
 use async_trait::async_trait;
 use serde::Deserialize;
 
+use crate::audio::Audio;
+use crate::model::TtsParams;
 use crate::tts::{CharacterView, StyleView, TtsService};
 
 mod coefont_tts;
-use coefont_tts::{VOICES, get_audio_bytes};
+use coefont_tts::{VOICES, get_audio};
 
 fn default_master_volume() -> f32 {
     1.0
 }
 
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Deserialize, Debug, Clone, PartialEq)]
 pub struct Setting {
     #[serde(default = "default_master_volume")]
     pub master_volume: f32,
@@ -41,8 +43,8 @@ impl CoefontTry {
 
 #[async_trait]
 impl TtsService for CoefontTry {
-    async fn tts(&self, style_id: &str, text: &str) -> Result<Vec<u8>> {
-        get_audio_bytes(text, style_id, self.inner.master_volume).await
+    async fn tts(&self, style_id: &str, text: &str, _params: &TtsParams) -> Result<Audio> {
+        get_audio(text, style_id, self.inner.master_volume).await
     }
 
     async fn styles(&self) -> Result<Vec<CharacterView>> {
@@ -58,6 +60,8 @@ impl TtsService for CoefontTry {
                     name: voice.name.to_string(),
                     id: voice.id.to_string(),
                     icon: vec![],
+                    language: None,
+                    gender: None,
                 });
             }
 
@@ -66,6 +70,8 @@ impl TtsService for CoefontTry {
                     name: lang_name.to_string(),
                     policy: "Coefont Terms of Service".to_string(),
                     styles,
+                    language: Some(lang_id.to_string()),
+                    gender: None,
                 });
             }
         }
@@ -0,0 +1,349 @@
+use std::fmt::Write as _;
+
+use serenity::{
+    all::{CommandOptionType, Permissions, ResolvedOption, ResolvedValue},
+    builder::{CreateCommand, CreateCommandOption},
+    client::Context,
+    model::application::CommandInteraction,
+};
+
+use crate::commands::simple_resp_helper;
+use crate::db::PERSISTENT_DB;
+use crate::live_config;
+use crate::locale::{Locale, Text, localize_command};
+use crate::model::{
+    GuildSettings, LiveConfig, TimeStretchConfig, TimeStretchSettings, TtsParams, TtsStyle,
+};
+use crate::tts::TtsServices;
+
+/// Value of `suppress_prefix` which disables the suppression, as Discord rejects empty strings.
+const SUPPRESS_PREFIX_NONE: &str = "none";
+
+const SETTINGS: [(&str, &str); 6] = [
+    ("default_style", "Default voice"),
+    ("auto_leave_when_alone", "Auto leave"),
+    ("announce_join_leave", "Join/leave announcements"),
+    ("suppress_prefix", "Suppression prefix"),
+    ("timestretch", "Time stretch"),
+    ("locale", "Language"),
+];
+
+pub fn register(prefix: &str) -> CreateCommand {
+    let reset = SETTINGS.iter().fold(
+        CreateCommandOption::new(
+            CommandOptionType::String,
+            "setting",
+            "Setting to reset (all if omitted)",
+        ),
+        |option, (value, name)| option.add_string_choice(*name, *value),
+    );
+
+    let language = Locale::ALL.iter().fold(
+        CreateCommandOption::new(
+            CommandOptionType::String,
+            "language",
+            "Language of the messages, unless a user speaks in another one",
+        ),
+        |option, locale| option.add_string_choice(locale.name(), locale.code()),
+    );
+
+    let command = CreateCommand::new(format!("{prefix}config"))
+        .description("Configure the bot for this server")
+        .dm_permission(false)
+        .default_member_permissions(Permissions::MANAGE_GUILD)
+        .add_option(CreateCommandOption::new(
+            CommandOptionType::SubCommand,
+            "show",
+            "Show the settings of this server",
+        ))
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::SubCommand,
+                "set",
+                "Override settings for this server",
+            )
+            .add_sub_option(CreateCommandOption::new(
+                CommandOptionType::String,
+                "service",
+                "Service of the default voice",
+            ))
+            .add_sub_option(CreateCommandOption::new(
+                CommandOptionType::String,
+                "style",
+                "Style ID of the default voice",
+            ))
+            .add_sub_option(CreateCommandOption::new(
+                CommandOptionType::Boolean,
+                "auto_leave",
+                "Leave when no one is in the voice channel",
+            ))
+            .add_sub_option(CreateCommandOption::new(
+                CommandOptionType::Boolean,
+                "announce",
+                "Read aloud when a user joins or leaves",
+            ))
+            .add_sub_option(CreateCommandOption::new(
+                CommandOptionType::String,
+                "suppress_prefix",
+                "Messages starting with this are not read aloud (`none` to disable)",
+            ))
+            .add_sub_option(
+                CreateCommandOption::new(
+                    CommandOptionType::Number,
+                    "timestretch_speed",
+                    "Speed reached by long messages",
+                )
+                .min_number_value(*TimeStretchConfig::SPEED_RANGE.start())
+                .max_number_value(*TimeStretchConfig::SPEED_RANGE.end()),
+            )
+            .add_sub_option(
+                CreateCommandOption::new(
+                    CommandOptionType::Number,
+                    "timestretch_ramp",
+                    "Seconds to reach the speed",
+                )
+                .min_number_value(0.0),
+            )
+            .add_sub_option(
+                CreateCommandOption::new(
+                    CommandOptionType::Number,
+                    "timestretch_delay",
+                    "Seconds before speeding up",
+                )
+                .min_number_value(0.0),
+            )
+            .add_sub_option(language),
+        )
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::SubCommand,
+                "reset",
+                "Follow the bot's config again",
+            )
+            .add_sub_option(reset),
+        );
+
+    localize_command(command, prefix, Text::ConfigName, Text::ConfigDescription)
+}
+
+fn get_string<'a>(options: &'a [ResolvedOption], name: &str) -> Option<&'a str> {
+    options.iter().find_map(|o| match o.value {
+        ResolvedValue::String(v) if o.name == name => Some(v),
+        _ => None,
+    })
+}
+
+fn get_bool(options: &[ResolvedOption], name: &str) -> Option<bool> {
+    options.iter().find_map(|o| match o.value {
+        ResolvedValue::Boolean(v) if o.name == name => Some(v),
+        _ => None,
+    })
+}
+
+fn get_number(options: &[ResolvedOption], name: &str) -> Option<f64> {
+    options.iter().find_map(|o| match o.value {
+        ResolvedValue::Number(v) if o.name == name => Some(v),
+        _ => None,
+    })
+}
+
+fn format_settings(settings: &GuildSettings, config: &LiveConfig) -> String {
+    let overridden = |is_set: bool| if is_set { "" } else { " (default)" };
+
+    let mut text = String::new();
+
+    let _ = writeln!(
+        text,
+        "Default voice: `{}/{}`{}",
+        config.default_style.service_id,
+        config.default_style.style_id,
+        overridden(settings.default_style.is_some()),
+    );
+    let _ = writeln!(
+        text,
+        "Auto leave: {}{}",
+        config.auto_leave_when_alone,
+        overridden(settings.auto_leave_when_alone.is_some()),
+    );
+    let _ = writeln!(
+        text,
+        "Join/leave announcements: {}{}",
+        config.announce_join_leave,
+        overridden(settings.announce_join_leave.is_some()),
+    );
+
+    let prefix = if config.suppress_prefix.is_empty() {
+        SUPPRESS_PREFIX_NONE.to_string()
+    } else {
+        format!("`{}`", config.suppress_prefix)
+    };
+    let _ = writeln!(
+        text,
+        "Suppression prefix: {prefix}{}",
+        overridden(settings.suppress_prefix.is_some()),
+    );
+
+    let _ = writeln!(
+        text,
+        "Language: {}{}",
+        config.locale.name(),
+        overridden(settings.locale.is_some()),
+    );
+
+    let _ = write!(
+        text,
+        "Time stretch: x{} after {}s, over {}s{}",
+        config.timestretch.target_speed,
+        config.timestretch.initial_delay,
+        config.timestretch.ramp_duration,
+        overridden(!settings.timestretch.is_default()),
+    );
+
+    text
+}
+
+async fn set(
+    options: &[ResolvedOption<'_>],
+    settings: &mut GuildSettings,
+    tts_services: &TtsServices,
+) -> Result<(), String> {
+    match (get_string(options, "service"), get_string(options, "style")) {
+        (None, None) => {}
+        (Some(service_id), Some(style_id)) => {
+            if !tts_services.is_available(service_id, style_id).await {
+                return Err(format!("`{service_id}/{style_id}` is not available."));
+            }
+
+            settings.default_style = Some(TtsStyle {
+                service_id: service_id.to_string(),
+                style_id: style_id.to_string(),
+                params: TtsParams::default(),
+            });
+        }
+        _ => return Err("Specify both `service` and `style`.".to_string()),
+    }
+
+    if let Some(v) = get_bool(options, "auto_leave") {
+        settings.auto_leave_when_alone = Some(v);
+    }
+
+    if let Some(v) = get_bool(options, "announce") {
+        settings.announce_join_leave = Some(v);
+    }
+
+    if let Some(v) = get_string(options, "suppress_prefix") {
+        settings.suppress_prefix = Some(if v == SUPPRESS_PREFIX_NONE {
+            String::new()
+        } else {
+            v.to_string()
+        });
+    }
+
+    if let Some(v) = get_string(options, "language") {
+        settings.locale = Locale::from_language(v);
+    }
+
+    // Values which are not given keep following the config file.
+    if let Some(v) = get_number(options, "timestretch_speed") {
+        let range = TimeStretchConfig::SPEED_RANGE;
+
+        if !range.contains(&v) {
+            return Err(format!(
+                "`timestretch_speed` must be between {} and {}.",
+                range.start(),
+                range.end()
+            ));
+        }
+
+        settings.timestretch.target_speed = Some(v);
+    }
+
+    if let Some(v) = get_number(options, "timestretch_ramp") {
+        settings.timestretch.ramp_duration = Some(v);
+    }
+
+    if let Some(v) = get_number(options, "timestretch_delay") {
+        settings.timestretch.initial_delay = Some(v);
+    }
+
+    Ok(())
+}
+
+fn reset(settings: &mut GuildSettings, setting: Option<&str>) {
+    match setting {
+        None => *settings = GuildSettings::default(),
+        Some("default_style") => settings.default_style = None,
+        Some("auto_leave_when_alone") => settings.auto_leave_when_alone = None,
+        Some("announce_join_leave") => settings.announce_join_leave = None,
+        Some("suppress_prefix") => settings.suppress_prefix = None,
+        Some("timestretch") => settings.timestretch = TimeStretchSettings::default(),
+        Some("locale") => settings.locale = None,
+        Some(v) => unreachable!("Unknown setting: {v}"),
+    }
+}
+
+pub async fn run(ctx: &Context, interaction: CommandInteraction, tts_services: &TtsServices) {
+    let guild_id = interaction.guild_id.unwrap();
+
+    let options = interaction.data.options();
+    let Some(ResolvedOption {
+        name,
+        value: ResolvedValue::SubCommand(options),
+        ..
+    }) = options.first()
+    else {
+        unreachable!("Illegal config call");
+    };
+
+    let mut settings = PERSISTENT_DB.get_guild_settings(guild_id);
+
+    match *name {
+        "show" => {}
+        "set" => {
+            if let Err(e) = set(options, &mut settings, tts_services).await {
+                simple_resp_helper(&interaction, ctx, &e, true).await;
+                return;
+            }
+
+            PERSISTENT_DB.store_guild_settings(guild_id, &settings);
+        }
+        "reset" => {
+            reset(&mut settings, get_string(options, "setting"));
+            PERSISTENT_DB.store_guild_settings(guild_id, &settings);
+        }
+        _ => unreachable!("Illegal config call"),
+    }
+
+    let text = format_settings(&settings, &live_config().with_guild(&settings));
+    simple_resp_helper(&interaction, ctx, &text, true).await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reset() {
+        let mut settings = GuildSettings {
+            auto_leave_when_alone: Some(true),
+            suppress_prefix: Some(String::new()),
+            ..Default::default()
+        };
+
+        reset(&mut settings, Some("suppress_prefix"));
+        assert_eq!(settings.suppress_prefix, None);
+        assert_eq!(settings.auto_leave_when_alone, Some(true));
+
+        reset(&mut settings, None);
+        assert!(settings.is_default());
+
+        settings.timestretch.target_speed = Some(2.0);
+        reset(&mut settings, Some("timestretch"));
+        assert!(settings.is_default());
+
+        // Every choice is handled.
+        for (setting, _) in SETTINGS {
+            reset(&mut settings, Some(setting));
+        }
+    }
+}
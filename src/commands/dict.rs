@@ -0,0 +1,154 @@
+use std::fmt::Write as _;
+
+use serenity::{
+    all::{CommandOptionType, Permissions, ResolvedOption, ResolvedValue},
+    builder::{CreateCommand, CreateCommandOption},
+    client::Context,
+    model::application::CommandInteraction,
+};
+
+use crate::commands::simple_resp_helper;
+use crate::db::PERSISTENT_DB;
+use crate::dictionary::{DictEntry, Dictionary};
+use crate::locale::{Text, localize_command};
+
+// Discord rejects messages longer than 2000 characters.
+const LIST_MAX_CHARS: usize = 1900;
+
+pub fn register(prefix: &str) -> CreateCommand {
+    let command = CreateCommand::new(format!("{prefix}dict"))
+        .description("Manage the pronunciation dictionary of this server")
+        .dm_permission(false)
+        // Entries rewrite what is read aloud to everyone.
+        .default_member_permissions(Permissions::MANAGE_GUILD)
+        .add_option(
+            CreateCommandOption::new(
+                CommandOptionType::SubCommand,
+                "add",
+                "Add or replace a word",
+            )
+            .add_sub_option(
+                CreateCommandOption::new(CommandOptionType::String, "word", "Word to replace")
+                    .required(true),
+            )
+            .add_sub_option(
+                CreateCommandOption::new(CommandOptionType::String, "reading", "Reading")
+                    .required(true),
+            )
+            .add_sub_option(CreateCommandOption::new(
+                CommandOptionType::Boolean,
+                "regex",
+                "Treat the word as a regular expression",
+            )),
+        )
+        .add_option(
+            CreateCommandOption::new(CommandOptionType::SubCommand, "remove", "Remove a word")
+                .add_sub_option(
+                    CreateCommandOption::new(CommandOptionType::String, "word", "Word to remove")
+                        .required(true),
+                ),
+        );
+
+    localize_command(command, prefix, Text::DictName, Text::DictDescription)
+}
+
+/// Listing is a separate command, so that it stays open to everyone.
+pub fn register_list(prefix: &str) -> CreateCommand {
+    let command = CreateCommand::new(format!("{prefix}dict_list"))
+        .description("List the words in the pronunciation dictionary of this server")
+        .dm_permission(false);
+
+    localize_command(
+        command,
+        prefix,
+        Text::DictListName,
+        Text::DictListDescription,
+    )
+}
+
+fn get_string<'a>(options: &'a [ResolvedOption], name: &str) -> Option<&'a str> {
+    options.iter().find_map(|o| match o.value {
+        ResolvedValue::String(v) if o.name == name => Some(v),
+        _ => None,
+    })
+}
+
+fn get_bool(options: &[ResolvedOption], name: &str) -> Option<bool> {
+    options.iter().find_map(|o| match o.value {
+        ResolvedValue::Boolean(v) if o.name == name => Some(v),
+        _ => None,
+    })
+}
+
+fn format_list(entries: &[DictEntry]) -> String {
+    if entries.is_empty() {
+        return "No words are registered.".to_string();
+    }
+
+    let mut ret = String::new();
+
+    for (i, entry) in entries.iter().enumerate() {
+        let line = if entry.is_regex {
+            format!("`/{}/` → {}\n", entry.word, entry.reading)
+        } else {
+            format!("`{}` → {}\n", entry.word, entry.reading)
+        };
+
+        if ret.chars().count() + line.chars().count() > LIST_MAX_CHARS {
+            let _ = write!(ret, "... and {} more", entries.len() - i);
+            break;
+        }
+
+        ret.push_str(&line);
+    }
+
+    ret
+}
+
+pub async fn run(ctx: &Context, interaction: CommandInteraction) {
+    let guild_id = interaction.guild_id.unwrap();
+
+    let options = interaction.data.options();
+    let Some(ResolvedOption {
+        name,
+        value: ResolvedValue::SubCommand(options),
+        ..
+    }) = options.first()
+    else {
+        unreachable!("Illegal dict call");
+    };
+
+    match *name {
+        "add" => {
+            let entry = DictEntry {
+                word: get_string(options, "word").unwrap().to_string(),
+                reading: get_string(options, "reading").unwrap().to_string(),
+                is_regex: get_bool(options, "regex").unwrap_or(false),
+            };
+
+            if let Err(e) = Dictionary::new(std::slice::from_ref(&entry)) {
+                simple_resp_helper(&interaction, ctx, &format!("{e:#}"), true).await;
+                return;
+            }
+
+            let text = format!("Registered: `{}` → {}", entry.word, entry.reading);
+            PERSISTENT_DB.store_dictionary_entry(guild_id, &entry);
+            simple_resp_helper(&interaction, ctx, &text, false).await;
+        }
+        "remove" => {
+            let word = get_string(options, "word").unwrap();
+
+            if PERSISTENT_DB.remove_dictionary_entry(guild_id, word) {
+                simple_resp_helper(&interaction, ctx, &format!("Removed: `{word}`"), false).await;
+            } else {
+                simple_resp_helper(&interaction, ctx, &format!("Not found: `{word}`"), true).await;
+            }
+        }
+        _ => unreachable!("Unknown dict subcommand: {name}"),
+    }
+}
+
+pub async fn run_list(ctx: &Context, interaction: CommandInteraction) {
+    let entries = PERSISTENT_DB.get_dictionary_entries(interaction.guild_id.unwrap());
+    simple_resp_helper(&interaction, ctx, &format_list(&entries), true).await;
+}
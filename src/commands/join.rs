@@ -3,16 +3,18 @@ use serenity::{
     client::Context,
     model::{Permissions, application::CommandInteraction, id::ChannelId, prelude::Mentionable},
 };
-use songbird::CoreEvent;
 
 use crate::commands::simple_resp_helper;
-use crate::db::INMEMORY_DB;
-use crate::songbird_handler::DriverDisconnectNotifier;
+use crate::guild_config;
+use crate::locale::{Localized, Text, localize_command};
+use crate::session;
 
 pub fn register(prefix: &str) -> CreateCommand {
-    CreateCommand::new(format!("{prefix}join"))
+    let command = CreateCommand::new(format!("{prefix}join"))
         .description("Join to your channel")
-        .dm_permission(false)
+        .dm_permission(false);
+
+    localize_command(command, prefix, Text::JoinName, Text::JoinDescription)
 }
 
 #[allow(clippy::enum_variant_names)]
@@ -24,13 +26,15 @@ enum JoinError {
 }
 
 impl JoinError {
-    fn to_message(&self) -> String {
+    fn to_message(&self, texts: &Localized) -> String {
         match self {
-            Self::YouAreNotInVoiceChannel => "You are not in voice channel".to_string(),
-            Self::FailedToJoinVoiceChannel => "Failed to join to voice channel".to_string(),
-            Self::CannotAccessToTextChannel(id) | Self::CannotAccessToVoiceChannel(id) => {
-                format!("Cannot access to {}", id.mention())
-            }
+            Self::YouAreNotInVoiceChannel => texts.get(Text::YouAreNotInVoiceChannel).to_string(),
+            Self::FailedToJoinVoiceChannel => texts.get(Text::FailedToJoinVoiceChannel).to_string(),
+            Self::CannotAccessToTextChannel(id) | Self::CannotAccessToVoiceChannel(id) => texts
+                .format(
+                    Text::CannotAccessChannel,
+                    &[("channel", &id.mention().to_string())],
+                ),
         }
     }
 }
@@ -69,44 +73,29 @@ async fn run_(
         return Err(JoinError::CannotAccessToVoiceChannel(vc.id));
     }
 
-    let manager = songbird::get(ctx).await.unwrap();
-
-    if let Some(h) = manager.get(guild.id) {
-        h.lock()
-            .await
-            .join(vc.id)
-            .await
-            .map_err(|_| JoinError::FailedToJoinVoiceChannel)?;
-    } else {
-        let h = manager
-            .join(guild.id, vc.id)
-            .await
-            .map_err(|_| JoinError::FailedToJoinVoiceChannel)?;
-
-        h.lock().await.add_global_event(
-            CoreEvent::DriverDisconnect.into(),
-            DriverDisconnectNotifier {
-                songbird_manager: manager,
-            },
-        );
-    }
-
-    INMEMORY_DB.store_instance(guild.id, interaction.channel_id);
+    session::connect(ctx, guild.id, vc.id, interaction.channel_id)
+        .await
+        .map_err(|_| JoinError::FailedToJoinVoiceChannel)?;
 
     Ok((interaction.channel_id, vc.id))
 }
 
 pub async fn run(ctx: &Context, interaction: CommandInteraction) {
+    let config = guild_config(interaction.guild_id.unwrap());
+    let texts = config.texts();
+
     match run_(ctx, &interaction).await {
         Ok((text, voice)) => {
-            simple_resp_helper(
-                &interaction,
-                ctx,
-                &format!("Linked! {} <-> {}", text.mention(), voice.mention()),
-                false,
-            )
-            .await;
+            let message = texts.format(
+                Text::Linked,
+                &[
+                    ("text", &text.mention().to_string()),
+                    ("voice", &voice.mention().to_string()),
+                ],
+            );
+
+            simple_resp_helper(&interaction, ctx, &message, false).await;
         }
-        Err(e) => simple_resp_helper(&interaction, ctx, &e.to_message(), true).await,
+        Err(e) => simple_resp_helper(&interaction, ctx, &e.to_message(&texts), true).await,
     }
 }
@@ -1,24 +1,30 @@
 use serenity::{builder::CreateCommand, client::Context, model::application::CommandInteraction};
 
 use crate::commands::simple_resp_helper;
+use crate::guild_config;
+use crate::locale::{Text, localize_command};
 
 pub fn register(prefix: &str) -> CreateCommand {
-    CreateCommand::new(format!("{prefix}leave"))
+    let command = CreateCommand::new(format!("{prefix}leave"))
         .description("Leave from VC")
-        .dm_permission(false)
+        .dm_permission(false);
+
+    localize_command(command, prefix, Text::LeaveName, Text::LeaveDescription)
 }
 
 pub async fn run(ctx: &Context, interaction: CommandInteraction) {
     let guild_id = interaction.guild_id.unwrap();
+    let config = guild_config(guild_id);
+    let texts = config.texts();
 
     let manager = songbird::get(ctx)
         .await
         .expect("Songbird is not initialized.");
 
     let Ok(()) = manager.leave(guild_id).await else {
-        simple_resp_helper(&interaction, ctx, "Not in a voice channel", true).await;
+        simple_resp_helper(&interaction, ctx, texts.get(Text::NotInVoiceChannel), true).await;
         return;
     };
 
-    simple_resp_helper(&interaction, ctx, "Connection has been closed.", false).await;
+    simple_resp_helper(&interaction, ctx, texts.get(Text::Disconnected), false).await;
 }
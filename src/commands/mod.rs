@@ -5,8 +5,11 @@ use serenity::{
     model::application::CommandInteraction,
 };
 
+pub mod config;
+pub mod dict;
 pub mod join;
 pub mod leave;
+pub mod refresh;
 pub mod skip;
 pub mod speaker;
 
@@ -0,0 +1,85 @@
+use std::fmt::Write as _;
+
+use serenity::{
+    all::Permissions,
+    builder::{CreateCommand, EditInteractionResponse},
+    client::Context,
+    model::application::CommandInteraction,
+};
+
+use crate::db::PERSISTENT_DB;
+use crate::locale::{Text, localize_command};
+use crate::tts::{RefreshReport, TtsServices};
+
+// Discord rejects messages longer than 2000 characters.
+const REPORT_MAX_CHARS: usize = 1900;
+
+pub fn register(prefix: &str) -> CreateCommand {
+    let command = CreateCommand::new(format!("{prefix}refresh"))
+        .description("Re-query the voice catalogs of the TTS services")
+        .dm_permission(false)
+        .default_member_permissions(Permissions::ADMINISTRATOR);
+
+    localize_command(command, prefix, Text::RefreshName, Text::RefreshDescription)
+}
+
+fn format_report(report: &RefreshReport, pending: &[String]) -> String {
+    let mut text = format!(
+        "Refreshed {} services: {} styles added, {} removed.",
+        report.refreshed,
+        report.added,
+        report.removed.len(),
+    );
+
+    for (service_id, style_id) in &report.removed {
+        let users = PERSISTENT_DB.count_voice_settings(service_id, style_id);
+        let line = format!(
+            "\nRemoved: `{service_id}/{style_id}` (selected by {users} users, the default voice is used for them)"
+        );
+
+        if text.len() + line.len() > REPORT_MAX_CHARS {
+            text.push_str("\n…");
+            return text;
+        }
+
+        text.push_str(&line);
+    }
+
+    for (service_id, e) in &report.failed {
+        let _ = write!(text, "\nFailed: `{service_id}` ({e})");
+    }
+
+    for service_id in pending {
+        let _ = write!(text, "\nPending: `{service_id}` (retrying in background)");
+    }
+
+    if text.len() > REPORT_MAX_CHARS {
+        let mut end = REPORT_MAX_CHARS;
+        while !text.is_char_boundary(end) {
+            end -= 1;
+        }
+        text.truncate(end);
+        text.push('…');
+    }
+
+    text
+}
+
+pub async fn run(ctx: &Context, interaction: CommandInteraction, tts_services: &TtsServices) {
+    // Fetching every catalog can take longer than the 3 seconds to respond.
+    interaction
+        .defer_ephemeral(&ctx.http)
+        .await
+        .expect("Failed to write response");
+
+    let report = tts_services.refresh().await;
+    report.log();
+
+    interaction
+        .edit_response(
+            &ctx.http,
+            EditInteractionResponse::new().content(format_report(&report, &tts_services.pending())),
+        )
+        .await
+        .expect("Failed to write response");
+}
@@ -1,25 +1,32 @@
 use serenity::{builder::CreateCommand, client::Context, model::application::CommandInteraction};
 
 use crate::commands::simple_resp_helper;
+use crate::guild_config;
+use crate::locale::{Text, localize_command};
+use crate::pipeline::skip;
 
 pub fn register(prefix: &str) -> CreateCommand {
-    CreateCommand::new(format!("{prefix}skip"))
+    let command = CreateCommand::new(format!("{prefix}skip"))
         .description("Skip a current message")
-        .dm_permission(false)
+        .dm_permission(false);
+
+    localize_command(command, prefix, Text::SkipName, Text::SkipDescription)
 }
 
 pub async fn run(ctx: &Context, interaction: CommandInteraction) {
     let guild_id = interaction.guild_id.unwrap();
+    let config = guild_config(guild_id);
+    let texts = config.texts();
 
     let manager = songbird::get(ctx)
         .await
         .expect("Songbird is not initialized.");
 
     let Some(handler) = manager.get(guild_id) else {
-        simple_resp_helper(&interaction, ctx, "Not in a voice channel.", true).await;
+        simple_resp_helper(&interaction, ctx, texts.get(Text::NotInVoiceChannel), true).await;
         return;
     };
 
-    handler.lock().await.queue().skip().expect("Failed to skip");
-    simple_resp_helper(&interaction, ctx, "Skipped!", true).await;
+    skip(handler.lock().await.queue()).expect("Failed to skip");
+    simple_resp_helper(&interaction, ctx, texts.get(Text::Skipped), true).await;
 }
@@ -1,50 +1,215 @@
+use std::collections::HashMap;
+use std::ops::RangeInclusive;
+
 use serenity::{
-    all::{ComponentInteraction, ComponentInteractionData, ComponentInteractionDataKind},
+    all::{
+        ActionRowComponent, ComponentInteraction, ComponentInteractionData,
+        ComponentInteractionDataKind, InputTextStyle, ModalInteraction,
+    },
     builder::{
         CreateActionRow, CreateAttachment, CreateButton, CreateCommand, CreateEmbed,
-        CreateEmbedAuthor, CreateInteractionResponse, CreateInteractionResponseMessage,
-        CreateSelectMenu, CreateSelectMenuKind, CreateSelectMenuOption,
+        CreateEmbedAuthor, CreateInputText, CreateInteractionResponse,
+        CreateInteractionResponseMessage, CreateModal, CreateSelectMenu, CreateSelectMenuKind,
+        CreateSelectMenuOption,
     },
     client::Context,
-    model::application::CommandInteraction,
+    model::{
+        application::CommandInteraction,
+        id::{GuildId, UserId},
+    },
 };
 
-use crate::{DEFAULT_TTS_STYLE, db::PERSISTENT_DB, model::TtsStyle, tts::TtsServices};
+use crate::{
+    db::PERSISTENT_DB,
+    guild_config,
+    locale::{Text, localize_command},
+    model::{TtsParams, TtsStyle},
+    tts::{Capabilities, CharacterView, Gender, TtsServices},
+};
 
 const PAGE_SIZE: usize = 25;
 
 pub fn register(prefix: &str) -> CreateCommand {
-    CreateCommand::new(format!("{prefix}speaker"))
+    let command = CreateCommand::new(format!("{prefix}speaker"))
         .description("Manage your speaker")
-        .dm_permission(false)
+        .dm_permission(false);
+
+    localize_command(command, prefix, Text::SpeakerName, Text::SpeakerDescription)
 }
 
 pub async fn run(ctx: &Context, interaction: CommandInteraction, tts_services: &TtsServices) {
+    let guild_id = interaction.guild_id.unwrap();
     let voice_setting = PERSISTENT_DB
         .get_voice_setting(interaction.user.id)
-        .unwrap_or(DEFAULT_TTS_STYLE.get().unwrap().clone());
+        .unwrap_or_else(|| guild_config(guild_id).default_style);
 
     interaction
         .create_response(
             &ctx.http,
             CreateInteractionResponse::Message(
-                create_modal(tts_services, &voice_setting, true).await,
+                create_modal(tts_services, guild_id, &voice_setting, true).await,
             ),
         )
         .await
         .unwrap();
 }
 
-fn parse_tts_style(s: &str) -> TtsStyle {
+fn stored_params(user: UserId) -> TtsParams {
+    PERSISTENT_DB
+        .get_voice_setting(user)
+        .map(|setting| setting.params)
+        .unwrap_or_default()
+}
+
+fn parse_tts_style(s: &str, params: TtsParams) -> TtsStyle {
     let (service_id, style_id) = s
         .split_once("_!DISCORDTTS!_")
         .expect("UNKNOWN TTS TYLE FORMAT");
     TtsStyle {
         service_id: service_id.to_string(),
         style_id: style_id.to_string(),
+        params,
     }
 }
 
+fn format_param(value: Option<f32>) -> String {
+    value.map_or_else(|| "default".to_string(), |v| format!("{v}"))
+}
+
+fn format_params(params: &TtsParams) -> String {
+    format!(
+        "Speed: {} / Pitch: {} / Intonation: {} / Volume: {}",
+        format_param(params.speed),
+        format_param(params.pitch),
+        format_param(params.intonation),
+        format_param(params.volume),
+    )
+}
+
+/// Parses a value of the modal, which is checked by `TtsParams::validate` afterwards.
+fn parse_param(name: &str, value: Option<&str>) -> Result<Option<f32>, String> {
+    let Some(value) = value.map(str::trim).filter(|v| !v.is_empty()) else {
+        return Ok(None);
+    };
+
+    let value: f32 = value
+        .parse()
+        .map_err(|_| format!("{name} must be a number"))?;
+
+    #[allow(clippy::float_cmp)]
+    Ok((value != 1.0).then_some(value))
+}
+
+fn format_tags(language: Option<&str>, gender: Option<Gender>) -> Option<String> {
+    match (language, gender) {
+        (None, None) => None,
+        (Some(language), None) => Some(language.to_string()),
+        (None, Some(gender)) => Some(gender.to_string()),
+        (Some(language), Some(gender)) => Some(format!("{language} / {gender}")),
+    }
+}
+
+// Discord rejects select menu option descriptions longer than 100 characters.
+const DESCRIPTION_MAX_CHARS: usize = 100;
+
+fn format_languages(languages: &[String]) -> Option<String> {
+    if languages.is_empty() {
+        return None;
+    }
+
+    let joined = languages.join(", ");
+
+    if joined.chars().count() <= DESCRIPTION_MAX_CHARS {
+        return Some(joined);
+    }
+
+    let truncated: String = joined.chars().take(DESCRIPTION_MAX_CHARS - 1).collect();
+    Some(format!("{truncated}…"))
+}
+
+fn format_capabilities(capabilities: Capabilities) -> String {
+    let native: Vec<_> = [
+        ("Speed", capabilities.params.speed),
+        ("Pitch", capabilities.params.pitch),
+        ("Intonation", capabilities.params.intonation),
+        ("Volume", capabilities.params.volume),
+    ]
+    .into_iter()
+    .filter_map(|(name, supported)| supported.then_some(name))
+    .collect();
+
+    let mut features = vec![if native.is_empty() {
+        "No native parameters".to_string()
+    } else {
+        format!("Native: {}", native.join(", "))
+    }];
+
+    if capabilities.ssml {
+        features.push("SSML".to_string());
+    }
+
+    if capabilities.per_style_volume {
+        features.push("Per-style volume".to_string());
+    }
+
+    features.join(" / ")
+}
+
+/// Pitch and intonation are only offered when the backend applies them, since they cannot be
+/// emulated.
+fn create_params_modal(
+    custom_id: &str,
+    params: &TtsParams,
+    capabilities: Capabilities,
+) -> CreateModal {
+    let input = |label: &str, id: &str, value: Option<f32>, range: &RangeInclusive<f32>| {
+        let input = CreateInputText::new(InputTextStyle::Short, label, id)
+            .placeholder(format!("1.0 ({} - {})", range.start(), range.end()))
+            .required(false);
+
+        let input = match value {
+            Some(v) => input.value(format!("{v}")),
+            None => input,
+        };
+
+        CreateActionRow::InputText(input)
+    };
+
+    let mut components = vec![input(
+        "Speed",
+        "speed",
+        params.speed,
+        &TtsParams::SPEED_RANGE,
+    )];
+
+    if capabilities.params.pitch {
+        components.push(input(
+            "Pitch",
+            "pitch",
+            params.pitch,
+            &TtsParams::PITCH_RANGE,
+        ));
+    }
+
+    if capabilities.params.intonation {
+        components.push(input(
+            "Intonation",
+            "intonation",
+            params.intonation,
+            &TtsParams::INTONATION_RANGE,
+        ));
+    }
+
+    components.push(input(
+        "Volume",
+        "volume",
+        params.volume,
+        &TtsParams::VOLUME_RANGE,
+    ));
+
+    CreateModal::new(custom_id, "Voice parameters").components(components)
+}
+
 pub async fn update(ctx: &Context, interaction: ComponentInteraction, tts_services: &TtsServices) {
     let (style, editable) = match &interaction.data {
         ComponentInteractionData {
@@ -57,15 +222,38 @@ pub async fn update(ctx: &Context, interaction: ComponentInteraction, tts_servic
                 unreachable!("Illegal style_selector call");
             };
 
-            (parse_tts_style(values.first().unwrap()), true)
+            let params = stored_params(interaction.user.id);
+            (parse_tts_style(values.first().unwrap(), params), true)
         }
         ComponentInteractionData { custom_id, .. } if custom_id.starts_with("apply_") => {
             let (_apply, style) = custom_id.split_once('_').unwrap();
-            let style = parse_tts_style(style);
+            let style = parse_tts_style(style, stored_params(interaction.user.id));
             PERSISTENT_DB.store_style_id(interaction.user.id, &style);
 
             (style, false)
         }
+        ComponentInteractionData { custom_id, .. } if custom_id.starts_with("params_") => {
+            let (_params, style) = custom_id.split_once('_').unwrap();
+            let style = parse_tts_style(style, stored_params(interaction.user.id));
+            let capabilities = tts_services
+                .capabilities(&style.service_id)
+                .await
+                .unwrap_or_default();
+
+            interaction
+                .create_response(
+                    &ctx.http,
+                    CreateInteractionResponse::Modal(create_params_modal(
+                        custom_id,
+                        &style.params,
+                        capabilities,
+                    )),
+                )
+                .await
+                .unwrap();
+
+            return;
+        }
         _ => unimplemented!(),
     };
 
@@ -73,29 +261,180 @@ pub async fn update(ctx: &Context, interaction: ComponentInteraction, tts_servic
         .create_response(
             &ctx.http,
             CreateInteractionResponse::UpdateMessage(
-                create_modal(tts_services, &style, editable).await,
+                create_modal(
+                    tts_services,
+                    interaction.guild_id.unwrap(),
+                    &style,
+                    editable,
+                )
+                .await,
             ),
         )
         .await
         .unwrap();
 }
 
+pub async fn update_params(
+    ctx: &Context,
+    interaction: ModalInteraction,
+    tts_services: &TtsServices,
+) {
+    let (_params, style) = interaction.data.custom_id.split_once('_').unwrap();
+
+    let value = |id: &str| {
+        interaction
+            .data
+            .components
+            .iter()
+            .flat_map(|row| row.components.iter())
+            .find_map(|component| match component {
+                ActionRowComponent::InputText(input) if input.custom_id == id => {
+                    input.value.as_deref()
+                }
+                _ => None,
+            })
+    };
+
+    let stored = stored_params(interaction.user.id);
+    let capabilities = tts_services
+        .capabilities(&parse_tts_style(style, stored).service_id)
+        .await
+        .unwrap_or_default();
+
+    // Values which were not offered in the modal are kept as they are.
+    let params = (|| {
+        let params = TtsParams {
+            speed: parse_param("Speed", value("speed"))?,
+            pitch: if capabilities.params.pitch {
+                parse_param("Pitch", value("pitch"))?
+            } else {
+                stored.pitch
+            },
+            intonation: if capabilities.params.intonation {
+                parse_param("Intonation", value("intonation"))?
+            } else {
+                stored.intonation
+            },
+            volume: parse_param("Volume", value("volume"))?,
+        };
+
+        params.validate().map(|()| params)
+    })();
+
+    let params = match params {
+        Ok(params) => params,
+        Err(e) => {
+            interaction
+                .create_response(
+                    &ctx.http,
+                    CreateInteractionResponse::Message(
+                        CreateInteractionResponseMessage::new()
+                            .content(e)
+                            .ephemeral(true),
+                    ),
+                )
+                .await
+                .unwrap();
+            return;
+        }
+    };
+
+    // Parameters belong to the user rather than to a style, so they are stored right away.
+    let guild_id = interaction.guild_id.unwrap();
+    let mut setting = PERSISTENT_DB
+        .get_voice_setting(interaction.user.id)
+        .unwrap_or_else(|| guild_config(guild_id).default_style);
+    setting.params = params;
+    PERSISTENT_DB.store_style_id(interaction.user.id, &setting);
+
+    interaction
+        .create_response(
+            &ctx.http,
+            CreateInteractionResponse::UpdateMessage(
+                create_modal(
+                    tts_services,
+                    guild_id,
+                    &parse_tts_style(style, params),
+                    true,
+                )
+                .await,
+            ),
+        )
+        .await
+        .unwrap();
+}
+
+async fn first_available_style(
+    tts_services: &TtsServices,
+    styles: &HashMap<String, Vec<CharacterView>>,
+) -> Option<TtsStyle> {
+    let mut service_ids: Vec<_> = styles.keys().collect();
+    service_ids.sort();
+
+    for service_id in service_ids {
+        let Some(style) = styles[service_id]
+            .iter()
+            .find_map(|character| character.styles.first())
+        else {
+            continue;
+        };
+
+        if tts_services.is_available(service_id, &style.id).await {
+            return Some(TtsStyle {
+                service_id: service_id.clone(),
+                style_id: style.id.clone(),
+                params: TtsParams::default(),
+            });
+        }
+    }
+
+    None
+}
+
 #[allow(clippy::too_many_lines)]
 pub async fn create_modal(
     tts_services: &TtsServices,
+    guild_id: GuildId,
     voice_setting: &TtsStyle,
     editable: bool,
 ) -> CreateInteractionResponseMessage {
     let styles = tts_services.styles().await;
+    let params = voice_setting.params;
+
+    let config = guild_config(guild_id);
+    let first_style;
 
     // Check avialablity
-    let voice_setting = if tts_services
+    let (voice_setting, notice) = if tts_services
         .is_available(&voice_setting.service_id, &voice_setting.style_id)
         .await
     {
-        voice_setting
+        (voice_setting, None)
     } else {
-        DEFAULT_TTS_STYLE.get().unwrap()
+        // The style may have disappeared from the catalog.
+        let notice = format!(
+            "`{}/{}` is currently unavailable, the default voice is used instead.",
+            voice_setting.service_id, voice_setting.style_id
+        );
+
+        let default_style = &config.default_style;
+
+        if tts_services
+            .is_available(&default_style.service_id, &default_style.style_id)
+            .await
+        {
+            (default_style, Some(notice))
+        } else {
+            // The service of the default style may still be pending.
+            let Some(style) = first_available_style(tts_services, &styles).await else {
+                return CreateInteractionResponseMessage::new()
+                    .content("No voice is available right now.")
+                    .ephemeral(true);
+            };
+
+            first_style = style;
+            (&first_style, Some(notice))
+        }
     };
 
     let current_service = styles.get(&voice_setting.service_id).unwrap();
@@ -123,11 +462,24 @@ pub async fn create_modal(
     let mut current_page_id = String::new();
 
     for (service, characters) in &styles {
+        // Down services stay listed so that users can tell why their voice isn't used.
+        let (label, languages) = if tts_services.is_down(service) {
+            (
+                format!("{service} (down)"),
+                Some("Currently unavailable".to_string()),
+            )
+        } else {
+            (
+                service.clone(),
+                format_languages(&tts_services.languages(service).await),
+            )
+        };
+
         if characters.len() <= PAGE_SIZE {
             let first_style_id = &characters.first().unwrap().styles.first().unwrap().id;
             let transition_target_id = format!("{service}_!DISCORDTTS!_{first_style_id}");
 
-            pages.push((service.clone(), transition_target_id.clone()));
+            pages.push((label, transition_target_id.clone(), languages));
 
             if service == &voice_setting.service_id {
                 current_page_id = transition_target_id;
@@ -145,8 +497,9 @@ pub async fn create_modal(
             let transition_target_id = format!("{service}_!DISCORDTTS!_{first_style_id}");
 
             pages.push((
-                format!("{service} ({page_index}/{page_count})"),
+                format!("{label} ({page_index}/{page_count})"),
                 transition_target_id.clone(),
+                languages.clone(),
             ));
 
             if service == &voice_setting.service_id
@@ -185,13 +538,27 @@ pub async fn create_modal(
 
     let mut styles = vec![];
 
-    for style in current_style_items {
+    for style in &current_style_items {
         let style_id = &style.id;
         let transition_target_id = format!("{}_!DISCORDTTS!_{style_id}", voice_setting.service_id);
+        let tags = format_tags(
+            current_speaker.style_language(style),
+            current_speaker.style_gender(style),
+        );
 
-        styles.push((style.name.clone(), transition_target_id.clone()));
+        styles.push((style.name.clone(), transition_target_id.clone(), tags));
     }
 
+    let capabilities = tts_services
+        .capabilities(&voice_setting.service_id)
+        .await
+        .unwrap_or_default();
+
+    let tags = format_tags(
+        current_speaker.style_language(current_style),
+        current_speaker.style_gender(current_style),
+    );
+
     let core = CreateInteractionResponseMessage::new()
         .embed(
             CreateEmbed::new()
@@ -199,7 +566,11 @@ pub async fn create_modal(
                     "{} / {}",
                     current_speaker.name, current_style.name
                 )))
+                .fields(notice.map(|notice| ("Notice", notice, false)))
                 .field("Policy", &current_speaker.policy, false)
+                .fields(tags.map(|tags| ("Tags", tags, false)))
+                .field("Parameters", format_params(&params), false)
+                .field("Capabilities", format_capabilities(capabilities), false)
                 .thumbnail("attachment://icon.png"),
         )
         .add_file(CreateAttachment::bytes(
@@ -214,9 +585,15 @@ pub async fn create_modal(
 
     let page_options: Vec<_> = pages
         .into_iter()
-        .map(|(display, transition_to)| {
+        .map(|(display, transition_to, languages)| {
             let is_default = current_page_id == transition_to;
-            CreateSelectMenuOption::new(display, transition_to).default_selection(is_default)
+            let option =
+                CreateSelectMenuOption::new(display, transition_to).default_selection(is_default);
+
+            match languages {
+                Some(languages) => option.description(languages),
+                None => option,
+            }
         })
         .collect();
 
@@ -239,9 +616,15 @@ pub async fn create_modal(
 
     let style_options: Vec<_> = styles
         .into_iter()
-        .map(|(display, transition_to)| {
+        .map(|(display, transition_to, tags)| {
             let is_default = apply_target_id == transition_to;
-            CreateSelectMenuOption::new(display, transition_to).default_selection(is_default)
+            let option =
+                CreateSelectMenuOption::new(display, transition_to).default_selection(is_default);
+
+            match tags {
+                Some(tags) => option.description(tags),
+                None => option,
+            }
         })
         .collect();
 
@@ -277,6 +660,7 @@ pub async fn create_modal(
         ),
         CreateActionRow::Buttons(vec![
             CreateButton::new(format!("apply_{apply_target_id}")).label("Apply"),
+            CreateButton::new(format!("params_{apply_target_id}")).label("Parameters"),
         ]),
     ])
 }
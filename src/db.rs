@@ -1,65 +1,466 @@
 use std::collections::HashMap;
-use std::fs::File;
-use std::io::{BufReader, Write};
-use std::path::{Path, PathBuf};
-use std::sync::{Arc, RwLock};
+use std::path::Path;
+use std::sync::{Arc, Mutex, RwLock};
 
+use anyhow::{Context, Result};
 use once_cell::sync::Lazy;
+use rusqlite::{Connection, OpenFlags, OptionalExtension, Transaction, params};
 use serde::{Deserialize, Serialize};
 use serenity::model::prelude::{ChannelId, GuildId, UserId};
 
-use crate::model::TtsStyle;
+use crate::dictionary::{DictEntry, Dictionary};
+use crate::model::{GuildSettings, TtsStyle};
 
 pub static PERSISTENT_DB: Lazy<PersistentDB> = Lazy::new(|| {
-    PersistentDB::new(&crate::CLI_OPTIONS.get().unwrap().persistent_path)
-        .expect("Failed to initialize DB")
+    let cli = crate::CLI_OPTIONS.get().unwrap();
+
+    PersistentDB::open(&cli.database_path, &cli.persistent_path).expect("Failed to initialize DB")
 });
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
-struct PersistentStructure {
+/// Schema changes applied in order. The number of applied ones is kept in `user_version`.
+const MIGRATIONS: &[&str] = &[
+    // Entries of a dictionary are ordered by rowid, which is kept when an entry is replaced.
+    "CREATE TABLE voice_settings (
+        user_id INTEGER PRIMARY KEY,
+        service_id TEXT NOT NULL,
+        style_id TEXT NOT NULL,
+        params TEXT NOT NULL
+    );
+    CREATE TABLE dictionary_entries (
+        guild_id INTEGER NOT NULL,
+        word TEXT NOT NULL,
+        reading TEXT NOT NULL,
+        is_regex INTEGER NOT NULL,
+        PRIMARY KEY (guild_id, word)
+    );",
+    // Settings are stored as JSON so that adding one doesn't need a migration.
+    "CREATE TABLE guild_settings (
+        guild_id INTEGER PRIMARY KEY,
+        settings TEXT NOT NULL
+    );",
+    "CREATE TABLE sessions (
+        guild_id INTEGER PRIMARY KEY,
+        voice_channel_id INTEGER NOT NULL,
+        text_channel_id INTEGER NOT NULL
+    );",
+];
+
+/// The format of `state.json` used before the database.
+#[derive(Deserialize, Debug)]
+struct LegacyStructure {
     voice_settings: HashMap<UserId, TtsStyle>,
+    #[serde(default)]
+    dictionaries: HashMap<GuildId, Vec<DictEntry>>,
+}
+
+/// A voice connection linked to a text channel, restored after a restart.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Session {
+    pub guild: GuildId,
+    pub voice_channel: ChannelId,
+    pub text_channel: ChannelId,
+}
+
+// Snowflakes fit in 63 bits, so they are stored as they are in INTEGER columns.
+#[allow(clippy::cast_possible_wrap)]
+fn to_sql_id(id: u64) -> i64 {
+    id as i64
+}
+
+#[allow(clippy::cast_sign_loss)]
+fn from_sql_id(id: i64) -> u64 {
+    id as u64
 }
 
 pub struct PersistentDB {
-    file: PathBuf,
-    data: RwLock<PersistentStructure>,
+    conn: Mutex<Connection>,
+    compiled_dictionaries: RwLock<HashMap<GuildId, Arc<Dictionary>>>,
+    /// Read on every message, so they are kept in memory once loaded.
+    guild_settings: RwLock<HashMap<GuildId, GuildSettings>>,
 }
 
 impl PersistentDB {
-    pub fn new(file: &Path) -> Result<Self, std::io::Error> {
-        let data =
-            serde_json::from_reader(BufReader::new(File::open(file)?)).expect("DB is corrupt");
+    /// Opens the database, creating it if needed. A new database imports `legacy` if it exists.
+    pub fn open(path: &Path, legacy: &Path) -> Result<Self> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)
+                .with_context(|| format!("Failed to create {}", dir.display()))?;
+        }
 
-        Ok(Self {
-            file: file.into(),
-            data,
-        })
+        let conn =
+            Connection::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+
+        conn.pragma_update(None, "journal_mode", "WAL")?;
+        conn.pragma_update(None, "synchronous", "NORMAL")?;
+
+        Self::new(conn, Some(legacy))
+    }
+
+    /// Opens an existing database without creating or migrating it, for inspecting it while the
+    /// bot is running.
+    pub fn open_read_only(path: &Path) -> Result<Self> {
+        let conn = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)
+            .with_context(|| format!("Failed to open {}", path.display()))?;
+
+        let version: u32 = conn.pragma_query_value(None, "user_version", |row| row.get(0))?;
+        let latest = u32::try_from(MIGRATIONS.len()).unwrap();
+
+        if version != latest {
+            anyhow::bail!(
+                "The database has schema version {version}, but this version expects {latest}"
+            );
+        }
+
+        Ok(Self::from_connection(conn))
+    }
+
+    fn new(mut conn: Connection, legacy: Option<&Path>) -> Result<Self> {
+        let tx = conn.transaction()?;
+        let version: u32 = tx.pragma_query_value(None, "user_version", |row| row.get(0))?;
+        let latest = u32::try_from(MIGRATIONS.len()).unwrap();
+
+        if version > latest {
+            anyhow::bail!("The database is newer than this version (schema version {version})");
+        }
+
+        for (i, migration) in MIGRATIONS.iter().enumerate().skip(version as usize) {
+            tx.execute_batch(migration)
+                .with_context(|| format!("Failed to migrate to schema version {}", i + 1))?;
+        }
+
+        if version == 0
+            && let Some(legacy) = legacy.filter(|legacy| legacy.exists())
+        {
+            import_legacy(&tx, legacy)?;
+        }
+
+        tx.pragma_update(None, "user_version", latest)?;
+        tx.commit()?;
+
+        Ok(Self::from_connection(conn))
+    }
+
+    fn from_connection(conn: Connection) -> Self {
+        Self {
+            conn: Mutex::new(conn),
+            compiled_dictionaries: RwLock::new(HashMap::new()),
+            guild_settings: RwLock::new(HashMap::new()),
+        }
     }
 
     pub fn get_voice_setting(&self, user: UserId) -> Option<TtsStyle> {
-        self.data.read().unwrap().voice_settings.get(&user).cloned()
+        self.conn
+            .lock()
+            .unwrap()
+            .query_row(
+                "SELECT service_id, style_id, params FROM voice_settings WHERE user_id = ?1",
+                params![to_sql_id(user.get())],
+                |row| {
+                    let params: String = row.get(2)?;
+
+                    Ok(TtsStyle {
+                        service_id: row.get(0)?,
+                        style_id: row.get(1)?,
+                        params: serde_json::from_str(&params).unwrap_or_default(),
+                    })
+                },
+            )
+            .optional()
+            .expect("Failed to read voice setting")
+    }
+
+    /// Number of users who have selected the style.
+    pub fn count_voice_settings(&self, service_id: &str, style_id: &str) -> usize {
+        let count: i64 = self
+            .conn
+            .lock()
+            .unwrap()
+            .query_row(
+                "SELECT COUNT(*) FROM voice_settings WHERE service_id = ?1 AND style_id = ?2",
+                params![service_id, style_id],
+                |row| row.get(0),
+            )
+            .expect("Failed to count voice settings");
+
+        usize::try_from(count).unwrap_or_default()
     }
 
     pub fn store_style_id(&self, user: UserId, voice_setting: &TtsStyle) {
-        self.data
-            .write()
+        store_voice_setting(&self.conn.lock().unwrap(), user, voice_setting)
+            .expect("Failed to store voice setting");
+    }
+
+    pub fn get_dictionary_entries(&self, guild: GuildId) -> Vec<DictEntry> {
+        let conn = self.conn.lock().unwrap();
+
+        let mut stmt = conn
+            .prepare_cached(
+                "SELECT word, reading, is_regex FROM dictionary_entries
+                 WHERE guild_id = ?1 ORDER BY rowid",
+            )
+            .expect("Failed to read dictionary");
+
+        stmt.query_map(params![to_sql_id(guild.get())], |row| {
+            Ok(DictEntry {
+                word: row.get(0)?,
+                reading: row.get(1)?,
+                is_regex: row.get(2)?,
+            })
+        })
+        .and_then(Iterator::collect)
+        .expect("Failed to read dictionary")
+    }
+
+    pub fn get_dictionary(&self, guild: GuildId) -> Arc<Dictionary> {
+        if let Some(dictionary) = self.compiled_dictionaries.read().unwrap().get(&guild) {
+            return dictionary.clone();
+        }
+
+        // The entries are read under the lock, so that an edit meanwhile can't be overwritten by
+        // the stale dictionary; the edit invalidates the cache after this returns.
+        let mut compiled = self.compiled_dictionaries.write().unwrap();
+
+        compiled
+            .entry(guild)
+            .or_insert_with(|| {
+                // Entries are validated on insertion, so compilation never fails here.
+                Arc::new(
+                    Dictionary::new(&self.get_dictionary_entries(guild))
+                        .expect("Dictionary is corrupt"),
+                )
+            })
+            .clone()
+    }
+
+    /// Inserts or replaces the entry for `entry.word`.
+    pub fn store_dictionary_entry(&self, guild: GuildId, entry: &DictEntry) {
+        store_dictionary_entry(&self.conn.lock().unwrap(), guild, entry)
+            .expect("Failed to store dictionary entry");
+
+        self.compiled_dictionaries.write().unwrap().remove(&guild);
+    }
+
+    /// Returns `false` if no entry for `word` exists.
+    pub fn remove_dictionary_entry(&self, guild: GuildId, word: &str) -> bool {
+        let removed = self
+            .conn
+            .lock()
+            .unwrap()
+            .execute(
+                "DELETE FROM dictionary_entries WHERE guild_id = ?1 AND word = ?2",
+                params![to_sql_id(guild.get()), word],
+            )
+            .expect("Failed to remove dictionary entry");
+
+        if removed == 0 {
+            return false;
+        }
+
+        self.compiled_dictionaries.write().unwrap().remove(&guild);
+
+        true
+    }
+
+    pub fn get_sessions(&self) -> Vec<Session> {
+        let conn = self.conn.lock().unwrap();
+
+        let mut stmt = conn
+            .prepare_cached("SELECT guild_id, voice_channel_id, text_channel_id FROM sessions")
+            .expect("Failed to read sessions");
+
+        stmt.query_map([], |row| {
+            Ok(Session {
+                guild: GuildId::new(from_sql_id(row.get(0)?)),
+                voice_channel: ChannelId::new(from_sql_id(row.get(1)?)),
+                text_channel: ChannelId::new(from_sql_id(row.get(2)?)),
+            })
+        })
+        .and_then(Iterator::collect)
+        .expect("Failed to read sessions")
+    }
+
+    pub fn store_session(&self, session: &Session) {
+        self.conn
+            .lock()
             .unwrap()
-            .voice_settings
-            .insert(user, voice_setting.clone());
+            .execute(
+                "INSERT INTO sessions (guild_id, voice_channel_id, text_channel_id)
+                 VALUES (?1, ?2, ?3)
+                 ON CONFLICT (guild_id) DO UPDATE SET
+                    voice_channel_id = excluded.voice_channel_id,
+                    text_channel_id = excluded.text_channel_id",
+                params![
+                    to_sql_id(session.guild.get()),
+                    to_sql_id(session.voice_channel.get()),
+                    to_sql_id(session.text_channel.get()),
+                ],
+            )
+            .expect("Failed to store session");
+    }
 
-        self.flush();
+    /// Follows the bot being moved to another voice channel.
+    pub fn update_session_voice_channel(&self, guild: GuildId, voice_channel: ChannelId) {
+        self.conn
+            .lock()
+            .unwrap()
+            .execute(
+                "UPDATE sessions SET voice_channel_id = ?2 WHERE guild_id = ?1",
+                params![to_sql_id(guild.get()), to_sql_id(voice_channel.get())],
+            )
+            .expect("Failed to update session");
+    }
+
+    pub fn remove_session(&self, guild: GuildId) {
+        self.conn
+            .lock()
+            .unwrap()
+            .execute(
+                "DELETE FROM sessions WHERE guild_id = ?1",
+                params![to_sql_id(guild.get())],
+            )
+            .expect("Failed to remove session");
     }
 
-    fn flush(&self) {
-        File::create(&self.file)
-            .expect("Failed to create renew file.")
-            .write_all(
-                serde_json::to_string(&(*self.data.read().unwrap()))
+    pub fn get_guild_settings(&self, guild: GuildId) -> GuildSettings {
+        if let Some(settings) = self.guild_settings.read().unwrap().get(&guild) {
+            return settings.clone();
+        }
+
+        // Read under the lock as in `get_dictionary`, so that a store meanwhile can't be
+        // overwritten by the stale row.
+        let mut cached = self.guild_settings.write().unwrap();
+
+        cached
+            .entry(guild)
+            .or_insert_with(|| {
+                let settings: Option<String> = self
+                    .conn
+                    .lock()
                     .unwrap()
-                    .as_bytes(),
+                    .query_row(
+                        "SELECT settings FROM guild_settings WHERE guild_id = ?1",
+                        params![to_sql_id(guild.get())],
+                        |row| row.get(0),
+                    )
+                    .optional()
+                    .expect("Failed to read guild settings");
+
+                settings
+                    .and_then(|settings| serde_json::from_str(&settings).ok())
+                    .unwrap_or_default()
+            })
+            .clone()
+    }
+
+    /// Stores the settings, removing the row if nothing is overridden.
+    pub fn store_guild_settings(&self, guild: GuildId, settings: &GuildSettings) {
+        // Released before the cache is locked, which `get_guild_settings` locks first.
+        let conn = self.conn.lock().unwrap();
+
+        if settings.is_default() {
+            conn.execute(
+                "DELETE FROM guild_settings WHERE guild_id = ?1",
+                params![to_sql_id(guild.get())],
             )
-            .expect("Failed to write file.");
+        } else {
+            conn.execute(
+                "INSERT INTO guild_settings (guild_id, settings) VALUES (?1, ?2)
+                 ON CONFLICT (guild_id) DO UPDATE SET settings = excluded.settings",
+                params![
+                    to_sql_id(guild.get()),
+                    serde_json::to_string(settings).expect("Failed to serialize guild settings"),
+                ],
+            )
+        }
+        .expect("Failed to store guild settings");
+        drop(conn);
+
+        self.guild_settings
+            .write()
+            .unwrap()
+            .insert(guild, settings.clone());
+    }
+}
+
+fn store_voice_setting(conn: &Connection, user: UserId, voice_setting: &TtsStyle) -> Result<()> {
+    conn.execute(
+        "INSERT INTO voice_settings (user_id, service_id, style_id, params)
+         VALUES (?1, ?2, ?3, ?4)
+         ON CONFLICT (user_id) DO UPDATE SET
+            service_id = excluded.service_id,
+            style_id = excluded.style_id,
+            params = excluded.params",
+        params![
+            to_sql_id(user.get()),
+            voice_setting.service_id,
+            voice_setting.style_id,
+            serde_json::to_string(&voice_setting.params)?,
+        ],
+    )?;
+
+    Ok(())
+}
+
+fn store_dictionary_entry(conn: &Connection, guild: GuildId, entry: &DictEntry) -> Result<()> {
+    conn.execute(
+        "INSERT INTO dictionary_entries (guild_id, word, reading, is_regex)
+         VALUES (?1, ?2, ?3, ?4)
+         ON CONFLICT (guild_id, word) DO UPDATE SET
+            reading = excluded.reading,
+            is_regex = excluded.is_regex",
+        params![
+            to_sql_id(guild.get()),
+            entry.word,
+            entry.reading,
+            entry.is_regex
+        ],
+    )?;
+
+    Ok(())
+}
+
+/// Imports `state.json`. The file is left as it is, unless it is corrupt, in which case it is
+/// renamed to `state.json.invalid` to be fixed by hand, since the import is never retried.
+fn import_legacy(tx: &Transaction, path: &Path) -> Result<()> {
+    let file = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+
+    let legacy: LegacyStructure = match serde_json::from_str(&file) {
+        Ok(v) => v,
+        Err(e) => {
+            let mut invalid = path.as_os_str().to_owned();
+            invalid.push(".invalid");
+
+            std::fs::rename(path, &invalid)
+                .with_context(|| format!("Failed to move aside corrupt {}", path.display()))?;
+
+            tracing::warn!(
+                "{} is corrupt and is not imported, moved to {}: {e}",
+                path.display(),
+                Path::new(&invalid).display(),
+            );
+            return Ok(());
+        }
+    };
+
+    for (user, voice_setting) in &legacy.voice_settings {
+        store_voice_setting(tx, *user, voice_setting)?;
+    }
+
+    for (guild, entries) in &legacy.dictionaries {
+        for entry in entries {
+            store_dictionary_entry(tx, *guild, entry)?;
+        }
     }
+
+    tracing::info!(
+        "Imported {} voice settings and {} dictionaries from {}",
+        legacy.voice_settings.len(),
+        legacy.dictionaries.len(),
+        path.display(),
+    );
+
+    Ok(())
 }
 
 struct InmemoryStructure {
@@ -90,6 +491,16 @@ impl InmemoryDB {
             .map(ToOwned::to_owned)
     }
 
+    pub fn get_instances(&self) -> Vec<(GuildId, ChannelId)> {
+        self.data
+            .read()
+            .unwrap()
+            .instances
+            .iter()
+            .map(|(guild_id, channel_id)| (*guild_id, *channel_id))
+            .collect()
+    }
+
     pub fn store_instance(&self, guild_id: GuildId, channel_id: ChannelId) {
         self.data
             .write()
@@ -133,3 +544,182 @@ impl EmojiDB {
         self.data.clone()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::TimeStretchSettings;
+
+    fn style(style_id: &str) -> TtsStyle {
+        TtsStyle {
+            service_id: "VOICEVOX".to_string(),
+            style_id: style_id.to_string(),
+            params: crate::model::TtsParams {
+                speed: Some(1.5),
+                ..Default::default()
+            },
+        }
+    }
+
+    fn entry(word: &str, reading: &str) -> DictEntry {
+        DictEntry {
+            word: word.to_string(),
+            reading: reading.to_string(),
+            is_regex: false,
+        }
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let db = PersistentDB::new(Connection::open_in_memory().unwrap(), None).unwrap();
+        let user = UserId::new(1);
+        let guild = GuildId::new(2);
+
+        assert_eq!(db.get_voice_setting(user), None);
+        db.store_style_id(user, &style("1"));
+        db.store_style_id(user, &style("2"));
+        assert_eq!(db.get_voice_setting(user), Some(style("2")));
+        assert_eq!(db.count_voice_settings("VOICEVOX", "2"), 1);
+
+        db.store_dictionary_entry(guild, &entry("a", "x"));
+        db.store_dictionary_entry(guild, &entry("b", "y"));
+        db.store_dictionary_entry(guild, &entry("a", "z"));
+        assert_eq!(
+            db.get_dictionary_entries(guild),
+            vec![entry("a", "z"), entry("b", "y")]
+        );
+
+        assert!(db.remove_dictionary_entry(guild, "a"));
+        assert!(!db.remove_dictionary_entry(guild, "a"));
+        assert_eq!(db.get_dictionary_entries(guild), vec![entry("b", "y")]);
+    }
+
+    #[test]
+    fn test_guild_settings() {
+        let db = PersistentDB::new(Connection::open_in_memory().unwrap(), None).unwrap();
+        let guild = GuildId::new(1);
+
+        assert_eq!(db.get_guild_settings(guild), GuildSettings::default());
+
+        let settings = GuildSettings {
+            default_style: Some(style("1")),
+            suppress_prefix: Some(String::new()),
+            timestretch: TimeStretchSettings {
+                target_speed: Some(2.0),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        db.store_guild_settings(guild, &settings);
+        db.guild_settings.write().unwrap().clear();
+        assert_eq!(db.get_guild_settings(guild), settings);
+
+        db.store_guild_settings(guild, &GuildSettings::default());
+        db.guild_settings.write().unwrap().clear();
+        assert_eq!(db.get_guild_settings(guild), GuildSettings::default());
+        assert_eq!(
+            db.get_guild_settings(GuildId::new(2)),
+            GuildSettings::default()
+        );
+    }
+
+    #[test]
+    fn test_sessions() {
+        let db = PersistentDB::new(Connection::open_in_memory().unwrap(), None).unwrap();
+
+        let session = Session {
+            guild: GuildId::new(1),
+            voice_channel: ChannelId::new(2),
+            text_channel: ChannelId::new(3),
+        };
+
+        db.store_session(&session);
+        db.update_session_voice_channel(session.guild, ChannelId::new(4));
+        db.update_session_voice_channel(GuildId::new(5), ChannelId::new(6));
+
+        assert_eq!(
+            db.get_sessions(),
+            vec![Session {
+                voice_channel: ChannelId::new(4),
+                ..session
+            }]
+        );
+
+        db.remove_session(session.guild);
+        assert_eq!(db.get_sessions(), vec![]);
+    }
+
+    #[test]
+    fn test_import_legacy() {
+        let path = std::env::temp_dir().join(format!("state-{}.json", uuid::Uuid::new_v4()));
+
+        std::fs::write(
+            &path,
+            r#"{
+                "voice_settings": {"1": {"service_id": "VOICEVOX", "style_id": "1", "params": {"speed": 1.5}}},
+                "dictionaries": {"2": [{"word": "a", "reading": "x"}]}
+            }"#,
+        )
+        .unwrap();
+
+        let db = PersistentDB::new(Connection::open_in_memory().unwrap(), Some(&path)).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(db.get_voice_setting(UserId::new(1)), Some(style("1")));
+        assert_eq!(
+            db.get_dictionary_entries(GuildId::new(2)),
+            vec![entry("a", "x")]
+        );
+    }
+
+    #[test]
+    fn test_import_corrupt_legacy() {
+        let path = std::env::temp_dir().join(format!("state-{}.json", uuid::Uuid::new_v4()));
+        let invalid = path.with_extension("json.invalid");
+
+        std::fs::write(&path, "{").unwrap();
+
+        let db = PersistentDB::new(Connection::open_in_memory().unwrap(), Some(&path)).unwrap();
+
+        assert!(!path.exists());
+        assert_eq!(std::fs::read_to_string(&invalid).unwrap(), "{");
+        std::fs::remove_file(&invalid).unwrap();
+
+        assert_eq!(db.get_voice_setting(UserId::new(1)), None);
+    }
+
+    #[test]
+    fn test_newer_schema() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.pragma_update(
+            None,
+            "user_version",
+            u32::try_from(MIGRATIONS.len()).unwrap() + 1,
+        )
+        .unwrap();
+
+        assert!(PersistentDB::new(conn, None).is_err());
+    }
+
+    #[test]
+    fn test_open_read_only() {
+        let path = std::env::temp_dir().join(format!("database-{}.sqlite3", uuid::Uuid::new_v4()));
+
+        assert!(PersistentDB::open_read_only(&path).is_err());
+        assert!(!path.exists());
+
+        let guild = GuildId::new(2);
+        PersistentDB::open(&path, Path::new(""))
+            .unwrap()
+            .store_dictionary_entry(guild, &entry("a", "x"));
+
+        let db = PersistentDB::open_read_only(&path).unwrap();
+        assert_eq!(db.get_dictionary_entries(guild), vec![entry("a", "x")]);
+
+        for suffix in ["", "-wal", "-shm"] {
+            let mut file = path.clone().into_os_string();
+            file.push(suffix);
+            std::fs::remove_file(file).ok();
+        }
+    }
+}
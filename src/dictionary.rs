@@ -0,0 +1,183 @@
+use std::borrow::Cow;
+
+use anyhow::{Context, Result};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct DictEntry {
+    pub word: String,
+    pub reading: String,
+    #[serde(default)]
+    pub is_regex: bool,
+}
+
+#[derive(Debug)]
+struct CompiledEntry {
+    pattern: Regex,
+    reading: String,
+    is_regex: bool,
+}
+
+/// A compiled per-guild pronunciation dictionary.
+///
+/// At every position the longest matching entry wins; ties are broken by definition order.
+#[derive(Debug)]
+pub struct Dictionary {
+    entries: Vec<CompiledEntry>,
+}
+
+impl Dictionary {
+    pub fn new(entries: &[DictEntry]) -> Result<Self> {
+        let entries = entries
+            .iter()
+            .map(|entry| {
+                let pattern = if entry.is_regex {
+                    Regex::new(&entry.word)
+                        .with_context(|| format!("Invalid regex: {}", entry.word))?
+                } else {
+                    Regex::new(&regex::escape(&entry.word)).unwrap()
+                };
+
+                Ok(CompiledEntry {
+                    pattern,
+                    reading: entry.reading.clone(),
+                    is_regex: entry.is_regex,
+                })
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(Self { entries })
+    }
+
+    pub fn apply<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        if self.entries.is_empty() {
+            return text.into();
+        }
+
+        let mut ret = String::new();
+        let mut last = 0;
+        let mut pos = 0;
+
+        while pos <= text.len() {
+            // (start, end, entry index)
+            let mut best: Option<(usize, usize, usize)> = None;
+
+            for (i, entry) in self.entries.iter().enumerate() {
+                let Some(m) = find_non_empty_at(&entry.pattern, text, pos) else {
+                    continue;
+                };
+
+                let better = match best {
+                    None => true,
+                    Some((start, end, _)) => {
+                        m.start() < start || (m.start() == start && m.end() > end)
+                    }
+                };
+
+                if better {
+                    best = Some((m.start(), m.end(), i));
+                }
+            }
+
+            let Some((start, end, i)) = best else {
+                break;
+            };
+
+            let entry = &self.entries[i];
+
+            ret.push_str(&text[last..start]);
+
+            if entry.is_regex {
+                let caps = entry.pattern.captures_at(text, start).unwrap();
+                caps.expand(&entry.reading, &mut ret);
+            } else {
+                ret.push_str(&entry.reading);
+            }
+
+            last = end;
+            pos = end;
+        }
+
+        if last == 0 {
+            return text.into();
+        }
+
+        ret.push_str(&text[last..]);
+        ret.into()
+    }
+}
+
+// Empty matches never make progress, so they are skipped.
+fn find_non_empty_at<'a>(
+    pattern: &Regex,
+    text: &'a str,
+    mut pos: usize,
+) -> Option<regex::Match<'a>> {
+    loop {
+        let m = pattern.find_at(text, pos)?;
+
+        if !m.is_empty() {
+            return Some(m);
+        }
+
+        pos = m.start() + text[m.start()..].chars().next()?.len_utf8();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(word: &str, reading: &str, is_regex: bool) -> DictEntry {
+        DictEntry {
+            word: word.to_string(),
+            reading: reading.to_string(),
+            is_regex,
+        }
+    }
+
+    #[test]
+    fn test_empty_dictionary() {
+        let dict = Dictionary::new(&[]).unwrap();
+        assert_eq!(dict.apply("hello"), "hello");
+    }
+
+    #[test]
+    fn test_plain_replacement() {
+        let dict = Dictionary::new(&[entry("yanorei32", "やのれい", false)]).unwrap();
+        assert_eq!(dict.apply("hi yanorei32!"), "hi やのれい!");
+        assert_eq!(dict.apply("yanorei32yanorei32"), "やのれいやのれい");
+    }
+
+    #[test]
+    fn test_longest_match_first() {
+        let dict = Dictionary::new(&[
+            entry("VC", "ブイシー", false),
+            entry("VCN", "ブイシーエヌ", false),
+        ])
+        .unwrap();
+        assert_eq!(dict.apply("VCN VC"), "ブイシーエヌ ブイシー");
+    }
+
+    #[test]
+    fn test_replacement_is_not_rescanned() {
+        let dict = Dictionary::new(&[entry("a", "ab", false), entry("b", "c", false)]).unwrap();
+        assert_eq!(dict.apply("ab"), "abc");
+    }
+
+    #[test]
+    fn test_regex_replacement() {
+        let dict = Dictionary::new(&[entry(r"w{2,}$", "わらわら", true)]).unwrap();
+        assert_eq!(dict.apply("それなwww"), "それなわらわら");
+
+        let dict = Dictionary::new(&[entry(r"(\d+)GB", "${1}ギガバイト", true)]).unwrap();
+        assert_eq!(dict.apply("16GBです"), "16ギガバイトです");
+    }
+
+    #[test]
+    fn test_invalid_regex() {
+        assert!(Dictionary::new(&[entry("(", "", true)]).is_err());
+        assert!(Dictionary::new(&[entry("(", "かっこ", false)]).is_ok());
+    }
+}
@@ -1,8 +1,13 @@
 use std::borrow::Cow;
 
-use crate::db::{EMOJI_DB, INMEMORY_DB};
+use crate::db::{EMOJI_DB, INMEMORY_DB, PERSISTENT_DB};
+use crate::dictionary::Dictionary;
+use crate::locale::{Locale, Localized, Text};
+use crate::markdown::{self, MarkdownOptions};
+use crate::metrics::METRICS;
 use once_cell::sync::Lazy;
-use regex::Regex;
+use regex::{NoExpand, Regex};
+use serde::{Deserialize, Deserializer};
 use serenity::all::{MessageReferenceKind, MessageType};
 use serenity::{
     cache::Cache,
@@ -10,6 +15,7 @@ use serenity::{
     model::{channel::Message, id::ChannelId},
     prelude::Mentionable,
 };
+use time::{OffsetDateTime, UtcOffset};
 
 // regex crate's named capture
 #[allow(clippy::invalid_regex)]
@@ -20,22 +26,327 @@ static EXTERNAL_EMOJI_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new(r"<a?:\w+:\d+
 static EMOJI_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new(r":\w+:").unwrap());
 static URI_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new(r"[A-Za-z][A-Za-z0-9+\-.]*:\S+").unwrap());
 
-pub fn filter<T>(ctx: T, mes: &'_ Message) -> Option<String>
+const LEGACY_COMMAND_PREFIX: &str = "~";
+const LEGACY_PING_COMMAND: &str = "ping";
+
+/// The `filter` table of the TTS config.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct FilterConfig {
+    pub builtin: BuiltinRules,
+    /// Applied in order after the built-in rules which drop messages.
+    pub rules: Vec<FilterRule>,
+    /// Time zone the timestamps in messages are read in, such as `+09:00`.
+    #[serde(deserialize_with = "deserialize_utc_offset")]
+    pub utc_offset: UtcOffset,
+}
+
+impl Default for FilterConfig {
+    fn default() -> Self {
+        Self {
+            builtin: BuiltinRules::default(),
+            rules: vec![],
+            utc_offset: UtcOffset::from_hms(9, 0, 0).unwrap(),
+        }
+    }
+}
+
+/// A built-in rule, which is enabled by `true`, disabled by `false`,
+/// or enabled with its string (a prefix, a word, or a replacement) overridden.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum Builtin {
+    Enabled(bool),
+    Override(String),
+}
+
+impl Default for Builtin {
+    fn default() -> Self {
+        Self::Enabled(true)
+    }
+}
+
+impl Builtin {
+    /// The string of the rule, or `None` if it is disabled.
+    fn get<'a>(&'a self, default: &'a str) -> Option<&'a str> {
+        match self {
+            Self::Enabled(true) => Some(default),
+            Self::Enabled(false) => None,
+            Self::Override(s) => Some(s),
+        }
+    }
+}
+
+#[allow(clippy::struct_excessive_bools)]
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct BuiltinRules {
+    /// Template of mentions, where `{name}` is the name of the user, the role or the channel.
+    /// The `mention` text of the locale if enabled by `true`, and so are `uri` and `codeblock`.
+    pub mention: Builtin,
+    /// Prefix of the commands of the former bot.
+    pub legacy_command: Builtin,
+    /// The whole message of the ping command of the former bot.
+    pub legacy_ping: Builtin,
+    /// Discord markdown such as `**bold**`, `[text](url)` and `<t:1700000000:R>`.
+    pub markdown: bool,
+    /// Text read in place of spoilers, which are read as they are by default.
+    /// The `spoiler` text of the locale if enabled by `true`.
+    pub spoiler: Builtin,
+    pub external_emoji: Builtin,
+    pub uri: Builtin,
+    pub emoji: Builtin,
+    pub unicode_emoji: bool,
+    pub attachments: bool,
+    pub forward: bool,
+    pub poll: bool,
+    pub codeblock: Builtin,
+}
+
+impl Default for BuiltinRules {
+    fn default() -> Self {
+        Self {
+            mention: Builtin::default(),
+            legacy_command: Builtin::default(),
+            legacy_ping: Builtin::default(),
+            markdown: true,
+            spoiler: Builtin::Enabled(false),
+            external_emoji: Builtin::Override(String::new()),
+            uri: Builtin::default(),
+            emoji: Builtin::Override(String::new()),
+            unicode_emoji: true,
+            attachments: true,
+            forward: true,
+            poll: true,
+            codeblock: Builtin::default(),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct FilterRule {
+    /// Shown by the `filter` subcommand and in the metrics. `rules[<index>]` if omitted.
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(flatten)]
+    pub action: RuleAction,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum RuleAction {
+    /// `replacement` may refer to the capture groups such as `$1`.
+    Replace {
+        #[serde(deserialize_with = "deserialize_regex")]
+        pattern: Regex,
+        replacement: String,
+    },
+    /// Drops messages matching `pattern`.
+    Drop {
+        #[serde(deserialize_with = "deserialize_regex")]
+        pattern: Regex,
+    },
+    /// Drops messages starting with `prefix`, unless it is doubled.
+    SuppressPrefix { prefix: String },
+}
+
+impl RuleAction {
+    fn apply<'a>(&self, mes: &'a str) -> Option<Cow<'a, str>> {
+        match self {
+            Self::Replace {
+                pattern,
+                replacement,
+            } => Some(pattern.replace_all(mes, replacement.as_str())),
+            Self::Drop { pattern } => (!pattern.is_match(mes)).then_some(mes.into()),
+            Self::SuppressPrefix { prefix } => suppress_by_prefix(mes, prefix).map(Cow::from),
+        }
+    }
+}
+
+fn deserialize_regex<'de, D>(deserializer: D) -> Result<Regex, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let pattern = String::deserialize(deserializer)?;
+    Regex::new(&pattern).map_err(serde::de::Error::custom)
+}
+
+fn deserialize_utc_offset<'de, D>(deserializer: D) -> Result<UtcOffset, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let offset = String::deserialize(deserializer)?;
+    parse_utc_offset(&offset)
+        .ok_or_else(|| serde::de::Error::custom(format!("invalid UTC offset: {offset}")))
+}
+
+/// Parses `+HH:MM` or `-HH:MM`.
+fn parse_utc_offset(offset: &str) -> Option<UtcOffset> {
+    let (sign, offset) = match offset.split_at_checked(1)? {
+        ("+", rest) => (1, rest),
+        ("-", rest) => (-1, rest),
+        _ => return None,
+    };
+
+    let (hours, minutes) = offset.split_once(':')?;
+    let hours: i8 = hours.parse().ok()?;
+    let minutes: i8 = minutes.parse().ok()?;
+
+    UtcOffset::from_hms(sign * hours, sign * minutes, 0).ok()
+}
+
+/// A message reduced to what the filter reads, so that it can be filtered without Discord.
+#[derive(Debug, Default, Clone)]
+pub struct FilterInput {
+    pub content: String,
+    /// Mention markups in the content such as `<@123>` and the names to read them as.
+    pub mentions: Vec<(String, String)>,
+    pub image_count: usize,
+    pub file_count: usize,
+    /// Whether the message forwards another message.
+    pub forwarded: bool,
+    pub poll: Option<PollEvent>,
+    /// Unix time the message is sent at, which relative timestamps are read from.
+    /// The current time if `None`.
+    pub sent_at: Option<i64>,
+}
+
+#[derive(Debug, Clone)]
+pub enum PollEvent {
+    /// A poll is created with the question.
+    Created(Option<String>),
+    /// A poll is closed with the winning answer.
+    Closed(Option<String>),
+}
+
+/// The settings of the guild the filter depends on.
+pub struct FilterOptions<'a> {
+    pub config: &'a FilterConfig,
+    /// Texts in the language of the message.
+    pub texts: Localized<'a>,
+    pub suppress_prefix: &'a str,
+    pub dictionary: &'a Dictionary,
+}
+
+impl FilterInput {
+    pub fn from_text(text: &str) -> Self {
+        Self {
+            content: text.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn from_message<T>(ctx: T, mes: &Message) -> Self
+    where
+        T: CacheHttp + AsRef<Cache>,
+    {
+        // Attachment::dimensions: If this attachment is an image, then a tuple of the width and height in pixels is returned.
+        let image_count = mes
+            .attachments
+            .iter()
+            .filter_map(serenity::all::Attachment::dimensions)
+            .count();
+
+        let forwarded = mes
+            .message_reference
+            .iter()
+            .any(|m| m.kind == MessageReferenceKind::Forward);
+
+        Self {
+            content: mes.content.clone(),
+            mentions: mention_names(ctx, mes),
+            image_count,
+            file_count: mes.attachments.len() - image_count,
+            forwarded,
+            poll: poll_event(mes),
+            sent_at: Some(mes.timestamp.unix_timestamp()),
+        }
+    }
+}
+
+/// Filters a message, where the notifications are in `locale`.
+pub fn filter<T>(ctx: T, mes: &'_ Message, locale: Locale) -> Option<String>
 where
     T: CacheHttp + AsRef<Cache>,
 {
-    if mes.channel_id != INMEMORY_DB.get_instance(mes.guild_id?)? {
+    let guild_id = mes.guild_id?;
+
+    if mes.channel_id != INMEMORY_DB.get_instance(guild_id)? {
         return None;
     }
 
     if mes.author.bot {
-        return None;
+        return counted("bot", None);
+    }
+
+    let input = FilterInput::from_message(ctx, mes);
+    let config = crate::guild_config(guild_id);
+
+    filter_input(
+        &input,
+        &FilterOptions {
+            config: &config.filter,
+            texts: config.catalog.localized(locale),
+            suppress_prefix: &config.suppress_prefix,
+            dictionary: &PERSISTENT_DB.get_dictionary(guild_id),
+        },
+    )
+}
+
+pub fn filter_input(input: &FilterInput, options: &FilterOptions) -> Option<String> {
+    filter_traced(input, options, &mut |_, _| {})
+}
+
+/// Filters `input`, passing the name and the output of every stage to `trace`.
+/// The output is `None` at the stage which drops the message.
+pub fn filter_traced(
+    input: &FilterInput,
+    options: &FilterOptions,
+    trace: &mut dyn FnMut(&str, Option<&str>),
+) -> Option<String> {
+    let builtin = &options.config.builtin;
+    let texts = &options.texts;
+
+    let s = run_builtin(
+        trace,
+        "mentions",
+        input.content.clone(),
+        builtin.mention.get(texts.get(Text::Mention)),
+        |s, template| Some(replace_mentions(s, &input.mentions, template)),
+    )?;
+    let s = run_builtin(
+        trace,
+        "legacy_command",
+        s,
+        builtin.legacy_command.get(LEGACY_COMMAND_PREFIX),
+        |s, prefix| legacy_command_compatibility(s, prefix).map(str::to_string),
+    )?;
+    let s = run_builtin(
+        trace,
+        "legacy_ping",
+        s,
+        builtin.legacy_ping.get(LEGACY_PING_COMMAND),
+        |s, command| legacy_ping_command_compatibility(s, command).map(str::to_string),
+    )?;
+    let mut s = stage(
+        trace,
+        "suppress_prefix",
+        suppress_by_prefix(&s, options.suppress_prefix),
+    )?;
+
+    for (i, rule) in options.config.rules.iter().enumerate() {
+        let name = rule.name.clone().unwrap_or_else(|| format!("rules[{i}]"));
+        s = stage(trace, &name, rule.action.apply(&s))?;
     }
 
-    let s = sanity_mention(ctx, mes);
-    let s = legacy_command_compatibility(&s)?;
-    let s = legacy_ping_command_compatibility(s)?;
-    let s = suppress_by_semicolon(s)?;
+    // Masked links and `<https://...>` must be unwrapped before replace_external_emoji and replace_uri.
+    let s = run_builtin(
+        trace,
+        "markdown",
+        s,
+        builtin.markdown.then_some(""),
+        |s, _| Some(normalize_markdown(s, input, options)),
+    )?;
 
     // `<a:emoji_identifier:123456789>` should be treated as a single emoji and not `<a:emoji_。ユーアールアイ省略。>`,
     // so replace_external_emoji must precede replace_uri.
@@ -46,138 +357,238 @@ where
     // an external_emoji cannot be a part of an URI (since an URI cannot contain a letter "<", as per RFC3986),
     // and the design decision that we want to treat a string like `<a:crime:1238318711>` as a single `external_emoji` and not
     // `<。ユーアールアイ省略。>` or `<a:。ユーアールアイ省略。>`. I mean, why would anyone enclose a strange URI within a pair of angle brackets?
-    let s = replace_external_emoji(s);
-    let s = replace_uri(&s);
-    let s = replace_emoji(&s);
-    let s = replace_unicode_emoji(&s);
-
-    // Attachment::dimensions: If this attachment is an image, then a tuple of the width and height in pixels is returned.
-    let image_count = mes
-        .attachments
-        .iter()
-        .filter_map(serenity::all::Attachment::dimensions)
-        .count();
+    let s = run_builtin(
+        trace,
+        "external_emoji",
+        s,
+        builtin.external_emoji.get(""),
+        |s, replacement| Some(replace_external_emoji(s, replacement).into_owned()),
+    )?;
+    let s = run_builtin(
+        trace,
+        "uri",
+        s,
+        builtin.uri.get(texts.get(Text::Uri)),
+        |s, replacement| Some(replace_uri(s, replacement).into_owned()),
+    )?;
+    let s = run_builtin(
+        trace,
+        "emoji",
+        s,
+        builtin.emoji.get(""),
+        |s, replacement| Some(replace_emoji(s, replacement).into_owned()),
+    )?;
+    let s = stage(trace, "dictionary", Some(options.dictionary.apply(&s)))?;
+    let s = run_builtin(
+        trace,
+        "unicode_emoji",
+        s,
+        builtin.unicode_emoji.then_some(""),
+        |s, _| Some(replace_unicode_emoji(s)),
+    )?;
+
+    let s = run_builtin(
+        trace,
+        "attachments",
+        s,
+        builtin.attachments.then_some(""),
+        |s, _| {
+            Some(
+                append_attachment_notification(s, input.image_count, input.file_count, texts)
+                    .into_owned(),
+            )
+        },
+    )?;
+    let s = run_builtin(
+        trace,
+        "forward",
+        s,
+        builtin.forward.then_some(""),
+        |s, _| Some(append_forward_notification(s, input.forwarded, texts).into_owned()),
+    )?;
+    let s = run_builtin(trace, "poll", s, builtin.poll.then_some(""), |s, _| {
+        Some(append_poll_notification(s, input.poll.as_ref(), texts).into_owned())
+    })?;
+
+    let s = run_builtin(
+        trace,
+        "codeblock",
+        s,
+        builtin.codeblock.get(texts.get(Text::Codeblock)),
+        |s, replacement| Some(replace_codeblock(s, replacement).into_owned()),
+    )?;
+    stage(trace, "whitespace", suppress_whitespaces(&s))
+}
 
-    let s = append_attachment_notification(&s, image_count, mes.attachments.len() - image_count);
-    let s = append_forward_notification(&s, mes);
-    let s = append_new_poll_notification(&s, mes);
-    let s = append_poll_result_notification(&s, mes);
-    let s = replace_codeblock(&s);
-    let s = suppress_whitespaces(&s)?;
+fn normalize_markdown(mes: &str, input: &FilterInput, options: &FilterOptions) -> String {
+    let texts = options.texts;
 
-    Some(s.to_string())
+    let options = MarkdownOptions {
+        texts,
+        spoiler: options.config.builtin.spoiler.get(texts.get(Text::Spoiler)),
+        utc_offset: options.config.utc_offset,
+        now: input
+            .sent_at
+            .unwrap_or_else(|| OffsetDateTime::now_utc().unix_timestamp()),
+    };
+
+    markdown::normalize(mes, &options)
+}
+
+/// Runs a built-in rule with its string, or passes `s` through if it is disabled.
+fn run_builtin<'a>(
+    trace: &mut dyn FnMut(&str, Option<&str>),
+    rule: &str,
+    s: String,
+    setting: Option<&'a str>,
+    f: impl FnOnce(&str, &'a str) -> Option<String>,
+) -> Option<String> {
+    match setting {
+        Some(setting) => stage(trace, rule, f(&s, setting)),
+        None => Some(s),
+    }
+}
+
+/// Reports the output of a stage, and counts the message if the stage drops it.
+fn stage(
+    trace: &mut dyn FnMut(&str, Option<&str>),
+    rule: &str,
+    output: Option<impl Into<String>>,
+) -> Option<String> {
+    let output = output.map(Into::into);
+    trace(rule, output.as_deref());
+
+    counted(rule, output)
+}
+
+/// Counts the messages dropped by `rule`.
+fn counted<T>(rule: &str, mes: Option<T>) -> Option<T> {
+    if mes.is_none() {
+        METRICS.filtered_messages.with_label_values(&[rule]).inc();
+    }
+
+    mes
 }
 
-fn append_attachment_notification(
-    body: &str,
+fn append_attachment_notification<'a>(
+    body: &'a str,
     image_count: usize,
     file_count: usize,
-) -> Cow<'_, str> {
+    texts: &Localized,
+) -> Cow<'a, str> {
     if image_count == 0 && file_count == 0 {
         return body.into();
     }
 
     let image_text = match image_count {
         0 => String::new(),
-        1 => "画像".to_string(),
-        n => format!("画像{n}枚"),
+        1 => texts.get(Text::Image).to_string(),
+        n => texts.format(Text::Images, &[("n", &n.to_string())]),
     };
 
     let file_text = match file_count {
         0 => String::new(),
-        1 => "ファイル".to_string(),
-        n => format!("ファイル{n}つ"),
+        1 => texts.get(Text::File).to_string(),
+        n => texts.format(Text::Files, &[("n", &n.to_string())]),
     };
 
-    let mut ret = body.to_string();
-
-    if !body.is_empty() {
-        ret.push('。');
-    }
-
-    ret.push_str(&image_text);
+    let mut attachments = image_text;
 
     if image_count != 0 && file_count != 0 {
-        ret.push('と');
+        attachments.push_str(texts.get(Text::AttachmentSeparator));
     }
 
-    ret.push_str(&file_text);
+    attachments.push_str(&file_text);
 
-    if body.is_empty() {
-        ret.push_str("が送信されました");
+    let text = if body.is_empty() {
+        Text::AttachmentsSent
     } else {
-        ret.push_str("添付");
-    }
+        Text::AttachmentsAppended
+    };
 
-    ret.into()
+    format!(
+        "{body}{}",
+        texts.format(text, &[("attachments", &attachments)])
+    )
+    .into()
 }
 
-fn append_forward_notification<'a>(body: &'a str, mes: &Message) -> Cow<'a, str> {
-    if !mes
-        .message_reference
-        .iter()
-        .any(|m| m.kind == MessageReferenceKind::Forward)
-    {
+fn append_forward_notification<'a>(
+    body: &'a str,
+    forwarded: bool,
+    texts: &Localized,
+) -> Cow<'a, str> {
+    if !forwarded {
         return body.into();
     }
 
     let mut ret = body.to_string();
     if ret.is_empty() {
-        ret.push_str("メッセージが転送されました。");
+        ret.push_str(texts.get(Text::Forwarded));
     } else {
-        ret.push_str("。メッセージ転送");
+        ret.push_str(texts.get(Text::ForwardAppended));
     }
 
     ret.into()
 }
 
-fn append_new_poll_notification<'a>(body: &'a str, mes: &Message) -> Cow<'a, str> {
-    let Some(poll) = &mes.poll else {
-        return body.into();
-    };
-
+fn append_poll_notification<'a>(
+    body: &'a str,
+    poll: Option<&PollEvent>,
+    texts: &Localized,
+) -> Cow<'a, str> {
     let mut ret = body.to_string();
-    ret.push_str("新たな投票が作成されました");
 
-    if let Some(text) = &poll.question.text {
-        ret = format!("{ret}。{text}");
+    match poll {
+        None => return body.into(),
+        Some(PollEvent::Created(question)) => {
+            ret.push_str(texts.get(Text::PollCreated));
+
+            if let Some(text) = question {
+                ret.push_str(&texts.format(Text::PollQuestion, &[("question", text)]));
+            }
+        }
+        Some(PollEvent::Closed(winner)) => {
+            ret.push_str(texts.get(Text::PollClosed));
+
+            if let Some(text) = winner {
+                ret.push_str(&texts.format(Text::PollWinner, &[("answer", text)]));
+            } else {
+                ret.push_str(texts.get(Text::PollNoWinner));
+            }
+        }
     }
 
     ret.into()
 }
 
-fn append_poll_result_notification<'a>(body: &'a str, mes: &Message) -> Cow<'a, str> {
+fn poll_event(mes: &Message) -> Option<PollEvent> {
+    if let Some(poll) = &mes.poll {
+        return Some(PollEvent::Created(poll.question.text.clone()));
+    }
+
     // serenity doesn't implement POLL_RESULT variant at 0.12.1.
     // Ref: https://github.com/serenity-rs/serenity/issues/2948
     if mes.kind != MessageType::Unknown(46) {
-        return body.into();
+        return None;
     }
 
-    let Some(embed) = mes.embeds.first() else {
-        return body.into();
-    };
-
-    let mut ret = body.to_string();
-    ret.push_str("投票結果が発表されました。");
-
-    if let Some(text) = embed
+    let winner = mes
+        .embeds
+        .first()?
         .fields
         .iter()
         .find(|field| field.name == "victor_answer_text")
-    {
-        ret = format!("{ret}一位、{}", &text.value);
-    } else {
-        ret.push_str("結論得ず");
-    }
+        .map(|field| field.value.clone());
 
-    ret.into()
+    Some(PollEvent::Closed(winner))
 }
 
-fn sanity_mention<T>(ctx: T, mes: &Message) -> String
+fn mention_names<T>(ctx: T, mes: &Message) -> Vec<(String, String)>
 where
     T: CacheHttp + AsRef<Cache>,
 {
-    let mut s = mes.content.clone();
+    let mut mentions = vec![];
 
     let guild = mes.guild(ctx.cache().unwrap()).unwrap();
 
@@ -188,42 +599,53 @@ where
             .and_then(|member| member.nick.clone())
             .unwrap_or(m.global_name.clone().unwrap_or(m.name.clone()));
 
-        s = s.replace(&m.id.mention().to_string(), &format!("。宛、{name}。"));
+        mentions.push((m.id.mention().to_string(), name));
     }
 
     for m in &mes.mention_roles {
-        let name = guild.roles.get(m).unwrap().name.as_str();
+        let name = guild.roles.get(m).unwrap().name.clone();
 
-        s = s.replace(&m.mention().to_string(), &format!("。宛、{name}。"));
+        mentions.push((m.mention().to_string(), name));
     }
 
     let channel_mentions: Vec<ChannelId> = CHANNEL_MENTION_REGEX
-        .captures_iter(&s)
+        .captures_iter(&mes.content)
         .map(|cap| cap.name("id").unwrap().as_str())
         .map(|s| s.parse::<u64>().unwrap().into())
         .collect();
 
     for m in &channel_mentions {
-        let name = guild.channels.get(m).unwrap().name();
-        s = s.replace(&m.mention().to_string(), &format!("。宛、{name}。"));
+        let name = guild.channels.get(m).unwrap().name().to_string();
+        mentions.push((m.mention().to_string(), name));
+    }
+
+    mentions
+}
+
+fn replace_mentions(mes: &str, mentions: &[(String, String)], template: &str) -> String {
+    let mut s = mes.to_string();
+
+    for (markup, name) in mentions {
+        s = s.replace(markup, &template.replace("{name}", name));
     }
 
     s
 }
 
 #[inline]
-fn legacy_command_compatibility(mes: &str) -> Option<&str> {
-    (!mes.starts_with('~')).then_some(mes)
+fn legacy_command_compatibility<'a>(mes: &'a str, prefix: &str) -> Option<&'a str> {
+    (prefix.is_empty() || !mes.starts_with(prefix)).then_some(mes)
 }
 
 #[inline]
-fn legacy_ping_command_compatibility(mes: &str) -> Option<&str> {
-    (mes != "ping").then_some(mes)
+fn legacy_ping_command_compatibility<'a>(mes: &'a str, command: &str) -> Option<&'a str> {
+    (mes != command).then_some(mes)
 }
 
 #[inline]
-fn suppress_by_semicolon(mes: &str) -> Option<&str> {
-    (!mes.starts_with(';') || mes.starts_with(";;")).then_some(mes)
+fn suppress_by_prefix<'a>(mes: &'a str, prefix: &str) -> Option<&'a str> {
+    (prefix.is_empty() || !mes.starts_with(prefix) || mes[prefix.len()..].starts_with(prefix))
+        .then_some(mes)
 }
 
 #[inline]
@@ -232,23 +654,23 @@ fn suppress_whitespaces(mes: &str) -> Option<&str> {
 }
 
 #[inline]
-fn replace_uri(mes: &str) -> Cow<'_, str> {
-    URI_REGEX.replace_all(mes, "。ユーアールアイ省略。")
+fn replace_uri<'a>(mes: &'a str, replacement: &str) -> Cow<'a, str> {
+    URI_REGEX.replace_all(mes, NoExpand(replacement))
 }
 
 #[inline]
-fn replace_external_emoji(mes: &str) -> Cow<'_, str> {
-    EXTERNAL_EMOJI_REGEX.replace_all(mes, "")
+fn replace_external_emoji<'a>(mes: &'a str, replacement: &str) -> Cow<'a, str> {
+    EXTERNAL_EMOJI_REGEX.replace_all(mes, NoExpand(replacement))
 }
 
 #[inline]
-fn replace_emoji(mes: &str) -> Cow<'_, str> {
-    EMOJI_REGEX.replace_all(mes, "")
+fn replace_emoji<'a>(mes: &'a str, replacement: &str) -> Cow<'a, str> {
+    EMOJI_REGEX.replace_all(mes, NoExpand(replacement))
 }
 
 #[inline]
-fn replace_codeblock(mes: &str) -> Cow<'_, str> {
-    CODEBLOCK_REGEX.replace_all(mes, "。コード省略。")
+fn replace_codeblock<'a>(mes: &'a str, replacement: &str) -> Cow<'a, str> {
+    CODEBLOCK_REGEX.replace_all(mes, NoExpand(replacement))
 }
 
 #[inline]
@@ -264,99 +686,296 @@ fn replace_unicode_emoji(mes: &str) -> String {
 
 #[test]
 fn replace_rule_unit_test() {
-    assert_eq!(legacy_command_compatibility("~join"), None);
-    assert_eq!(legacy_command_compatibility("hello"), Some("hello"));
+    assert_eq!(
+        legacy_command_compatibility("~join", LEGACY_COMMAND_PREFIX),
+        None
+    );
+    assert_eq!(
+        legacy_command_compatibility("hello", LEGACY_COMMAND_PREFIX),
+        Some("hello")
+    );
 
-    assert_eq!(legacy_ping_command_compatibility("ping"), None);
-    assert_eq!(legacy_ping_command_compatibility("hello"), Some("hello"));
+    assert_eq!(
+        legacy_ping_command_compatibility("ping", LEGACY_PING_COMMAND),
+        None
+    );
+    assert_eq!(
+        legacy_ping_command_compatibility("hello", LEGACY_PING_COMMAND),
+        Some("hello")
+    );
 
-    assert_eq!(suppress_by_semicolon("hello"), Some("hello"));
-    assert_eq!(suppress_by_semicolon(";hello"), None);
-    assert_eq!(suppress_by_semicolon(";;hello"), Some(";;hello"));
+    assert_eq!(suppress_by_prefix("hello", ";"), Some("hello"));
+    assert_eq!(suppress_by_prefix(";hello", ";"), None);
+    assert_eq!(suppress_by_prefix(";;hello", ";"), Some(";;hello"));
+    assert_eq!(suppress_by_prefix("//hello", "//"), None);
+    assert_eq!(suppress_by_prefix("////hello", "//"), Some("////hello"));
+    assert_eq!(suppress_by_prefix("/hello", "//"), Some("/hello"));
+    assert_eq!(suppress_by_prefix("//hello", "/"), Some("//hello"));
+    assert_eq!(suppress_by_prefix(";hello", ""), Some(";hello"));
 
-    assert_eq!(replace_uri("hello"), "hello");
+    assert_eq!(replace_uri("hello", Locale::Ja.text(Text::Uri)), "hello");
     assert_eq!(
-        replace_uri("ms-settings:privacy-microphone"),
+        replace_uri("ms-settings:privacy-microphone", Locale::Ja.text(Text::Uri)),
         "。ユーアールアイ省略。"
     );
     assert_eq!(
-        replace_uri("some.strange-protocol+ver2:pathpathpath"),
+        replace_uri(
+            "some.strange-protocol+ver2:pathpathpath",
+            Locale::Ja.text(Text::Uri)
+        ),
         "。ユーアールアイ省略。"
     );
     assert_eq!(
-        replace_uri("20:40に秋葉原にて待つ"),
+        replace_uri("20:40に秋葉原にて待つ", Locale::Ja.text(Text::Uri)),
         "20:40に秋葉原にて待つ"
     );
     assert_eq!(
-        replace_uri("abc,def://nyan.com:22/mofu"),
+        replace_uri("abc,def://nyan.com:22/mofu", Locale::Ja.text(Text::Uri)),
         "abc,。ユーアールアイ省略。"
     );
     assert_eq!(
-        replace_uri("そこから ms-settings:privacy-microphone を開いて"),
+        replace_uri(
+            "そこから ms-settings:privacy-microphone を開いて",
+            Locale::Ja.text(Text::Uri)
+        ),
         "そこから 。ユーアールアイ省略。 を開いて"
     );
     assert_eq!(
-        replace_uri("そこから http://metaba.su を開いて"),
+        replace_uri(
+            "そこから http://metaba.su を開いて",
+            Locale::Ja.text(Text::Uri)
+        ),
         "そこから 。ユーアールアイ省略。 を開いて"
     );
 
-    assert_eq!(replace_emoji("hello!"), "hello!");
-    assert_eq!(replace_emoji("hello:emoji:!"), "hello!");
+    assert_eq!(replace_emoji("hello!", ""), "hello!");
+    assert_eq!(replace_emoji("hello:emoji:!", ""), "hello!");
     assert_eq!(
-        replace_external_emoji("hello<:emoji:012345678901234567>!"),
+        replace_external_emoji("hello<:emoji:012345678901234567>!", ""),
         "hello!"
     );
 
     assert_eq!(
-        replace_codeblock("Codeblock ```Inline``` !"),
+        replace_codeblock("Codeblock ```Inline``` !", Locale::Ja.text(Text::Codeblock)),
         "Codeblock 。コード省略。 !"
     );
     assert_eq!(
-        replace_codeblock("Codeblock\n```\nMultiline\n```\n!"),
+        replace_codeblock(
+            "Codeblock\n```\nMultiline\n```\n!",
+            Locale::Ja.text(Text::Codeblock)
+        ),
         "Codeblock\n。コード省略。\n!"
     );
+}
 
-    assert_eq!(append_attachment_notification("", 0, 0), "");
-    assert_eq!(append_attachment_notification("あ", 0, 0), "あ");
+#[test]
+fn attachment_notification_unit_test() {
+    let catalog = crate::locale::Catalog::default();
+    let texts = catalog.localized(Locale::Ja);
+
+    assert_eq!(append_attachment_notification("", 0, 0, &texts), "");
+    assert_eq!(append_attachment_notification("あ", 0, 0, &texts), "あ");
 
     assert_eq!(
-        append_attachment_notification("", 1, 0),
+        append_attachment_notification("", 1, 0, &texts),
         "画像が送信されました"
     );
     assert_eq!(
-        append_attachment_notification("", 4, 0),
+        append_attachment_notification("", 4, 0, &texts),
         "画像4枚が送信されました"
     );
-    assert_eq!(append_attachment_notification("あ", 1, 0), "あ。画像添付");
     assert_eq!(
-        append_attachment_notification("あ", 4, 0),
+        append_attachment_notification("あ", 1, 0, &texts),
+        "あ。画像添付"
+    );
+    assert_eq!(
+        append_attachment_notification("あ", 4, 0, &texts),
         "あ。画像4枚添付"
     );
 
     assert_eq!(
-        append_attachment_notification("", 0, 1),
+        append_attachment_notification("", 0, 1, &texts),
         "ファイルが送信されました"
     );
     assert_eq!(
-        append_attachment_notification("", 0, 4),
+        append_attachment_notification("", 0, 4, &texts),
         "ファイル4つが送信されました"
     );
     assert_eq!(
-        append_attachment_notification("あ", 0, 1),
+        append_attachment_notification("あ", 0, 1, &texts),
         "あ。ファイル添付"
     );
     assert_eq!(
-        append_attachment_notification("あ", 0, 4),
+        append_attachment_notification("あ", 0, 4, &texts),
         "あ。ファイル4つ添付"
     );
 
     assert_eq!(
-        append_attachment_notification("あ", 1, 4),
+        append_attachment_notification("あ", 1, 4, &texts),
         "あ。画像とファイル4つ添付"
     );
 
     assert_eq!(
-        append_attachment_notification("", 4, 1),
+        append_attachment_notification("", 4, 1, &texts),
         "画像4枚とファイルが送信されました"
     );
 }
+
+#[test]
+fn filter_input_unit_test() {
+    let config = FilterConfig::default();
+    let catalog = crate::locale::Catalog::default();
+    let dictionary = Dictionary::new(&[]).unwrap();
+    let options = FilterOptions {
+        config: &config,
+        texts: catalog.localized(Locale::Ja),
+        suppress_prefix: ";",
+        dictionary: &dictionary,
+    };
+
+    assert_eq!(
+        filter_input(&FilterInput::from_text("hello"), &options).as_deref(),
+        Some("hello")
+    );
+    assert_eq!(
+        filter_input(&FilterInput::from_text(";hello"), &options),
+        None
+    );
+    assert_eq!(filter_input(&FilterInput::from_text(" "), &options), None);
+
+    let input = FilterInput {
+        mentions: vec![("<@1>".to_string(), "yanorei32".to_string())],
+        image_count: 2,
+        forwarded: true,
+        ..FilterInput::from_text("<@1> https://example.com")
+    };
+
+    assert_eq!(
+        filter_input(&input, &options).as_deref(),
+        Some("。宛、yanorei32。 。ユーアールアイ省略。。画像2枚添付。メッセージ転送")
+    );
+
+    let input = FilterInput {
+        poll: Some(PollEvent::Closed(None)),
+        ..FilterInput::default()
+    };
+
+    assert_eq!(
+        filter_input(&input, &options).as_deref(),
+        Some("投票結果が発表されました。結論得ず")
+    );
+
+    let mut stages = vec![];
+    filter_traced(
+        &FilterInput::from_text("~join"),
+        &options,
+        &mut |stage, output| stages.push((stage.to_string(), output.map(str::to_string))),
+    );
+
+    assert_eq!(
+        stages,
+        [
+            ("mentions".to_string(), Some("~join".to_string())),
+            ("legacy_command".to_string(), None),
+        ]
+    );
+}
+
+#[test]
+fn filter_config_unit_test() {
+    let config: FilterConfig = toml::from_str(
+        r#"
+        utc_offset = "-05:00"
+
+        [builtin]
+        legacy_ping = false
+        uri = "(link)"
+        mention = "@{name}"
+        spoiler = "(spoiler)"
+
+        [[rules]]
+        kind = "drop"
+        pattern = "^!"
+
+        [[rules]]
+        name = "laugh"
+        kind = "replace"
+        pattern = "w{3,}"
+        replacement = "lol"
+
+        [[rules]]
+        kind = "suppress_prefix"
+        prefix = "//"
+        "#,
+    )
+    .unwrap();
+
+    let catalog: crate::locale::Catalog = toml::from_str(
+        r#"
+        [en]
+        attachments_appended = ", {attachments}"
+        "#,
+    )
+    .unwrap();
+    let dictionary = Dictionary::new(&[]).unwrap();
+    let options = FilterOptions {
+        config: &config,
+        texts: catalog.localized(Locale::En),
+        suppress_prefix: ";",
+        dictionary: &dictionary,
+    };
+
+    let filter = |text: &str| filter_input(&FilterInput::from_text(text), &options);
+
+    assert_eq!(filter("ping").as_deref(), Some("ping"));
+    assert_eq!(filter("!play"), None);
+    assert_eq!(filter("//note"), None);
+    assert_eq!(filter("wwww").as_deref(), Some("lol"));
+    assert_eq!(filter("see http://x.y").as_deref(), Some("see (link)"));
+    assert_eq!(
+        filter("**[docs](https://x.y)** ||boo||").as_deref(),
+        Some("docs (spoiler)")
+    );
+
+    let input = FilterInput {
+        sent_at: Some(1_700_000_000),
+        ..FilterInput::from_text("<t:1700000000:t>, <t:1700003600:R>")
+    };
+
+    assert_eq!(
+        filter_input(&input, &options).as_deref(),
+        Some("5:13 PM, in an hour")
+    );
+
+    let input = FilterInput {
+        mentions: vec![("<@1>".to_string(), "yanorei32".to_string())],
+        image_count: 1,
+        ..FilterInput::from_text("<@1>")
+    };
+
+    assert_eq!(
+        filter_input(&input, &options).as_deref(),
+        Some("@yanorei32, an image")
+    );
+
+    let mut stages = vec![];
+    filter_traced(&FilterInput::from_text("www"), &options, &mut |stage, _| {
+        stages.push(stage.to_string());
+    });
+
+    assert!(stages.contains(&"laugh".to_string()));
+    assert!(stages.contains(&"rules[0]".to_string()));
+    assert!(!stages.contains(&"legacy_ping".to_string()));
+
+    assert!(
+        toml::from_str::<FilterConfig>(
+            r#"
+            [[rules]]
+            kind = "drop"
+            pattern = "("
+            "#
+        )
+        .is_err()
+    );
+    assert!(toml::from_str::<FilterConfig>(r#"utc_offset = "09:00""#).is_err());
+}
@@ -1,15 +1,17 @@
 use anyhow::Result;
 use reqwest::Url;
 
+use crate::audio::Audio;
+
 const GOOGLE_TTS_MAX_CHARS: usize = 200;
 
-pub async fn get_audio_bytes(
+pub async fn get_audio(
     text: &str,
     lang: &str,
     slow: bool,
     host: &Url,
     volume: f32,
-) -> Result<Vec<u8>> {
+) -> Result<Audio> {
     let parts = crate::tts::split_long_text(text, GOOGLE_TTS_MAX_CHARS);
     let mut combined_audio = Vec::new();
 
@@ -35,5 +37,5 @@ pub async fn get_audio_bytes(
         combined_audio.extend_from_slice(&resp);
     }
 
-    crate::tts::convert_mp3_to_wav(combined_audio, volume)
+    Ok(Audio::from_mp3(combined_audio)?.gain(volume))
 }
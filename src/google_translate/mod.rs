@@ -5,10 +5,12 @@ use async_trait::async_trait;
 use reqwest::Url;
 use serde::Deserialize;
 
+use crate::audio::Audio;
+use crate::model::TtsParams;
 use crate::tts::{CharacterView, StyleView, TtsService};
 
 mod google_tts;
-use google_tts::get_audio_bytes;
+use google_tts::get_audio;
 
 fn default_master_volume() -> f32 {
     1.0
@@ -39,7 +41,7 @@ impl Language {
     }
 }
 
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Deserialize, Debug, Clone, PartialEq)]
 pub struct Setting {
     pub host: Url,
 
@@ -76,8 +78,8 @@ impl GoogleTranslate {
 
 #[async_trait]
 impl TtsService for GoogleTranslate {
-    async fn tts(&self, style_id: &str, text: &str) -> Result<Vec<u8>> {
-        let bytes = get_audio_bytes(
+    async fn tts(&self, style_id: &str, text: &str, _params: &TtsParams) -> Result<Audio> {
+        let audio = get_audio(
             text,
             style_id,
             false,
@@ -86,7 +88,7 @@ impl TtsService for GoogleTranslate {
         )
         .await?;
 
-        Ok(bytes)
+        Ok(audio)
     }
 
     async fn styles(&self) -> Result<Vec<CharacterView>> {
@@ -101,8 +103,12 @@ impl TtsService for GoogleTranslate {
                     name: language.name.clone(),
                     id: language.code.clone(),
                     icon: vec![],
+                    language: Some(language.code.clone()),
+                    gender: None,
                 })
                 .collect(),
+            language: None,
+            gender: None,
         }])
     }
 }
@@ -0,0 +1,163 @@
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+fn default_interval() -> u64 {
+    60
+}
+
+fn default_failure_threshold() -> u32 {
+    3
+}
+
+fn default_cooldown() -> u64 {
+    30
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct HealthConfig {
+    /// Interval of the health probes in seconds. `0` disables them.
+    #[serde(default = "default_interval")]
+    pub interval: u64,
+    /// Consecutive failures after which a service is marked down.
+    #[serde(default = "default_failure_threshold")]
+    pub failure_threshold: u32,
+    /// Seconds a down service is skipped before a single request is let through to retry it.
+    #[serde(default = "default_cooldown")]
+    pub cooldown: u64,
+}
+
+impl Default for HealthConfig {
+    fn default() -> Self {
+        Self {
+            interval: default_interval(),
+            failure_threshold: default_failure_threshold(),
+            cooldown: default_cooldown(),
+        }
+    }
+}
+
+/// Tracks the failures of a service.
+///
+/// The service is marked down after `failure_threshold` consecutive failures. While it is down,
+/// requests are rejected without reaching the backend until `cooldown` has passed since the last
+/// failure; then a single request is let through as a probe and its success brings the service
+/// back. Another probe is let through if the previous one doesn't finish within `cooldown`.
+#[derive(Debug, Default)]
+pub struct CircuitBreaker {
+    failures: u32,
+    down_since: Option<Instant>,
+    last_failure: Option<Instant>,
+    probe_since: Option<Instant>,
+}
+
+impl CircuitBreaker {
+    pub fn is_down(&self) -> bool {
+        self.down_since.is_some()
+    }
+
+    /// How long the service has been down.
+    pub fn downtime(&self, now: Instant) -> Option<Duration> {
+        self.down_since.map(|since| now.duration_since(since))
+    }
+
+    /// Whether requests are rejected without reaching the backend, which is while the service is
+    /// down, except when a probe may be let through.
+    pub fn rejects(&self, config: &HealthConfig, now: Instant) -> bool {
+        let (Some(_), Some(last_failure)) = (self.down_since, self.last_failure) else {
+            return false;
+        };
+
+        let cooldown = Duration::from_secs(config.cooldown);
+        let elapsed = |since: Instant| now.duration_since(since) >= cooldown;
+
+        !elapsed(last_failure) || self.probe_since.is_some_and(|since| !elapsed(since))
+    }
+
+    /// Whether a request may be sent to the backend. A request let through while the service is
+    /// down is taken as the probe.
+    pub fn allow(&mut self, config: &HealthConfig, now: Instant) -> bool {
+        if self.rejects(config, now) {
+            return false;
+        }
+
+        if self.is_down() {
+            self.probe_since = Some(now);
+        }
+
+        true
+    }
+
+    /// Returns `true` if the service has recovered.
+    pub fn record_success(&mut self) -> bool {
+        self.failures = 0;
+        self.last_failure = None;
+        self.probe_since = None;
+        self.down_since.take().is_some()
+    }
+
+    /// Returns `true` if the service has just been marked down.
+    pub fn record_failure(&mut self, config: &HealthConfig, now: Instant) -> bool {
+        self.failures = self.failures.saturating_add(1);
+        self.last_failure = Some(now);
+        self.probe_since = None;
+
+        if self.down_since.is_none() && self.failures >= config.failure_threshold.max(1) {
+            self.down_since = Some(now);
+            return true;
+        }
+
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_circuit_breaker() {
+        let config = HealthConfig {
+            interval: 60,
+            failure_threshold: 2,
+            cooldown: 30,
+        };
+
+        let now = Instant::now();
+        let mut breaker = CircuitBreaker::default();
+
+        assert!(!breaker.record_failure(&config, now));
+        assert!(!breaker.is_down());
+        assert!(breaker.allow(&config, now));
+        assert!(breaker.allow(&config, now));
+
+        assert!(breaker.record_failure(&config, now));
+        assert!(breaker.is_down());
+        assert!(breaker.rejects(&config, now + Duration::from_secs(10)));
+        assert!(!breaker.allow(&config, now + Duration::from_secs(10)));
+
+        // Only a single probe is let through after the cooldown.
+        let retry = now + Duration::from_secs(30);
+        assert!(!breaker.rejects(&config, retry));
+        assert!(breaker.allow(&config, retry));
+        assert!(breaker.rejects(&config, retry));
+        assert!(!breaker.allow(&config, retry));
+
+        // A failed probe keeps it down and restarts the cooldown.
+        assert!(!breaker.record_failure(&config, retry));
+        assert!(!breaker.allow(&config, retry + Duration::from_secs(10)));
+        assert_eq!(breaker.downtime(retry), Some(Duration::from_secs(30)));
+
+        // A probe which never finishes is replaced after the cooldown.
+        let retry = retry + Duration::from_secs(30);
+        assert!(breaker.allow(&config, retry));
+        assert!(!breaker.allow(&config, retry + Duration::from_secs(10)));
+        assert!(breaker.allow(&config, retry + Duration::from_secs(30)));
+
+        assert!(breaker.record_success());
+        assert!(!breaker.is_down());
+        assert!(breaker.allow(&config, retry));
+        assert!(breaker.allow(&config, retry));
+        assert!(!breaker.record_success());
+    }
+}
@@ -0,0 +1,104 @@
+//! HTTP endpoint for other systems to read text aloud in a voice channel.
+
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+use axum::{Json, Router, extract::State, http::StatusCode, middleware, routing::post};
+use serde::Deserialize;
+use serenity::{http::Http, model::id::GuildId};
+use songbird::Songbird;
+
+use crate::admin::{require_token, validate_params};
+use crate::guild_config;
+use crate::model::TtsStyle;
+use crate::pipeline::{Pipeline, SpeechRequest};
+use crate::tts::TtsServices;
+
+pub struct Ingress {
+    pub http: Arc<Http>,
+    pub tts_services: TtsServices,
+    pub songbird: Arc<Songbird>,
+    pub pipeline: Arc<Pipeline>,
+}
+
+#[derive(Deserialize)]
+struct SpeakRequest {
+    guild_id: GuildId,
+    text: String,
+    /// The default style of the guild if omitted.
+    #[serde(default)]
+    style: Option<TtsStyle>,
+}
+
+async fn speak(
+    State(ingress): State<Arc<Ingress>>,
+    Json(request): Json<SpeakRequest>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    if request.text.trim().is_empty() {
+        return Err((StatusCode::BAD_REQUEST, "Text is empty".to_string()));
+    }
+
+    let in_voice_channel = match ingress.songbird.get(request.guild_id) {
+        Some(call) => call.lock().await.current_channel().is_some(),
+        None => false,
+    };
+
+    if !in_voice_channel {
+        return Err((StatusCode::CONFLICT, "Not in a voice channel".to_string()));
+    }
+
+    let style = request
+        .style
+        .unwrap_or_else(|| guild_config(request.guild_id).default_style);
+
+    validate_params(&style.params)?;
+
+    if !ingress
+        .tts_services
+        .is_available(&style.service_id, &style.style_id)
+        .await
+    {
+        return Err((
+            StatusCode::UNPROCESSABLE_ENTITY,
+            format!("{}/{} is not available", style.service_id, style.style_id),
+        ));
+    }
+
+    ingress.pipeline.submit(
+        &ingress.http,
+        request.guild_id,
+        SpeechRequest {
+            style,
+            text: request.text,
+            reply_to: None,
+        },
+    );
+
+    Ok(StatusCode::ACCEPTED)
+}
+
+/// Serves `POST /speak` on `addr`. Requests need `Authorization: Bearer <token>`.
+pub async fn spawn(addr: SocketAddr, token: String, ingress: Ingress) -> Result<()> {
+    let app = Router::new()
+        .route("/speak", post(speak))
+        .layer(middleware::from_fn_with_state(
+            Arc::from(token),
+            require_token,
+        ))
+        .with_state(Arc::new(ingress));
+
+    let listener = tokio::net::TcpListener::bind(addr)
+        .await
+        .with_context(|| format!("Failed to listen on {addr}"))?;
+
+    tracing::info!("Accepting speech requests on {addr}");
+
+    tokio::spawn(async move {
+        if let Err(e) = axum::serve(listener, app).await {
+            tracing::warn!("Speech ingress stopped: {e}");
+        }
+    });
+
+    Ok(())
+}
@@ -1,14 +1,14 @@
 use std::collections::HashMap;
-use std::io::Cursor;
 use std::sync::Arc;
 
 use anyhow::{Context, Result};
 use async_trait::async_trait;
-use hound::{WavReader, WavWriter};
 use reqwest::header::{HeaderMap, HeaderName};
 use serde::Deserialize;
 use tap::Tap;
 
+use crate::audio::Audio;
+use crate::model::TtsParams;
 use crate::tts::{CharacterView, StyleView, TtsService};
 
 mod api;
@@ -25,7 +25,7 @@ fn default_master_volume() -> f32 {
     1.0
 }
 
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Deserialize, Debug, Clone, PartialEq)]
 pub struct Setting {
     pub url: reqwest::Url,
     #[serde(default = "default_headers")]
@@ -46,30 +46,6 @@ struct KTTSInner {
     master_volume: f32,
 }
 
-fn gain(buffer: &[u8], gain: f32) -> Result<Vec<u8>> {
-    let mut in_wav =
-        WavReader::new(Cursor::new(buffer)).with_context(|| "Failed to read as wav file")?;
-    let spec = in_wav.spec();
-    let mut buffer = Cursor::new(vec![]);
-    let mut out_wav = WavWriter::new(&mut buffer, spec).with_context(|| "Failed to create wav")?;
-
-    for sample in in_wav.samples::<i16>().map(|s| s.unwrap()) {
-        let sample = f32::from(sample) * gain;
-        let sample = sample.min(f32::from(i16::MAX)).max(f32::from(i16::MIN));
-
-        #[allow(clippy::cast_possible_truncation)]
-        out_wav
-            .write_sample::<i16>(sample as i16)
-            .with_context(|| "Failed to write sample")?;
-    }
-
-    out_wav
-        .finalize()
-        .with_context(|| "Failed to finalize wav file")?;
-
-    Ok(buffer.into_inner())
-}
-
 #[allow(clippy::upper_case_acronyms)]
 #[derive(Clone, Debug)]
 pub struct KTTS {
@@ -122,7 +98,7 @@ impl KTTS {
 
 #[async_trait]
 impl TtsService for KTTS {
-    async fn tts(&self, style_id: &str, text: &str) -> Result<Vec<u8>> {
+    async fn tts(&self, style_id: &str, text: &str, _params: &TtsParams) -> Result<Audio> {
         let api_tts = self.inner.url.clone().tap_mut(|u| {
             u.path_segments_mut().unwrap().push("api").push("tts");
         });
@@ -176,7 +152,7 @@ impl TtsService for KTTS {
             .await
             .context("Failed to post /api/tts (body)")?;
 
-        Ok(gain(&resp, self.inner.master_volume)?)
+        Ok(Audio::from_wav(&resp)?.gain(self.inner.master_volume))
     }
 
     async fn styles(&self) -> Result<Vec<CharacterView>> {
@@ -187,6 +163,8 @@ impl TtsService for KTTS {
                 icon: vec![],
                 name: "Default with G2P".to_string(),
                 id: "G2P".to_string(),
+                language: None,
+                gender: None,
             });
         }
 
@@ -194,12 +172,16 @@ impl TtsService for KTTS {
             icon: vec![],
             name: "Default".to_string(),
             id: "Default".to_string(),
+            language: None,
+            gender: None,
         });
 
         Ok(vec![CharacterView {
             name: "Default".to_string(),
             policy: "조선어음성합성프로그람 《청봉》 3.2 by RedStar 3.0".to_string(),
             styles,
+            language: Some("ko".to_string()),
+            gender: None,
         }])
     }
 }
@@ -0,0 +1,551 @@
+use std::collections::HashMap;
+
+use clap::ValueEnum;
+use once_cell::sync::Lazy;
+use regex::{Captures, Regex};
+use serde::{Deserialize, Serialize};
+use serenity::builder::CreateCommand;
+use time::{Month, Weekday};
+
+// regex crate's named capture
+#[allow(clippy::invalid_regex)]
+static PLACEHOLDER_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new(r"\{(?<key>\w+)\}").unwrap());
+
+#[derive(Serialize, Deserialize, ValueEnum, Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Locale {
+    #[default]
+    Ja,
+    En,
+    Ko,
+}
+
+impl Locale {
+    pub const ALL: [Self; 3] = [Self::Ja, Self::En, Self::Ko];
+
+    pub fn code(self) -> &'static str {
+        match self {
+            Self::Ja => "ja",
+            Self::En => "en",
+            Self::Ko => "ko",
+        }
+    }
+
+    /// Name of the language in itself.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Ja => "日本語",
+            Self::En => "English",
+            Self::Ko => "한국어",
+        }
+    }
+
+    /// Locales of Discord in the language.
+    fn discord_locales(self) -> &'static [&'static str] {
+        match self {
+            Self::Ja => &["ja"],
+            Self::En => &["en-US", "en-GB"],
+            Self::Ko => &["ko"],
+        }
+    }
+
+    /// The locale of a BCP 47 language tag such as `en-US`.
+    pub fn from_language(language: &str) -> Option<Self> {
+        let primary = language.split(['-', '_']).next()?;
+
+        Self::ALL
+            .into_iter()
+            .find(|locale| primary.eq_ignore_ascii_case(locale.code()))
+    }
+
+    pub fn weekday_name(self, weekday: Weekday) -> &'static str {
+        let names = match self {
+            Self::Ja => [
+                "月曜日",
+                "火曜日",
+                "水曜日",
+                "木曜日",
+                "金曜日",
+                "土曜日",
+                "日曜日",
+            ],
+            Self::En => [
+                "Monday",
+                "Tuesday",
+                "Wednesday",
+                "Thursday",
+                "Friday",
+                "Saturday",
+                "Sunday",
+            ],
+            Self::Ko => [
+                "월요일",
+                "화요일",
+                "수요일",
+                "목요일",
+                "금요일",
+                "토요일",
+                "일요일",
+            ],
+        };
+
+        names[usize::from(weekday.number_days_from_monday())]
+    }
+
+    pub fn month_name(self, month: Month) -> String {
+        const EN: [&str; 12] = [
+            "January",
+            "February",
+            "March",
+            "April",
+            "May",
+            "June",
+            "July",
+            "August",
+            "September",
+            "October",
+            "November",
+            "December",
+        ];
+
+        let n = u8::from(month);
+
+        match self {
+            Self::Ja => format!("{n}月"),
+            Self::En => EN[usize::from(n - 1)].to_string(),
+            Self::Ko => format!("{n}월"),
+        }
+    }
+
+    /// The built-in text.
+    pub fn text(self, text: Text) -> &'static str {
+        match self {
+            Self::Ja => ja(text),
+            Self::En => en(text),
+            Self::Ko => ko(text),
+        }
+    }
+}
+
+/// Keys of the message catalog. `{...}` in a text is replaced by an argument.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum Text {
+    /// `{name}` joined the voice channel.
+    Joined,
+    /// `{name}` left the voice channel.
+    Left,
+    /// Read in place of the mention of `{name}`.
+    Mention,
+    Uri,
+    Codeblock,
+    Image,
+    /// `{n}` images.
+    Images,
+    File,
+    /// `{n}` files.
+    Files,
+    /// Joins the images and the files.
+    AttachmentSeparator,
+    /// `{attachments}` are sent without text.
+    AttachmentsSent,
+    /// `{attachments}` are appended to text.
+    AttachmentsAppended,
+    Forwarded,
+    ForwardAppended,
+    PollCreated,
+    /// `{question}` of the poll appended to `poll_created`.
+    PollQuestion,
+    PollClosed,
+    /// `{answer}` which won the poll appended to `poll_closed`.
+    PollWinner,
+    PollNoWinner,
+    /// Read in place of a skipped spoiler.
+    Spoiler,
+
+    /// `{year}`, `{month}`, `{month_name}` and `{day}`.
+    Date,
+    /// `{hour}`, `{hour12}`, `{minute}`, `{minute2}` (zero-padded) and `{am_pm}`.
+    Time,
+    /// `Time` with `{second}` and `{second2}`.
+    LongTime,
+    /// `{date}` and `{time}`.
+    DateTime,
+    /// `DateTime` with `{weekday}`.
+    LongDateTime,
+    Am,
+    Pm,
+    Now,
+    /// `{amount}` of time ago.
+    RelativePast,
+    /// In `{amount}` of time.
+    RelativeFuture,
+    Minute,
+    /// `{n}` minutes.
+    Minutes,
+    Hour,
+    Hours,
+    Day,
+    Days,
+    Month,
+    Months,
+    Year,
+    Years,
+
+    NotInVoiceChannel,
+    YouAreNotInVoiceChannel,
+    FailedToJoinVoiceChannel,
+    /// `{channel}` cannot be accessed.
+    CannotAccessChannel,
+    /// `{text}` and `{voice}` channels are linked.
+    Linked,
+    /// `{text}` and `{voice}` channels are linked again after a restart.
+    Reconnected,
+    Disconnected,
+    Skipped,
+
+    JoinName,
+    JoinDescription,
+    LeaveName,
+    LeaveDescription,
+    SkipName,
+    SkipDescription,
+    SpeakerName,
+    SpeakerDescription,
+    DictName,
+    DictDescription,
+    DictListName,
+    DictListDescription,
+    ConfigName,
+    ConfigDescription,
+    RefreshName,
+    RefreshDescription,
+}
+
+fn ja(text: Text) -> &'static str {
+    match text {
+        Text::Joined => "{name}が参加しました",
+        Text::Left => "{name}が退出しました",
+        Text::Mention => "。宛、{name}。",
+        Text::Uri => "。ユーアールアイ省略。",
+        Text::Codeblock => "。コード省略。",
+        Text::Image => "画像",
+        Text::Images => "画像{n}枚",
+        Text::File => "ファイル",
+        Text::Files => "ファイル{n}つ",
+        Text::AttachmentSeparator => "と",
+        Text::AttachmentsSent => "{attachments}が送信されました",
+        Text::AttachmentsAppended => "。{attachments}添付",
+        Text::Forwarded => "メッセージが転送されました。",
+        Text::ForwardAppended => "。メッセージ転送",
+        Text::PollCreated => "新たな投票が作成されました",
+        Text::PollQuestion => "。{question}",
+        Text::PollClosed => "投票結果が発表されました。",
+        Text::PollWinner => "一位、{answer}",
+        Text::PollNoWinner => "結論得ず",
+        Text::Spoiler => "。伏せ字。",
+
+        Text::Date => "{year}年{month}月{day}日",
+        Text::Time => "{hour}時{minute}分",
+        Text::LongTime => "{hour}時{minute}分{second}秒",
+        Text::DateTime => "{date} {time}",
+        Text::LongDateTime => "{date}{weekday} {time}",
+        Text::Am => "午前",
+        Text::Pm => "午後",
+        Text::Now => "たった今",
+        Text::RelativePast => "{amount}前",
+        Text::RelativeFuture => "{amount}後",
+        Text::Minute => "1分",
+        Text::Minutes => "{n}分",
+        Text::Hour => "1時間",
+        Text::Hours => "{n}時間",
+        Text::Day => "1日",
+        Text::Days => "{n}日",
+        Text::Month => "1か月",
+        Text::Months => "{n}か月",
+        Text::Year => "1年",
+        Text::Years => "{n}年",
+
+        Text::NotInVoiceChannel => "ボイスチャンネルに参加していません。",
+        Text::YouAreNotInVoiceChannel => "ボイスチャンネルに参加してから実行してください",
+        Text::FailedToJoinVoiceChannel => "ボイスチャンネルに参加できませんでした",
+        Text::CannotAccessChannel => "{channel}にアクセスできません",
+        Text::Linked => "接続しました！ {text} <-> {voice}",
+        Text::Reconnected => "再接続しました！ {text} <-> {voice}",
+        Text::Disconnected => "切断しました。",
+        Text::Skipped => "スキップしました！",
+
+        Text::JoinName => "参加",
+        Text::JoinDescription => "あなたのボイスチャンネルに参加します",
+        Text::LeaveName => "退出",
+        Text::LeaveDescription => "ボイスチャンネルから退出します",
+        Text::SkipName => "スキップ",
+        Text::SkipDescription => "読み上げ中のメッセージをスキップします",
+        Text::SpeakerName => "話者",
+        Text::SpeakerDescription => "あなたの話者を設定します",
+        Text::DictName => "辞書",
+        Text::DictDescription => "このサーバーの読み方辞書を管理します",
+        Text::DictListName => "辞書一覧",
+        Text::DictListDescription => "このサーバーの読み方辞書の単語を表示します",
+        Text::ConfigName => "設定",
+        Text::ConfigDescription => "このサーバーでのボットの設定を変更します",
+        Text::RefreshName => "更新",
+        Text::RefreshDescription => "TTSサービスの音声一覧を再取得します",
+    }
+}
+
+fn en(text: Text) -> &'static str {
+    match text {
+        Text::Joined => "{name} joined",
+        Text::Left => "{name} left",
+        Text::Mention => ". To {name}. ",
+        Text::Uri => ". URL omitted. ",
+        Text::Codeblock => ". Code omitted. ",
+        Text::Image => "an image",
+        Text::Images => "{n} images",
+        Text::File => "a file",
+        Text::Files => "{n} files",
+        Text::AttachmentSeparator => " and ",
+        Text::AttachmentsSent => "Sent {attachments}",
+        Text::AttachmentsAppended => ". With {attachments}",
+        Text::Forwarded => "A message was forwarded.",
+        Text::ForwardAppended => ". Forwarded",
+        Text::PollCreated => "A new poll was created",
+        Text::PollQuestion => ". {question}",
+        Text::PollClosed => "The poll has ended. ",
+        Text::PollWinner => "The winner is {answer}",
+        Text::PollNoWinner => "No winner",
+        Text::Spoiler => ". Spoiler. ",
+
+        Text::Date => "{month_name} {day}, {year}",
+        Text::Time => "{hour12}:{minute2} {am_pm}",
+        Text::LongTime => "{hour12}:{minute2}:{second2} {am_pm}",
+        Text::DateTime => "{date} at {time}",
+        Text::LongDateTime => "{weekday}, {date} at {time}",
+        Text::Am => "AM",
+        Text::Pm => "PM",
+        Text::Now => "just now",
+        Text::RelativePast => "{amount} ago",
+        Text::RelativeFuture => "in {amount}",
+        Text::Minute => "a minute",
+        Text::Minutes => "{n} minutes",
+        Text::Hour => "an hour",
+        Text::Hours => "{n} hours",
+        Text::Day => "a day",
+        Text::Days => "{n} days",
+        Text::Month => "a month",
+        Text::Months => "{n} months",
+        Text::Year => "a year",
+        Text::Years => "{n} years",
+
+        Text::NotInVoiceChannel => "Not in a voice channel.",
+        Text::YouAreNotInVoiceChannel => "You are not in voice channel",
+        Text::FailedToJoinVoiceChannel => "Failed to join to voice channel",
+        Text::CannotAccessChannel => "Cannot access to {channel}",
+        Text::Linked => "Linked! {text} <-> {voice}",
+        Text::Reconnected => "Reconnected! {text} <-> {voice}",
+        Text::Disconnected => "Connection has been closed.",
+        Text::Skipped => "Skipped!",
+
+        Text::JoinName => "join",
+        Text::JoinDescription => "Join to your channel",
+        Text::LeaveName => "leave",
+        Text::LeaveDescription => "Leave from VC",
+        Text::SkipName => "skip",
+        Text::SkipDescription => "Skip a current message",
+        Text::SpeakerName => "speaker",
+        Text::SpeakerDescription => "Manage your speaker",
+        Text::DictName => "dict",
+        Text::DictDescription => "Manage the pronunciation dictionary of this server",
+        Text::DictListName => "dict_list",
+        Text::DictListDescription => {
+            "List the words in the pronunciation dictionary of this server"
+        }
+        Text::ConfigName => "config",
+        Text::ConfigDescription => "Configure the bot for this server",
+        Text::RefreshName => "refresh",
+        Text::RefreshDescription => "Re-query the voice catalogs of the TTS services",
+    }
+}
+
+fn ko(text: Text) -> &'static str {
+    match text {
+        Text::Joined => "{name} 님이 참가했습니다",
+        Text::Left => "{name} 님이 나갔습니다",
+        Text::Mention => ". {name}에게. ",
+        Text::Uri => ". URL 생략. ",
+        Text::Codeblock => ". 코드 생략. ",
+        Text::Image => "이미지",
+        Text::Images => "이미지 {n}장",
+        Text::File => "파일",
+        Text::Files => "파일 {n}개",
+        Text::AttachmentSeparator => ", ",
+        Text::AttachmentsSent => "{attachments} 전송됨",
+        Text::AttachmentsAppended => ". {attachments} 첨부",
+        Text::Forwarded => "메시지가 전달되었습니다.",
+        Text::ForwardAppended => ". 메시지 전달",
+        Text::PollCreated => "새 투표가 만들어졌습니다",
+        Text::PollQuestion => ". {question}",
+        Text::PollClosed => "투표가 종료되었습니다. ",
+        Text::PollWinner => "1위, {answer}",
+        Text::PollNoWinner => "결론 없음",
+        Text::Spoiler => ". 스포일러. ",
+
+        Text::Date => "{year}년 {month}월 {day}일",
+        Text::Time => "{am_pm} {hour12}시 {minute}분",
+        Text::LongTime => "{am_pm} {hour12}시 {minute}분 {second}초",
+        Text::DateTime => "{date} {time}",
+        Text::LongDateTime => "{date} {weekday} {time}",
+        Text::Am => "오전",
+        Text::Pm => "오후",
+        Text::Now => "방금",
+        Text::RelativePast => "{amount} 전",
+        Text::RelativeFuture => "{amount} 후",
+        Text::Minute => "1분",
+        Text::Minutes => "{n}분",
+        Text::Hour => "1시간",
+        Text::Hours => "{n}시간",
+        Text::Day => "1일",
+        Text::Days => "{n}일",
+        Text::Month => "1개월",
+        Text::Months => "{n}개월",
+        Text::Year => "1년",
+        Text::Years => "{n}년",
+
+        Text::NotInVoiceChannel => "음성 채널에 참가하고 있지 않습니다.",
+        Text::YouAreNotInVoiceChannel => "먼저 음성 채널에 참가해 주세요",
+        Text::FailedToJoinVoiceChannel => "음성 채널에 참가하지 못했습니다",
+        Text::CannotAccessChannel => "{channel}에 접근할 수 없습니다",
+        Text::Linked => "연결했습니다! {text} <-> {voice}",
+        Text::Reconnected => "다시 연결했습니다! {text} <-> {voice}",
+        Text::Disconnected => "연결을 종료했습니다.",
+        Text::Skipped => "건너뛰었습니다!",
+
+        Text::JoinName => "참가",
+        Text::JoinDescription => "내 음성 채널에 참가합니다",
+        Text::LeaveName => "퇴장",
+        Text::LeaveDescription => "음성 채널에서 나갑니다",
+        Text::SkipName => "건너뛰기",
+        Text::SkipDescription => "읽고 있는 메시지를 건너뜁니다",
+        Text::SpeakerName => "목소리",
+        Text::SpeakerDescription => "내 목소리를 설정합니다",
+        Text::DictName => "사전",
+        Text::DictDescription => "이 서버의 발음 사전을 관리합니다",
+        Text::DictListName => "사전목록",
+        Text::DictListDescription => "이 서버의 발음 사전 단어를 표시합니다",
+        Text::ConfigName => "설정",
+        Text::ConfigDescription => "이 서버의 봇 설정을 변경합니다",
+        Text::RefreshName => "새로고침",
+        Text::RefreshDescription => "TTS 서비스의 음성 목록을 다시 가져옵니다",
+    }
+}
+
+/// The `texts` table of the TTS config, which overrides the built-in texts by locale.
+#[derive(Deserialize, Debug, Clone, Default)]
+#[serde(transparent)]
+pub struct Catalog {
+    overrides: HashMap<Locale, HashMap<Text, String>>,
+}
+
+impl Catalog {
+    pub fn localized(&self, locale: Locale) -> Localized<'_> {
+        Localized {
+            catalog: self,
+            locale,
+        }
+    }
+}
+
+/// The texts of a locale.
+#[derive(Debug, Clone, Copy)]
+pub struct Localized<'a> {
+    catalog: &'a Catalog,
+    pub locale: Locale,
+}
+
+impl<'a> Localized<'a> {
+    pub fn get(&self, text: Text) -> &'a str {
+        self.catalog
+            .overrides
+            .get(&self.locale)
+            .and_then(|texts| texts.get(&text))
+            .map_or_else(|| self.locale.text(text), String::as_str)
+    }
+
+    /// The text with each `{key}` replaced by its value. The values are not scanned for keys,
+    /// since they may come from users such as the names of the channels.
+    pub fn format(&self, text: Text, args: &[(&str, &str)]) -> String {
+        PLACEHOLDER_REGEX
+            .replace_all(self.get(text), |cap: &Captures| {
+                args.iter()
+                    .find(|(key, _)| *key == &cap["key"])
+                    .map_or_else(|| cap[0].to_string(), |(_, value)| (*value).to_string())
+            })
+            .into_owned()
+    }
+}
+
+/// Adds the names and the descriptions in the locales other than English, the default.
+pub fn localize_command(
+    mut command: CreateCommand,
+    prefix: &str,
+    name: Text,
+    description: Text,
+) -> CreateCommand {
+    for locale in Locale::ALL {
+        if locale == Locale::En {
+            continue;
+        }
+
+        for discord_locale in locale.discord_locales() {
+            command = command
+                .name_localized(*discord_locale, format!("{prefix}{}", locale.text(name)))
+                .description_localized(*discord_locale, locale.text(description));
+        }
+    }
+
+    command
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_language() {
+        assert_eq!(Locale::from_language("ja"), Some(Locale::Ja));
+        assert_eq!(Locale::from_language("en-US"), Some(Locale::En));
+        assert_eq!(Locale::from_language("ko_KR"), Some(Locale::Ko));
+        assert_eq!(Locale::from_language("zh-CN"), None);
+        assert_eq!(Locale::from_language(""), None);
+    }
+
+    #[test]
+    fn test_catalog() {
+        let catalog: Catalog = toml::from_str(
+            r#"
+            [en]
+            joined = "Welcome, {name}"
+            "#,
+        )
+        .unwrap();
+
+        let en = catalog.localized(Locale::En);
+        assert_eq!(en.format(Text::Joined, &[("name", "Rei")]), "Welcome, Rei");
+        assert_eq!(en.format(Text::Left, &[("name", "Rei")]), "Rei left");
+
+        let ja = catalog.localized(Locale::Ja);
+        assert_eq!(
+            ja.format(Text::Joined, &[("name", "Rei")]),
+            "Reiが参加しました"
+        );
+
+        // A value looking like a key is left as it is.
+        assert_eq!(
+            en.format(Text::Linked, &[("text", "{voice}"), ("voice", "General")]),
+            "Linked! {voice} <-> General"
+        );
+
+        assert!(toml::from_str::<Catalog>("[en]\nunknown = \"\"").is_err());
+    }
+}
@@ -1,21 +1,34 @@
 #![warn(clippy::pedantic)]
 #![allow(clippy::similar_names)]
 
+mod admin;
 mod android_tts;
+mod audio;
 mod bing_speech;
+mod cache;
 mod capcutttswrapper;
 mod coefont_try;
 mod commands;
 mod db;
+mod dictionary;
 mod filter;
 mod google_translate;
+mod health;
+mod ingress;
 mod ktts;
+mod locale;
+mod markdown;
+mod metrics;
 mod mirae_tts;
 mod model;
 mod naver;
 mod omnivoice;
+mod pipeline;
+mod reload;
 mod sayserver;
+mod session;
 mod songbird_handler;
+mod subcommands;
 mod timestretch;
 mod tts;
 mod voiceroid;
@@ -24,13 +37,13 @@ mod volcengine;
 mod wavsource;
 mod winrttts;
 
-use std::io::Cursor;
+use std::sync::Arc;
 
-use anyhow::Context as _;
+use arc_swap::ArcSwap;
 use clap::Parser;
 use once_cell::sync::OnceCell;
 use serenity::{
-    all::{ChunkGuildFilter, Guild},
+    all::{ChunkGuildFilter, Guild, GuildId, UserId},
     async_trait,
     client::{Client, Context, EventHandler},
     model::{
@@ -41,31 +54,52 @@ use serenity::{
         voice::VoiceState,
     },
 };
-use songbird::SerenityInit;
+use songbird::{SerenityInit, Songbird};
 
-use crate::android_tts::AndroidTTS;
-use crate::bing_speech::BingSpeech;
-use crate::capcutttswrapper::CapCutTTSWrapper;
-use crate::coefont_try::CoefontTry;
+use crate::cache::TtsCache;
 use crate::db::{INMEMORY_DB, PERSISTENT_DB};
-use crate::google_translate::GoogleTranslate;
-use crate::ktts::KTTS;
-use crate::mirae_tts::MiraeTTS;
-use crate::model::TtsServiceConfig;
-use crate::naver::Naver;
-use crate::omnivoice::OmniVoice;
-use crate::sayserver::SayServer;
+use crate::locale::{Locale, Text};
+use crate::pipeline::{Pipeline, SpeechRequest};
+use crate::reload::Reloader;
 use crate::tts::TtsServices;
-use crate::voiceroid::Voiceroid;
-use crate::voicevox::Voicevox;
-use crate::volcengine::Volcengine;
-use crate::winrttts::WinRTTTS;
 
 struct Bot {
     tts_services: TtsServices,
+    pipeline: Arc<Pipeline>,
     prefix: String,
-    timestretch_config: model::TimeStretchConfig,
-    auto_leave_when_alone: bool,
+}
+
+impl Bot {
+    /// The style of the user, falling back to the default of the guild if it is not available,
+    /// and the language to speak in.
+    async fn speaker(&self, user: UserId, config: &model::LiveConfig) -> (model::TtsStyle, Locale) {
+        let speaker = PERSISTENT_DB
+            .get_voice_setting(user)
+            .unwrap_or_else(|| config.default_style.clone());
+
+        // Check avialablity
+        let speaker = if self
+            .tts_services
+            .is_available(&speaker.service_id, &speaker.style_id)
+            .await
+        {
+            speaker
+        } else {
+            model::TtsStyle {
+                params: speaker.params,
+                ..config.default_style.clone()
+            }
+        };
+
+        let locale = self
+            .tts_services
+            .style_language(&speaker)
+            .await
+            .and_then(|language| Locale::from_language(&language))
+            .unwrap_or(config.locale);
+
+        (speaker, locale)
+    }
 }
 
 #[async_trait]
@@ -78,6 +112,10 @@ impl EventHandler for Bot {
                 commands::leave::register(&self.prefix),
                 commands::skip::register(&self.prefix),
                 commands::speaker::register(&self.prefix),
+                commands::dict::register(&self.prefix),
+                commands::dict::register_list(&self.prefix),
+                commands::refresh::register(&self.prefix),
+                commands::config::register(&self.prefix),
             ],
         )
         .await
@@ -86,61 +124,41 @@ impl EventHandler for Bot {
         println!("{} is connected!", ready.user.name);
     }
 
+    async fn cache_ready(&self, ctx: Context, _guilds: Vec<GuildId>) {
+        // Voice states of the guilds are known once they are cached.
+        session::restore(&ctx).await;
+    }
+
     async fn guild_create(&self, ctx: Context, guild: Guild, _is_new: Option<bool>) {
         ctx.shard
             .chunk_guild(guild.id, None, false, ChunkGuildFilter::None, None);
     }
 
     async fn message(&self, ctx: Context, msg: Message) {
-        let Some(content) = filter::filter(&ctx, &msg) else {
+        let Some(guild_id) = msg.guild_id else {
             return;
         };
 
-        let speaker = PERSISTENT_DB
-            .get_voice_setting(msg.author.id)
-            .unwrap_or(DEFAULT_TTS_STYLE.get().unwrap().clone());
-
-        // Check avialablity
-        let speaker = if self
-            .tts_services
-            .is_available(&speaker.service_id, &speaker.style_id)
-            .await
-        {
-            speaker
-        } else {
-            DEFAULT_TTS_STYLE.get().unwrap().clone()
-        };
-
-        let manager = songbird::get(&ctx)
-            .await
-            .expect("Songbird is not initialized");
+        // Resolving the speaker queries the DB, so skip the other channels beforehand.
+        if INMEMORY_DB.get_instance(guild_id) != Some(msg.channel_id) {
+            return;
+        }
 
-        let handler = manager.get(msg.guild_id.unwrap()).unwrap();
+        let (speaker, locale) = self.speaker(msg.author.id, &guild_config(guild_id)).await;
 
-        let wav = match self
-            .tts_services
-            .tts(&speaker.service_id, &speaker.style_id, &content)
-            .await
-        {
-            Err(e) => {
-                msg.reply(
-                    &ctx.http,
-                    &format!("Error: Failed to synthesise a message {e}"),
-                )
-                .await
-                .unwrap();
-                return;
-            }
-            Ok(v) => v,
+        let Some(content) = filter::filter(&ctx, &msg, locale) else {
+            return;
         };
 
-        let (source, sample_rate) =
-            wavsource::WavSource::new(&mut Cursor::new(wav), &self.timestretch_config);
-        handler
-            .lock()
-            .await
-            .enqueue_input(songbird::input::RawAdapter::new(source, sample_rate, 1).into())
-            .await;
+        self.pipeline.submit(
+            &ctx.http,
+            guild_id,
+            SpeechRequest {
+                style: speaker,
+                text: content,
+                reply_to: Some(msg),
+            },
+        );
     }
 
     async fn interaction_create(&self, ctx: Context, interaction: Interaction) {
@@ -153,11 +171,24 @@ impl EventHandler for Bot {
                 s if s == format!("{prefix}join") => commands::join::run(&ctx, command).await,
                 s if s == format!("{prefix}leave") => commands::leave::run(&ctx, command).await,
                 s if s == format!("{prefix}skip") => commands::skip::run(&ctx, command).await,
+                s if s == format!("{prefix}dict") => commands::dict::run(&ctx, command).await,
+                s if s == format!("{prefix}dict_list") => {
+                    commands::dict::run_list(&ctx, command).await;
+                }
+                s if s == format!("{prefix}refresh") => {
+                    commands::refresh::run(&ctx, command, &self.tts_services).await;
+                }
+                s if s == format!("{prefix}config") => {
+                    commands::config::run(&ctx, command, &self.tts_services).await;
+                }
                 _ => unreachable!("Unknown command: {}", command.data.name),
             },
             Interaction::Component(interaction) => {
                 commands::speaker::update(&ctx, interaction, &self.tts_services).await;
             }
+            Interaction::Modal(interaction) => {
+                commands::speaker::update_params(&ctx, interaction, &self.tts_services).await;
+            }
             _ => {}
         }
     }
@@ -168,6 +199,17 @@ impl EventHandler for Bot {
             return;
         };
 
+        if new.user_id == ctx.cache.current_user().id {
+            match new.channel_id {
+                // Stop synthesizing for a guild the bot has left
+                None => self.pipeline.remove(guild_id),
+                // Restore the channel the bot has been moved to
+                Some(channel_id) => {
+                    PERSISTENT_DB.update_session_voice_channel(guild_id, channel_id);
+                }
+            }
+        }
+
         let Some(_text_channel_id) = INMEMORY_DB.get_instance(guild_id) else {
             return;
         };
@@ -205,8 +247,10 @@ impl EventHandler for Bot {
             return;
         }
 
+        let config = guild_config(guild_id);
+
         // If the bot is now alone in the voice channel, leave automatically.
-        if self.auto_leave_when_alone && left_bot_channel {
+        if config.auto_leave_when_alone && left_bot_channel {
             let is_alone = ctx.cache.guild(guild_id).is_some_and(|guild| {
                 guild
                     .voice_states
@@ -222,6 +266,10 @@ impl EventHandler for Bot {
             }
         }
 
+        if !config.announce_join_leave {
+            return;
+        }
+
         // Get user's display name
         let user_name = if let Some(member) = new.member {
             member.display_name().to_string()
@@ -232,51 +280,69 @@ impl EventHandler for Bot {
             }
         };
 
+        let (speaker, locale) = self.speaker(*user, &config).await;
+
         // Create join/leave message
-        let message_text = if joined_bot_channel {
-            format!("{user_name}が参加しました")
-        } else {
-            format!("{user_name}が退出しました")
-        };
+        let message_text = config.catalog.localized(locale).format(
+            if joined_bot_channel {
+                Text::Joined
+            } else {
+                Text::Left
+            },
+            &[("name", &user_name)],
+        );
 
-        // Get user's TTS style (or default if not set)
-        let speaker = PERSISTENT_DB
-            .get_voice_setting(*user)
-            .unwrap_or(DEFAULT_TTS_STYLE.get().unwrap().clone());
+        self.pipeline.submit(
+            &ctx.http,
+            guild_id,
+            SpeechRequest {
+                style: speaker,
+                text: message_text,
+                reply_to: None,
+            },
+        );
+    }
+}
 
-        // Check availability
-        let speaker = if self
-            .tts_services
-            .is_available(&speaker.service_id, &speaker.style_id)
-            .await
-        {
-            speaker
-        } else {
-            DEFAULT_TTS_STYLE.get().unwrap().clone()
-        };
+static LIVE_CONFIG: OnceCell<ArcSwap<model::LiveConfig>> = OnceCell::new();
+static CLI_OPTIONS: OnceCell<model::Cli> = OnceCell::new();
 
-        // Synthesize the announcement
-        let Ok(wav) = self
-            .tts_services
-            .tts(&speaker.service_id, &speaker.style_id, &message_text)
-            .await
-        else {
-            return;
-        };
+fn live_config() -> Arc<model::LiveConfig> {
+    LIVE_CONFIG.get().unwrap().load_full()
+}
 
-        // Enqueue the audio
-        let (source, sample_rate) =
-            wavsource::WavSource::new(&mut Cursor::new(wav), &self.timestretch_config);
-        handler
-            .lock()
-            .await
-            .enqueue_input(songbird::input::RawAdapter::new(source, sample_rate, 1).into())
+/// The config file overridden by the settings of the guild.
+fn guild_config(guild_id: GuildId) -> model::LiveConfig {
+    live_config().with_guild(&PERSISTENT_DB.get_guild_settings(guild_id))
+}
+
+/// Registers every service in the config.
+///
+/// Unreachable backends don't prevent the bot from starting, they are retried in background.
+async fn create_tts_services(
+    tts_config: &model::TtsConfig,
+    tts_cache: Option<TtsCache>,
+) -> TtsServices {
+    let tts_services = TtsServices::new(
+        tts_cache,
+        tts_config.fallbacks.clone(),
+        tts_config
+            .synthesis_timeout
+            .map(std::time::Duration::from_secs),
+        tts_config.health,
+    );
+
+    for (service_id, service) in &tts_config.tts_services {
+        let id = service_id.clone();
+        let config = service.clone();
+
+        tts_services
+            .register_or_retry(service_id, move || config.build(&id))
             .await;
     }
-}
 
-static DEFAULT_TTS_STYLE: OnceCell<model::TtsStyle> = OnceCell::new();
-static CLI_OPTIONS: OnceCell<model::Cli> = OnceCell::new();
+    tts_services
+}
 
 #[allow(clippy::too_many_lines)]
 #[tokio::main]
@@ -290,209 +356,109 @@ async fn main() {
 
     let cli = CLI_OPTIONS.get().unwrap();
 
+    if let Some(command) = &cli.command {
+        if let Err(e) = subcommands::run(cli, command).await {
+            eprintln!("Error: {e:#}");
+            std::process::exit(1);
+        }
+
+        return;
+    }
+
     let tts_config = model::TtsConfig::new(&cli.tts_config_path).unwrap();
 
-    DEFAULT_TTS_STYLE
-        .set(tts_config.default_style.clone())
+    // Create or migrate the database before connecting.
+    once_cell::sync::Lazy::force(&PERSISTENT_DB);
+
+    LIVE_CONFIG
+        .set(ArcSwap::from_pointee(model::LiveConfig::from(&tts_config)))
         .unwrap();
 
-    let tts_services = TtsServices::new();
+    let tts_cache = tts_config.cache.as_ref().map(|config| {
+        let disk_dir = cli.database_path.parent().map(|dir| dir.join("cache"));
+        TtsCache::new(config, disk_dir)
+    });
+
+    let tts_services = create_tts_services(&tts_config, tts_cache).await;
 
-    for (service_id, service) in &tts_config.tts_services {
-        match service {
-            TtsServiceConfig::Voiceroid(config) => {
-                tts_services
-                    .register(
-                        service_id,
-                        Box::new(
-                            Voiceroid::new(config)
-                                .await
-                                .with_context(|| {
-                                    format!("Failed to initialize VOICEROID backend ({service_id})")
-                                })
-                                .unwrap(),
-                        ),
-                    )
-                    .await
-            }
-            TtsServiceConfig::Voicevox(config) => {
-                tts_services
-                    .register(
-                        service_id,
-                        Box::new(
-                            Voicevox::new(config)
-                                .with_context(|| {
-                                    format!("Failed to initialize VOICEVOX backend ({service_id})")
-                                })
-                                .unwrap(),
-                        ),
-                    )
-                    .await
-            }
-            TtsServiceConfig::KTTS(config) => {
-                tts_services
-                    .register(
-                        service_id,
-                        Box::new(
-                            KTTS::new(config)
-                                .with_context(|| {
-                                    format!("Failed to initialize KTTS backend ({service_id})")
-                                })
-                                .unwrap(),
-                        ),
-                    )
-                    .await
-            }
+    let default_style = &tts_config.default_style;
 
-            TtsServiceConfig::MiraeTTS(config) => {
-                tts_services
-                    .register(
-                        service_id,
-                        Box::new(
-                            MiraeTTS::new(config)
-                                .with_context(|| {
-                                    format!("Failed to initialize MiraeTTS backend ({service_id})")
-                                })
-                                .unwrap(),
-                        ),
-                    )
-                    .await
-            }
-            TtsServiceConfig::WinRTTTS(config) => {
-                tts_services
-                    .register(
-                        service_id,
-                        Box::new(
-                            WinRTTTS::new(config)
-                                .await
-                                .with_context(|| {
-                                    format!("Failed to initialize WinRTTTS backend ({service_id})")
-                                })
-                                .unwrap(),
-                        ),
-                    )
-                    .await
-            }
-            TtsServiceConfig::GoogleTranslate(config) => {
-                tts_services
-                    .register(service_id, Box::new(GoogleTranslate::new(config)))
-                    .await
-            }
-            TtsServiceConfig::Naver(config) => {
-                tts_services
-                    .register(service_id, Box::new(Naver::new(config)))
-                    .await
-            }
-            TtsServiceConfig::BingSpeech(config) => {
-                tts_services
-                    .register(service_id, Box::new(BingSpeech::new(config)))
-                    .await
-            }
-            TtsServiceConfig::CoefontTry(config) => {
-                tts_services
-                    .register(service_id, Box::new(CoefontTry::new(config)))
-                    .await
-            }
-            TtsServiceConfig::AndroidTTS(config) => {
-                tts_services
-                    .register(
-                        service_id,
-                        Box::new(
-                            AndroidTTS::new(config)
-                                .with_context(|| {
-                                    format!(
-                                        "Failed to initialize AndroidTTS backend ({service_id})"
-                                    )
-                                })
-                                .unwrap(),
-                        ),
-                    )
-                    .await
-            }
-            TtsServiceConfig::OmniVoice(config) => {
-                tts_services
-                    .register(
-                        service_id,
-                        Box::new(
-                            OmniVoice::new(config)
-                                .await
-                                .with_context(|| {
-                                    format!("Failed to initialize OmniVoice backend ({service_id})")
-                                })
-                                .unwrap(),
-                        ),
-                    )
-                    .await
-            }
-            TtsServiceConfig::CapCutTTSWrapper(config) => tts_services
-                .register(
-                    service_id,
-                    Box::new(
-                        CapCutTTSWrapper::new(config)
-                            .with_context(|| {
-                                format!(
-                                    "Failed to initialize CapCutTTSWrapper backend ({service_id})"
-                                )
-                            })
-                            .unwrap(),
-                    ),
-                )
-                .await,
-            TtsServiceConfig::SayServer(config) => {
-                tts_services
-                    .register(
-                        service_id,
-                        Box::new(
-                            SayServer::new(config)
-                                .await
-                                .with_context(|| {
-                                    format!("Failed to initialize SayServer backend ({service_id})")
-                                })
-                                .unwrap(),
-                        ),
-                    )
-                    .await
-            }
-            TtsServiceConfig::Volcengine(config) => {
-                tts_services
-                    .register(
-                        service_id,
-                        Box::new(
-                            Volcengine::new(config)
-                                .with_context(|| {
-                                    format!(
-                                        "Failed to initialize Volcengine backend ({service_id})"
-                                    )
-                                })
-                                .unwrap(),
-                        ),
-                    )
-                    .await
-            }
-        }
-        .with_context(|| format!("Failed to register service {service_id}"))
-        .unwrap();
+    if !tts_services
+        .is_available(&default_style.service_id, &default_style.style_id)
+        .await
+    {
+        tracing::warn!(
+            "default_style {}/{} is not available yet",
+            default_style.service_id,
+            default_style.style_id,
+        );
     }
 
+    Reloader::new(
+        cli.tts_config_path.clone(),
+        tts_services.clone(),
+        LIVE_CONFIG.get().unwrap(),
+        tts_config.tts_services,
+    )
+    .spawn()
+    .unwrap();
+
     let intents = GatewayIntents::GUILDS
         | GatewayIntents::GUILD_VOICE_STATES
         | GatewayIntents::GUILD_MESSAGES
         | GatewayIntents::MESSAGE_CONTENT;
 
-    let timestretch_config = tts_config.timestretch.unwrap_or_default();
-    let auto_leave_when_alone = tts_config.auto_leave_when_alone;
+    tts_services.spawn_health_checks();
+    tts_services.spawn_catalog_refresh(std::time::Duration::from_secs(
+        tts_config.catalog_refresh_interval,
+    ));
+
+    let songbird = Songbird::serenity();
+
+    let pipeline = Arc::new(Pipeline::new(
+        tts_services.clone(),
+        songbird.clone(),
+        tts_config.synthesis_concurrency,
+    ));
+
+    if let Some(addr) = cli.metrics_addr {
+        metrics::spawn(addr, songbird.clone()).await.unwrap();
+    }
+
+    if let (Some(addr), Some(token)) = (cli.admin_addr, &cli.admin_token) {
+        admin::spawn(addr, token.clone(), tts_services.clone(), songbird.clone())
+            .await
+            .unwrap();
+    }
+
+    // Required by clap unless a subcommand is given.
+    let discord_token = cli.discord_token.as_deref().unwrap();
 
-    let mut client = Client::builder(&cli.discord_token, intents)
+    let mut client = Client::builder(discord_token, intents)
         .event_handler(Bot {
-            tts_services,
+            tts_services: tts_services.clone(),
+            pipeline: pipeline.clone(),
             prefix: cli.command_prefix.clone().unwrap_or_default(),
-            timestretch_config,
-            auto_leave_when_alone,
         })
-        .register_songbird()
+        .register_songbird_with(songbird.clone())
         .await
         .expect("Failed to create client");
 
+    if let (Some(addr), Some(token)) = (cli.ingress_addr, &cli.ingress_token) {
+        ingress::spawn(
+            addr,
+            token.clone(),
+            ingress::Ingress {
+                http: client.http.clone(),
+                tts_services,
+                songbird,
+                pipeline,
+            },
+        )
+        .await
+        .unwrap();
+    }
+
     tokio::spawn(async move {
         let _: Result<_, _> = client
             .start()
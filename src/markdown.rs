@@ -0,0 +1,374 @@
+//! Reads Discord markdown as it is displayed rather than as it is typed.
+
+use once_cell::sync::Lazy;
+use regex::{Captures, Regex};
+use time::{OffsetDateTime, UtcOffset};
+
+use crate::locale::{Localized, Text};
+
+// Fenced code blocks are left to the codeblock rule of the filter.
+static FENCE_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?s)```.*?```").unwrap());
+static INLINE_CODE_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new(r"``(.+?)``|`([^`\n]+)`").unwrap());
+
+static QUOTE_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new(r"^(>>>|>) ").unwrap());
+static HEADING_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new(r"^(#{1,3}|-#) ").unwrap());
+static BULLET_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new(r"^\s*[-*] ").unwrap());
+
+// regex crate's named capture
+#[allow(clippy::invalid_regex)]
+static TIMESTAMP_REGEX: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"<t:(?<time>-?\d+)(?::(?<style>[tTdDfFR]))?>").unwrap());
+static MASKED_LINK_REGEX: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"\[([^\]\n]+)\]\(<?[^\s)>]+>?\)").unwrap());
+// `<https://...>` suppresses the embed of the link.
+static ANGLE_LINK_REGEX: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"<([A-Za-z][A-Za-z0-9+\-.]*://[^\s>]+)>").unwrap());
+static SPOILER_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?s)\|\|(.+?)\|\|").unwrap());
+static BOLD_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?s)\*\*(.+?)\*\*").unwrap());
+static UNDERLINE_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?s)__(.+?)__").unwrap());
+static STRIKETHROUGH_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?s)~~(.+?)~~").unwrap());
+static ITALIC_REGEX: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"\*([^*\s](?:[^*]*[^*\s])?)\*").unwrap());
+// `_` inside a word such as `snake_case` is not italic.
+static UNDERSCORE_ITALIC_REGEX: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"(^|\W)_([^_\s](?:[^_]*[^_\s])?)_(\W|$)").unwrap());
+
+/// Markers escaped by a backslash, and the private use characters standing for them meanwhile.
+const ESCAPES: [(char, char); 6] = [
+    ('*', '\u{E000}'),
+    ('_', '\u{E001}'),
+    ('~', '\u{E002}'),
+    ('|', '\u{E003}'),
+    ('`', '\u{E004}'),
+    ('#', '\u{E005}'),
+];
+
+pub struct MarkdownOptions<'a> {
+    pub texts: Localized<'a>,
+    /// Read in place of spoilers, or `None` to read their content.
+    pub spoiler: Option<&'a str>,
+    /// Offset of the time zone the timestamps are read in.
+    pub utc_offset: UtcOffset,
+    /// Unix time the relative timestamps are relative to.
+    pub now: i64,
+}
+
+pub fn normalize(text: &str, options: &MarkdownOptions) -> String {
+    let text = escape(text);
+    let mut ret = String::new();
+    let mut last = 0;
+
+    for fence in FENCE_REGEX.find_iter(&text) {
+        ret.push_str(&normalize_prose(&text[last..fence.start()], options));
+        ret.push_str(fence.as_str());
+        last = fence.end();
+    }
+
+    ret.push_str(&normalize_prose(&text[last..], options));
+
+    unescape(&ret)
+}
+
+fn escape(text: &str) -> String {
+    let mut ret = String::new();
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        let escaped = (c == '\\')
+            .then(|| chars.peek())
+            .flatten()
+            .and_then(|next| ESCAPES.iter().find(|(marker, _)| marker == next));
+
+        match escaped {
+            Some((_, placeholder)) => {
+                ret.push(*placeholder);
+                chars.next();
+            }
+            None => ret.push(c),
+        }
+    }
+
+    ret
+}
+
+fn unescape(text: &str) -> String {
+    text.chars()
+        .map(|c| {
+            ESCAPES
+                .iter()
+                .find(|(_, placeholder)| *placeholder == c)
+                .map_or(c, |(marker, _)| *marker)
+        })
+        .collect()
+}
+
+/// Text outside of code blocks.
+fn normalize_prose(text: &str, options: &MarkdownOptions) -> String {
+    let text = text
+        .split('\n')
+        .map(strip_line_markers)
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    let mut ret = String::new();
+    let mut last = 0;
+
+    for code in INLINE_CODE_REGEX.captures_iter(&text) {
+        let whole = code.get(0).unwrap();
+        let content = code.get(1).or_else(|| code.get(2)).unwrap();
+
+        ret.push_str(&normalize_inline(&text[last..whole.start()], options));
+        ret.push_str(content.as_str());
+        last = whole.end();
+    }
+
+    ret.push_str(&normalize_inline(&text[last..], options));
+    ret
+}
+
+fn strip_line_markers(line: &str) -> &str {
+    let line = QUOTE_REGEX.find(line).map_or(line, |m| &line[m.end()..]);
+    let line = HEADING_REGEX.find(line).map_or(line, |m| &line[m.end()..]);
+    BULLET_REGEX.find(line).map_or(line, |m| &line[m.end()..])
+}
+
+fn normalize_inline(text: &str, options: &MarkdownOptions) -> String {
+    let s = TIMESTAMP_REGEX.replace_all(text, |cap: &Captures| {
+        cap["time"].parse().ok().map_or_else(
+            || cap[0].to_string(),
+            |time| {
+                let style = cap.name("style").map_or("f", |m| m.as_str());
+                format_timestamp(time, style, options).unwrap_or_else(|| cap[0].to_string())
+            },
+        )
+    });
+    let s = MASKED_LINK_REGEX.replace_all(&s, "$1");
+    let s = ANGLE_LINK_REGEX.replace_all(&s, "$1");
+    let s = match options.spoiler {
+        Some(replacement) => SPOILER_REGEX.replace_all(&s, regex::NoExpand(replacement)),
+        None => SPOILER_REGEX.replace_all(&s, "$1"),
+    };
+    let s = BOLD_REGEX.replace_all(&s, "$1");
+    let s = UNDERLINE_REGEX.replace_all(&s, "$1");
+    let s = STRIKETHROUGH_REGEX.replace_all(&s, "$1");
+    let s = ITALIC_REGEX.replace_all(&s, "$1");
+    let s = UNDERSCORE_ITALIC_REGEX.replace_all(&s, "${1}${2}${3}");
+
+    s.into_owned()
+}
+
+/// Reads `<t:time:style>` as Discord displays it, or `None` if `time` is out of range.
+fn format_timestamp(time: i64, style: &str, options: &MarkdownOptions) -> Option<String> {
+    let texts = &options.texts;
+
+    if style == "R" {
+        return Some(format_relative(time.saturating_sub(options.now), texts));
+    }
+
+    let t = OffsetDateTime::from_unix_timestamp(time)
+        .ok()?
+        .to_offset(options.utc_offset);
+
+    let hour12 = match t.hour() % 12 {
+        0 => 12,
+        h => h,
+    };
+
+    let args = [
+        ("year", t.year().to_string()),
+        ("month", u8::from(t.month()).to_string()),
+        ("month_name", texts.locale.month_name(t.month())),
+        ("day", t.day().to_string()),
+        (
+            "weekday",
+            texts.locale.weekday_name(t.weekday()).to_string(),
+        ),
+        ("hour", t.hour().to_string()),
+        ("hour12", hour12.to_string()),
+        ("minute", t.minute().to_string()),
+        ("minute2", format!("{:02}", t.minute())),
+        ("second", t.second().to_string()),
+        ("second2", format!("{:02}", t.second())),
+        (
+            "am_pm",
+            texts
+                .get(if t.hour() < 12 { Text::Am } else { Text::Pm })
+                .to_string(),
+        ),
+    ];
+    let args: Vec<_> = args.iter().map(|(k, v)| (*k, v.as_str())).collect();
+
+    let date = texts.format(Text::Date, &args);
+    let short_time = texts.format(Text::Time, &args);
+
+    let s = match style {
+        "d" | "D" => date,
+        "t" => short_time,
+        "T" => texts.format(Text::LongTime, &args),
+        "F" => texts.format(
+            Text::LongDateTime,
+            &[
+                ("date", &date),
+                ("time", &short_time),
+                ("weekday", texts.locale.weekday_name(t.weekday())),
+            ],
+        ),
+        _ => texts.format(Text::DateTime, &[("date", &date), ("time", &short_time)]),
+    };
+
+    Some(s)
+}
+
+/// Reads `seconds` from now in the largest unit, as Discord does.
+fn format_relative(seconds: i64, texts: &Localized) -> String {
+    const MINUTE: u64 = 60;
+    const HOUR: u64 = 60 * MINUTE;
+    const DAY: u64 = 24 * HOUR;
+
+    let abs = seconds.unsigned_abs();
+
+    let (n, one, many) = match abs {
+        _ if abs < MINUTE => return texts.get(Text::Now).to_string(),
+        _ if abs < HOUR => (abs / MINUTE, Text::Minute, Text::Minutes),
+        _ if abs < DAY => (abs / HOUR, Text::Hour, Text::Hours),
+        _ if abs < 30 * DAY => (abs / DAY, Text::Day, Text::Days),
+        _ if abs < 365 * DAY => (abs / (30 * DAY), Text::Month, Text::Months),
+        _ => (abs / (365 * DAY), Text::Year, Text::Years),
+    };
+
+    let amount = if n == 1 {
+        texts.get(one).to_string()
+    } else {
+        texts.format(many, &[("n", &n.to_string())])
+    };
+
+    let text = if seconds < 0 {
+        Text::RelativePast
+    } else {
+        Text::RelativeFuture
+    };
+
+    texts.format(text, &[("amount", &amount)])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::locale::{Catalog, Locale};
+
+    fn options<'a>(
+        catalog: &'a Catalog,
+        locale: Locale,
+        spoiler: Option<&'a str>,
+    ) -> MarkdownOptions<'a> {
+        MarkdownOptions {
+            texts: catalog.localized(locale),
+            spoiler,
+            utc_offset: UtcOffset::from_hms(9, 0, 0).unwrap(),
+            // 2023-11-15 07:13:20 +09:00
+            now: 1_700_000_000,
+        }
+    }
+
+    #[test]
+    fn test_formatting() {
+        let catalog = Catalog::default();
+        let options = options(&catalog, Locale::Ja, None);
+
+        assert_eq!(
+            normalize("**bold** and *italic*", &options),
+            "bold and italic"
+        );
+        assert_eq!(normalize("__under__ ~~strike~~", &options), "under strike");
+        assert_eq!(
+            normalize("_italic_ snake_case_name", &options),
+            "italic snake_case_name"
+        );
+        assert_eq!(normalize("2 * 3 * 4", &options), "2 * 3 * 4");
+        assert_eq!(normalize(r"\*not italic\*", &options), "*not italic*");
+        assert_eq!(normalize("||secret||", &options), "secret");
+        assert_eq!(normalize("see `a *b* c`", &options), "see a *b* c");
+        assert_eq!(
+            normalize("```\n**code**\n```", &options),
+            "```\n**code**\n```"
+        );
+    }
+
+    #[test]
+    fn test_lines() {
+        let catalog = Catalog::default();
+        let options = options(&catalog, Locale::Ja, None);
+
+        assert_eq!(
+            normalize("# Title\n> quote\n- item\n* item\n-# small", &options),
+            "Title\nquote\nitem\nitem\nsmall"
+        );
+        assert_eq!(normalize(">>> # quoted title", &options), "quoted title");
+        assert_eq!(normalize("#hashtag", &options), "#hashtag");
+    }
+
+    #[test]
+    fn test_links_and_spoilers() {
+        let catalog = Catalog::default();
+
+        let options = options(&catalog, Locale::Ja, Some("。伏せ字。"));
+        assert_eq!(
+            normalize("[docs](https://example.com) ||spoiler||", &options),
+            "docs 。伏せ字。"
+        );
+        assert_eq!(normalize("[docs](<https://example.com>)", &options), "docs");
+        assert_eq!(
+            normalize("<https://example.com>", &options),
+            "https://example.com"
+        );
+    }
+
+    #[test]
+    fn test_timestamps() {
+        let catalog = Catalog::default();
+
+        let ja = options(&catalog, Locale::Ja, None);
+        assert_eq!(normalize("<t:1700000000:D>", &ja), "2023年11月15日");
+        assert_eq!(normalize("<t:1700000000:t>", &ja), "7時13分");
+        assert_eq!(
+            normalize("<t:1700000000:F>", &ja),
+            "2023年11月15日水曜日 7時13分"
+        );
+        assert_eq!(normalize("<t:1700000000>", &ja), "2023年11月15日 7時13分");
+        assert_eq!(normalize("<t:1699996400:R>", &ja), "1時間前");
+        assert_eq!(normalize("<t:1700259200:R>", &ja), "3日後");
+        assert_eq!(normalize("<t:1700000030:R>", &ja), "たった今");
+
+        let en = options(&catalog, Locale::En, None);
+        assert_eq!(
+            normalize("<t:1700000000:f>", &en),
+            "November 15, 2023 at 7:13 AM"
+        );
+        assert_eq!(normalize("<t:1700000000:T>", &en), "7:13:20 AM");
+        assert_eq!(normalize("<t:1699996400:R>", &en), "an hour ago");
+        assert_eq!(normalize("<t:1700259200:R>", &en), "in 3 days");
+
+        let ko = options(&catalog, Locale::Ko, None);
+        assert_eq!(
+            normalize("<t:1700000000:f>", &ko),
+            "2023년 11월 15일 오전 7시 13분"
+        );
+
+        assert_eq!(
+            normalize("<t:99999999999999999:f>", &ja),
+            "<t:99999999999999999:f>"
+        );
+        assert_eq!(
+            normalize(&format!("<t:{}:R>", i64::MIN), &ja),
+            "292471208677年前"
+        );
+        assert_eq!(
+            normalize(&format!("<t:{}:R>", i64::MAX), &ja),
+            "292471208623年後"
+        );
+        assert_eq!(
+            normalize(&format!("<t:{}:f>", i64::MIN), &ja),
+            format!("<t:{}:f>", i64::MIN)
+        );
+    }
+}
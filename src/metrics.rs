@@ -0,0 +1,183 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+use axum::{Router, extract::State, http::header, response::IntoResponse, routing::get};
+use once_cell::sync::Lazy;
+use prometheus::{
+    HistogramOpts, HistogramVec, IntCounterVec, IntGauge, IntGaugeVec, Opts, Registry, TextEncoder,
+};
+use songbird::Songbird;
+
+pub static METRICS: Lazy<Metrics> = Lazy::new(Metrics::new);
+
+pub struct Metrics {
+    registry: Registry,
+    /// Seconds taken by each backend, labeled by `service`.
+    pub synthesis_duration: HistogramVec,
+    /// Failed synthesis attempts, labeled by `service`.
+    pub synthesis_errors: IntCounterVec,
+    /// Messages not read aloud, labeled by the `rule` which dropped them.
+    pub filtered_messages: IntCounterVec,
+    /// Cache lookups, labeled by `result` (`hit` or `miss`).
+    pub cache_requests: IntCounterVec,
+    /// Messages submitted but not yet enqueued for playback, labeled by `guild`.
+    pub pending_requests: IntGaugeVec,
+    /// Tracks in the playback queue of songbird, labeled by `guild`.
+    queue_depth: IntGaugeVec,
+    voice_connections: IntGauge,
+}
+
+impl Metrics {
+    fn new() -> Self {
+        let registry = Registry::new_custom(Some("discordtts".to_string()), None)
+            .expect("Invalid metrics prefix");
+
+        let synthesis_duration = HistogramVec::new(
+            HistogramOpts::new(
+                "synthesis_duration_seconds",
+                "Time taken to synthesize a chunk",
+            )
+            .buckets(vec![0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0]),
+            &["service"],
+        )
+        .unwrap();
+
+        let synthesis_errors = IntCounterVec::new(
+            Opts::new("synthesis_errors_total", "Failed synthesis attempts"),
+            &["service"],
+        )
+        .unwrap();
+
+        let filtered_messages = IntCounterVec::new(
+            Opts::new("filtered_messages_total", "Messages not read aloud"),
+            &["rule"],
+        )
+        .unwrap();
+
+        let cache_requests = IntCounterVec::new(
+            Opts::new("cache_requests_total", "Lookups of the synthesis cache"),
+            &["result"],
+        )
+        .unwrap();
+
+        let pending_requests = IntGaugeVec::new(
+            Opts::new(
+                "pending_requests",
+                "Messages waiting to be synthesized and enqueued",
+            ),
+            &["guild"],
+        )
+        .unwrap();
+
+        let queue_depth = IntGaugeVec::new(
+            Opts::new("queue_depth", "Tracks waiting to be played"),
+            &["guild"],
+        )
+        .unwrap();
+
+        let voice_connections =
+            IntGauge::new("voice_connections", "Active voice connections").unwrap();
+
+        for collector in [
+            Box::new(synthesis_duration.clone()) as Box<dyn prometheus::core::Collector>,
+            Box::new(synthesis_errors.clone()),
+            Box::new(filtered_messages.clone()),
+            Box::new(cache_requests.clone()),
+            Box::new(pending_requests.clone()),
+            Box::new(queue_depth.clone()),
+            Box::new(voice_connections.clone()),
+        ] {
+            registry.register(collector).unwrap();
+        }
+
+        Self {
+            registry,
+            synthesis_duration,
+            synthesis_errors,
+            filtered_messages,
+            cache_requests,
+            pending_requests,
+            queue_depth,
+            voice_connections,
+        }
+    }
+
+    /// Samples the voice connections, which are owned by songbird.
+    async fn collect_voice(&self, songbird: &Songbird) {
+        self.queue_depth.reset();
+
+        let calls: Vec<_> = songbird.iter().collect();
+        let mut connections = 0;
+
+        for (guild_id, call) in calls {
+            let call = call.lock().await;
+
+            if call.current_connection().is_some() {
+                connections += 1;
+            }
+
+            self.queue_depth
+                .with_label_values(&[&guild_id.0.to_string()])
+                .set(i64::try_from(call.queue().len()).unwrap_or(i64::MAX));
+        }
+
+        self.voice_connections.set(connections);
+    }
+
+    fn encode(&self) -> String {
+        TextEncoder::new()
+            .encode_to_string(&self.registry.gather())
+            .expect("Failed to encode metrics")
+    }
+}
+
+async fn handler(State(songbird): State<Arc<Songbird>>) -> impl IntoResponse {
+    METRICS.collect_voice(&songbird).await;
+
+    (
+        [(header::CONTENT_TYPE, prometheus::TEXT_FORMAT)],
+        METRICS.encode(),
+    )
+}
+
+/// Serves `/metrics` on `addr`.
+pub async fn spawn(addr: SocketAddr, songbird: Arc<Songbird>) -> Result<()> {
+    let app = Router::new()
+        .route("/metrics", get(handler))
+        .with_state(songbird);
+
+    let listener = tokio::net::TcpListener::bind(addr)
+        .await
+        .with_context(|| format!("Failed to listen on {addr}"))?;
+
+    tracing::info!("Serving metrics on {addr}");
+
+    tokio::spawn(async move {
+        if let Err(e) = axum::serve(listener, app).await {
+            tracing::warn!("Metrics server stopped: {e}");
+        }
+    });
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode() {
+        let metrics = Metrics::new();
+
+        metrics
+            .synthesis_errors
+            .with_label_values(&["VOICEVOX"])
+            .inc();
+        metrics.cache_requests.with_label_values(&["hit"]).inc();
+
+        let text = metrics.encode();
+        assert!(text.contains(r#"discordtts_synthesis_errors_total{service="VOICEVOX"} 1"#));
+        assert!(text.contains(r#"discordtts_cache_requests_total{result="hit"} 1"#));
+    }
+}
@@ -1,31 +1,154 @@
 use std::collections::HashMap;
+use std::num::NonZeroU64;
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
 // use once_cell::sync::Lazy;
 use anyhow::{Context, Result};
-use clap::Parser;
+use clap::{Args, Parser, Subcommand, ValueEnum};
 use serde::{Deserialize, Serialize};
 
+use crate::android_tts::AndroidTTS;
+use crate::bing_speech::BingSpeech;
+use crate::capcutttswrapper::CapCutTTSWrapper;
+use crate::coefont_try::CoefontTry;
+use crate::google_translate::GoogleTranslate;
+use crate::ktts::KTTS;
+use crate::locale::{Catalog, Locale, Localized};
+use crate::mirae_tts::MiraeTTS;
+use crate::naver::Naver;
+use crate::omnivoice::OmniVoice;
+use crate::sayserver::SayServer;
+use crate::tts::TtsService;
+use crate::voiceroid::Voiceroid;
+use crate::voicevox::Voicevox;
+use crate::volcengine::Volcengine;
+use crate::winrttts::WinRTTTS;
+
 // pub static CONFIG: Lazy<Config> =
 //     Lazy::new(|| envy::from_env().expect("Failed to load Environment variable"));
 
+/// Runs the bot unless a subcommand is given.
 #[derive(Parser, Debug)]
+#[command(subcommand_negates_reqs = true)]
 pub struct Cli {
-    #[clap(env, long, default_value = "/etc/discord-tts.tts.toml")]
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    #[clap(env, long, global = true, default_value = "/etc/discord-tts.tts.toml")]
     pub tts_config_path: PathBuf,
 
     #[clap(env, long)]
     pub command_prefix: Option<String>,
 
-    #[clap(env, long)]
-    pub discord_token: String,
+    #[clap(env, long, required = true)]
+    pub discord_token: Option<String>,
 
+    #[clap(env, long, default_value = "/var/discordtts/state.sqlite3")]
+    pub database_path: PathBuf,
+
+    /// The JSON state of older versions, imported when the database is created.
     #[clap(env, long, default_value = "/var/discordtts/state.json")]
     pub persistent_path: PathBuf,
+
+    /// Serve Prometheus metrics on `/metrics` at this address, e.g. `0.0.0.0:9100`.
+    #[clap(env, long)]
+    pub metrics_addr: Option<std::net::SocketAddr>,
+
+    /// Serve the admin API at this address. Bind it to a local address such as `127.0.0.1:8080`.
+    #[clap(env, long, requires = "admin_token")]
+    pub admin_addr: Option<std::net::SocketAddr>,
+
+    /// Bearer token required by the admin API.
+    #[clap(env, long, hide_env_values = true)]
+    pub admin_token: Option<String>,
+
+    /// Accept text to read aloud on `/speak` at this address.
+    #[clap(env, long, requires = "ingress_token")]
+    pub ingress_addr: Option<std::net::SocketAddr>,
+
+    /// Bearer token required by `/speak`.
+    #[clap(env, long, hide_env_values = true)]
+    pub ingress_token: Option<String>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Synthesize text into a WAV file without connecting to Discord
+    Synth(SynthArgs),
+    /// Validate the TTS config against the backends
+    Check(CheckArgs),
+    /// Print the text after every stage of the message filter
+    Filter(FilterArgs),
+}
+
+#[derive(Args, Debug)]
+pub struct FilterArgs {
+    /// Use the dictionary and the settings of the guild in the database
+    #[clap(long)]
+    pub guild: Option<NonZeroU64>,
+
+    /// Number of attached images
+    #[clap(long, default_value_t = 0)]
+    pub images: usize,
+
+    /// Number of attached files other than images
+    #[clap(long, default_value_t = 0)]
+    pub files: usize,
+
+    /// Treat the message as forwarded
+    #[clap(long)]
+    pub forwarded: bool,
+
+    /// Language of the notifications (the one of the config or the guild if omitted)
+    #[clap(long, value_enum)]
+    pub locale: Option<Locale>,
+
+    /// Content of the message
+    pub text: String,
+}
+
+#[derive(Args, Debug)]
+pub struct CheckArgs {
+    /// Print the styles of every service
+    #[clap(long, value_enum)]
+    pub catalog: Option<CatalogFormat>,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum CatalogFormat {
+    Json,
+    Table,
+}
+
+#[derive(Args, Debug)]
+pub struct SynthArgs {
+    /// Service ID in `tts_services`
+    #[clap(long)]
+    pub service: String,
+
+    /// Style ID of the service
+    #[clap(long)]
+    pub style: String,
+
+    /// Path of the WAV file to write
+    #[clap(short, long, default_value = "synth.wav")]
+    pub output: PathBuf,
+
+    /// Process the text as a message, e.g. replacing URLs and emojis
+    #[clap(long)]
+    pub filter: bool,
+
+    /// Speed up long text as in a voice channel
+    #[clap(long)]
+    pub timestretch: bool,
+
+    /// Text to read aloud
+    pub text: String,
 }
 
 #[allow(clippy::upper_case_acronyms)]
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, Clone, PartialEq)]
 pub enum TtsServiceConfig {
     Voiceroid(crate::voiceroid::Setting),
     Voicevox(crate::voicevox::Setting),
@@ -43,6 +166,50 @@ pub enum TtsServiceConfig {
     Volcengine(crate::volcengine::Setting),
 }
 
+impl TtsServiceConfig {
+    pub fn build(&self, service_id: &str) -> Result<Box<dyn TtsService>> {
+        let (name, service): (_, Result<Box<dyn TtsService>>) = match self {
+            Self::Voiceroid(config) => (
+                "VOICEROID",
+                Voiceroid::new(config).map(|s| Box::new(s) as _),
+            ),
+            Self::Voicevox(config) => ("VOICEVOX", Voicevox::new(config).map(|s| Box::new(s) as _)),
+            Self::KTTS(config) => ("KTTS", KTTS::new(config).map(|s| Box::new(s) as _)),
+            Self::MiraeTTS(config) => ("MiraeTTS", MiraeTTS::new(config).map(|s| Box::new(s) as _)),
+            Self::WinRTTTS(config) => ("WinRTTTS", WinRTTTS::new(config).map(|s| Box::new(s) as _)),
+            Self::GoogleTranslate(config) => (
+                "GoogleTranslate",
+                Ok(Box::new(GoogleTranslate::new(config))),
+            ),
+            Self::Naver(config) => ("Naver", Ok(Box::new(Naver::new(config)))),
+            Self::BingSpeech(config) => ("BingSpeech", Ok(Box::new(BingSpeech::new(config)))),
+            Self::CoefontTry(config) => ("CoefontTry", Ok(Box::new(CoefontTry::new(config)))),
+            Self::CapCutTTSWrapper(config) => (
+                "CapCutTTSWrapper",
+                CapCutTTSWrapper::new(config).map(|s| Box::new(s) as _),
+            ),
+            Self::AndroidTTS(config) => (
+                "AndroidTTS",
+                AndroidTTS::new(config).map(|s| Box::new(s) as _),
+            ),
+            Self::OmniVoice(config) => (
+                "OmniVoice",
+                OmniVoice::new(config).map(|s| Box::new(s) as _),
+            ),
+            Self::SayServer(config) => (
+                "SayServer",
+                SayServer::new(config).map(|s| Box::new(s) as _),
+            ),
+            Self::Volcengine(config) => (
+                "Volcengine",
+                Volcengine::new(config).map(|s| Box::new(s) as _),
+            ),
+        };
+
+        service.with_context(|| format!("Failed to initialize {name} backend ({service_id})"))
+    }
+}
+
 #[derive(Deserialize, Debug)]
 pub struct TtsConfig {
     pub default_style: TtsStyle,
@@ -51,15 +218,73 @@ pub struct TtsConfig {
     pub timestretch: Option<TimeStretchConfig>,
     #[serde(default)]
     pub auto_leave_when_alone: bool,
+    /// Messages starting with this are not read aloud, unless it is doubled. Empty disables it.
+    #[serde(default = "default_suppress_prefix")]
+    pub suppress_prefix: String,
+    /// Read aloud when a user joins or leaves the voice channel.
+    #[serde(default = "default_announce_join_leave")]
+    pub announce_join_leave: bool,
+    #[serde(default)]
+    pub cache: Option<crate::cache::CacheConfig>,
+    #[serde(default)]
+    pub fallbacks: Vec<FallbackConfig>,
+    /// Timeout of a single synthesis attempt in seconds.
+    #[serde(default)]
+    pub synthesis_timeout: Option<u64>,
+    /// Number of messages synthesized ahead in each guild.
+    #[serde(default = "default_synthesis_concurrency")]
+    pub synthesis_concurrency: usize,
+    #[serde(default)]
+    pub health: crate::health::HealthConfig,
+    /// Interval of re-querying the style catalogs in seconds. `0` disables it.
+    #[serde(default = "default_catalog_refresh_interval")]
+    pub catalog_refresh_interval: u64,
+    #[serde(default)]
+    pub filter: crate::filter::FilterConfig,
+    /// Language of the bot, unless a user speaks in another one.
+    #[serde(default)]
+    pub locale: Locale,
+    #[serde(default)]
+    pub texts: Catalog,
+}
+
+fn default_suppress_prefix() -> String {
+    ";".to_string()
+}
+
+fn default_announce_join_leave() -> bool {
+    true
+}
+
+fn default_synthesis_concurrency() -> usize {
+    2
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+fn default_catalog_refresh_interval() -> u64 {
+    60 * 60
+}
+
+/// Styles to try in order when synthesis with `service_id` (and `style_id` if given) fails.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct FallbackConfig {
+    pub service_id: String,
+    #[serde(default)]
+    pub style_id: Option<String>,
+    pub to: Vec<TtsStyle>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
 pub struct TimeStretchConfig {
     pub target_speed: f64,
     pub ramp_duration: f64,
     pub initial_delay: f64,
 }
 
+impl TimeStretchConfig {
+    /// The resampler allocates buffers in proportion to the speed.
+    pub const SPEED_RANGE: std::ops::RangeInclusive<f64> = 1.0..=4.0;
+}
+
 impl Default for TimeStretchConfig {
     fn default() -> Self {
         Self {
@@ -77,8 +302,165 @@ impl TtsConfig {
     }
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+/// The part of [`TtsConfig`] which is swapped as a whole when the file is reloaded.
+#[derive(Debug, Clone)]
+pub struct LiveConfig {
+    pub default_style: TtsStyle,
+    pub timestretch: TimeStretchConfig,
+    pub auto_leave_when_alone: bool,
+    pub suppress_prefix: String,
+    pub announce_join_leave: bool,
+    pub filter: Arc<crate::filter::FilterConfig>,
+    pub locale: Locale,
+    pub catalog: Arc<Catalog>,
+}
+
+impl From<&TtsConfig> for LiveConfig {
+    fn from(config: &TtsConfig) -> Self {
+        Self {
+            default_style: config.default_style.clone(),
+            timestretch: config.timestretch.unwrap_or_default(),
+            auto_leave_when_alone: config.auto_leave_when_alone,
+            suppress_prefix: config.suppress_prefix.clone(),
+            announce_join_leave: config.announce_join_leave,
+            filter: Arc::new(config.filter.clone()),
+            locale: config.locale,
+            catalog: Arc::new(config.texts.clone()),
+        }
+    }
+}
+
+impl LiveConfig {
+    /// The config of a guild, where its settings take precedence.
+    pub fn with_guild(&self, settings: &GuildSettings) -> Self {
+        Self {
+            default_style: settings
+                .default_style
+                .clone()
+                .unwrap_or_else(|| self.default_style.clone()),
+            timestretch: settings.timestretch.apply(self.timestretch),
+            auto_leave_when_alone: settings
+                .auto_leave_when_alone
+                .unwrap_or(self.auto_leave_when_alone),
+            suppress_prefix: settings
+                .suppress_prefix
+                .clone()
+                .unwrap_or_else(|| self.suppress_prefix.clone()),
+            announce_join_leave: settings
+                .announce_join_leave
+                .unwrap_or(self.announce_join_leave),
+            filter: self.filter.clone(),
+            locale: settings.locale.unwrap_or(self.locale),
+            catalog: self.catalog.clone(),
+        }
+    }
+
+    /// Texts in the language of the guild.
+    pub fn texts(&self) -> Localized<'_> {
+        self.catalog.localized(self.locale)
+    }
+}
+
+/// Per-guild overrides of [`LiveConfig`] set by `/config`. `None` follows the config file.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct GuildSettings {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_style: Option<TtsStyle>,
+    #[serde(default, skip_serializing_if = "TimeStretchSettings::is_default")]
+    pub timestretch: TimeStretchSettings,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub auto_leave_when_alone: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub suppress_prefix: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub announce_join_leave: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub locale: Option<Locale>,
+}
+
+impl GuildSettings {
+    pub fn is_default(&self) -> bool {
+        self == &Self::default()
+    }
+}
+
+/// Per-guild overrides of [`TimeStretchConfig`], so that the values which are not overridden
+/// follow the config file.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+pub struct TimeStretchSettings {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target_speed: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ramp_duration: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub initial_delay: Option<f64>,
+}
+
+impl TimeStretchSettings {
+    pub fn is_default(&self) -> bool {
+        self == &Self::default()
+    }
+
+    pub fn apply(&self, config: TimeStretchConfig) -> TimeStretchConfig {
+        TimeStretchConfig {
+            target_speed: self.target_speed.unwrap_or(config.target_speed),
+            ramp_duration: self.ramp_duration.unwrap_or(config.ramp_duration),
+            initial_delay: self.initial_delay.unwrap_or(config.initial_delay),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct TtsStyle {
     pub service_id: String,
     pub style_id: String,
+    #[serde(default, skip_serializing_if = "TtsParams::is_default")]
+    pub params: TtsParams,
+}
+
+/// Per-user voice parameters. Every value is a ratio where `1.0` is the backend default.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+pub struct TtsParams {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub speed: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pitch: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub intonation: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub volume: Option<f32>,
+}
+
+impl TtsParams {
+    pub const SPEED_RANGE: std::ops::RangeInclusive<f32> = 0.5..=2.0;
+    pub const PITCH_RANGE: std::ops::RangeInclusive<f32> = 0.5..=2.0;
+    pub const INTONATION_RANGE: std::ops::RangeInclusive<f32> = 0.0..=2.0;
+    pub const VOLUME_RANGE: std::ops::RangeInclusive<f32> = 0.0..=2.0;
+
+    pub fn is_default(&self) -> bool {
+        self == &Self::default()
+    }
+
+    /// Checks that every value is within its range, so that it can be passed to the backends.
+    pub fn validate(&self) -> Result<(), String> {
+        let params = [
+            ("Speed", self.speed, Self::SPEED_RANGE),
+            ("Pitch", self.pitch, Self::PITCH_RANGE),
+            ("Intonation", self.intonation, Self::INTONATION_RANGE),
+            ("Volume", self.volume, Self::VOLUME_RANGE),
+        ];
+
+        for (name, value, range) in params {
+            // NaN is not contained in any range.
+            if value.is_some_and(|v| !range.contains(&v)) {
+                return Err(format!(
+                    "{name} must be between {} and {}",
+                    range.start(),
+                    range.end()
+                ));
+            }
+        }
+
+        Ok(())
+    }
 }
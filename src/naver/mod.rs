@@ -5,11 +5,13 @@ use async_trait::async_trait;
 
 use serde::Deserialize;
 
-use crate::tts::{CharacterView, StyleView, TtsService};
+use crate::audio::Audio;
+use crate::model::TtsParams;
+use crate::tts::{CharacterView, Gender, StyleView, TtsService};
 
 mod naver_tts;
 use naver_tts::VOICES;
-use naver_tts::get_audio_bytes;
+use naver_tts::get_audio;
 
 fn default_master_volume() -> f32 {
     1.0
@@ -19,7 +21,7 @@ fn default_speed() -> i32 {
     0
 }
 
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Deserialize, Debug, Clone, PartialEq)]
 pub struct Setting {
     #[serde(default = "default_master_volume")]
     pub master_volume: f32,
@@ -54,13 +56,13 @@ impl Naver {
 
 #[async_trait]
 impl TtsService for Naver {
-    async fn tts(&self, style_id: &str, text: &str) -> Result<Vec<u8>> {
+    async fn tts(&self, style_id: &str, text: &str, _params: &TtsParams) -> Result<Audio> {
         let voice = VOICES
             .iter()
             .find(|v| v.speaker == style_id)
             .ok_or_else(|| anyhow::anyhow!("Unsupported style: {style_id}"))?;
 
-        let bytes = get_audio_bytes(
+        let audio = get_audio(
             text,
             voice.lang,
             voice.speaker,
@@ -69,7 +71,7 @@ impl TtsService for Naver {
         )
         .await?;
 
-        Ok(bytes)
+        Ok(audio)
     }
 
     async fn styles(&self) -> Result<Vec<CharacterView>> {
@@ -91,6 +93,8 @@ impl TtsService for Naver {
                     name: voice.name.to_string(),
                     id: voice.speaker.to_string(),
                     icon: vec![],
+                    language: Some(voice.lang.to_string()),
+                    gender: Gender::parse(voice.gender),
                 });
             }
 
@@ -99,6 +103,8 @@ impl TtsService for Naver {
                     name: lang_name.to_string(),
                     policy: "Naver Terms of Service".to_string(),
                     styles,
+                    language: Some(lang_id.to_string()),
+                    gender: None,
                 });
             }
         }
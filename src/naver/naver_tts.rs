@@ -2,6 +2,8 @@ use anyhow::Result;
 use reqwest::Url;
 use tracing::subscriber::NoSubscriber;
 
+use crate::audio::Audio;
+
 #[derive(Debug, Clone)]
 pub struct NaverVoice {
     pub name: &'static str,
@@ -74,30 +76,15 @@ pub const VOICES: &[NaverVoice] = &[
     },
 ];
 
-fn create_empty_wav() -> Result<Vec<u8>> {
-    use std::io::Cursor;
-
-    let spec = hound::WavSpec {
-        channels: 1,
-        sample_rate: 24000,
-        bits_per_sample: 16,
-        sample_format: hound::SampleFormat::Int,
-    };
-    let mut wav_cursor = Cursor::new(Vec::new());
-    let wav_writer = hound::WavWriter::new(&mut wav_cursor, spec)?;
-    wav_writer.finalize()?;
-    Ok(wav_cursor.into_inner())
-}
-
 const NAVER_TTS_MAX_CHARS: usize = 500;
 
-pub async fn get_audio_bytes(
+pub async fn get_audio(
     text: &str,
     _lang: &str,
     speaker: &str,
     speed: i32,
     volume: f32,
-) -> Result<Vec<u8>> {
+) -> Result<Audio> {
     use reqwest::header::{HeaderMap, HeaderValue};
 
     let parts = crate::tts::split_long_text(text, NAVER_TTS_MAX_CHARS);
@@ -138,10 +125,10 @@ pub async fn get_audio_bytes(
     }
 
     if combined_audio.is_empty() {
-        return create_empty_wav();
+        return Ok(Audio::silence());
     }
 
     tracing::subscriber::with_default(NoSubscriber::new(), || {
-        crate::tts::convert_mp3_to_wav(combined_audio, volume)
+        Ok(Audio::from_mp3(combined_audio)?.gain(volume))
     })
 }
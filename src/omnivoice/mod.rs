@@ -1,15 +1,17 @@
 use std::collections::HashMap;
-use std::io::Cursor;
 use std::sync::Arc;
 
 use anyhow::{Context, Result};
 use async_trait::async_trait;
-use hound::{WavReader, WavWriter};
 use reqwest::header::{HeaderMap, HeaderName};
 use serde::Deserialize;
 use tap::Tap;
 
-use crate::tts::{CharacterView, StyleView, TtsService, split_long_text};
+use crate::audio::Audio;
+use crate::model::TtsParams;
+use crate::tts::{
+    Capabilities, CharacterView, NativeParams, StyleView, TtsService, probe, split_long_text,
+};
 
 mod api;
 
@@ -33,7 +35,7 @@ fn default_max_chars() -> usize {
     500
 }
 
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Deserialize, Debug, Clone, PartialEq)]
 pub struct Setting {
     pub url: reqwest::Url,
     #[serde(default = "default_headers")]
@@ -53,7 +55,6 @@ struct OmniVoiceInner {
     client: reqwest::Client,
     url: reqwest::Url,
     master_volume: f32,
-    voices: Vec<api::Voice>,
     voice_volumes: HashMap<String, f32>,
     voice_speeds: HashMap<String, f32>,
     max_chars: usize,
@@ -66,11 +67,7 @@ pub struct OmniVoice {
 }
 
 impl OmniVoice {
-    pub async fn new(setting: &Setting) -> Result<Self> {
-        let api_voices = setting.url.clone().tap_mut(|u| {
-            u.path_segments_mut().unwrap().push("v1").push("voices");
-        });
-
+    pub fn new(setting: &Setting) -> Result<Self> {
         let mut headers = HeaderMap::new();
 
         for (key, value) in &setting.headers {
@@ -86,21 +83,10 @@ impl OmniVoice {
             .build()
             .unwrap();
 
-        let voices = client
-            .get(api_voices)
-            .send()
-            .await
-            .context("Failed to get /v1/voices")?
-            .error_for_status()
-            .context("Failed to get /v1/voices")?;
-
-        let voices: api::Voices = voices.json().await.context("Failed to parse /v1/voices")?;
-
         Ok(OmniVoice {
             inner: Arc::new(OmniVoiceInner {
                 url: setting.url.clone(),
                 master_volume: setting.master_volume,
-                voices: voices.voices.clone(),
                 client,
                 max_chars: setting.max_chars,
                 voice_volumes: setting.voice_volumes.clone(),
@@ -112,14 +98,16 @@ impl OmniVoice {
 
 #[async_trait]
 impl TtsService for OmniVoice {
-    async fn tts(&self, style_id: &str, text: &str) -> Result<Vec<u8>> {
+    async fn tts(&self, style_id: &str, text: &str, params: &TtsParams) -> Result<Audio> {
         let api_tts = self.inner.url.clone().tap_mut(|u| {
             u.path_segments_mut().unwrap().push("v1").push("tts");
         });
 
-        let volume =
-            *self.inner.voice_volumes.get(style_id).unwrap_or(&1.0) * self.inner.master_volume;
-        let speed = *self.inner.voice_speeds.get(style_id).unwrap_or(&1.0);
+        let volume = *self.inner.voice_volumes.get(style_id).unwrap_or(&1.0)
+            * self.inner.master_volume
+            * params.volume.unwrap_or(1.0);
+        let speed =
+            *self.inner.voice_speeds.get(style_id).unwrap_or(&1.0) * params.speed.unwrap_or(1.0);
 
         let parts = if self.inner.max_chars == 0 {
             vec![text.to_string()]
@@ -127,8 +115,7 @@ impl TtsService for OmniVoice {
             split_long_text(text, self.inner.max_chars)
         };
 
-        let mut all_samples: Vec<i16> = Vec::new();
-        let mut sample_rate = None;
+        let mut parts_audio = vec![];
 
         for part in parts {
             let query = api::TtsRequest {
@@ -153,43 +140,56 @@ impl TtsService for OmniVoice {
                 .await
                 .context("Failed to post /v1/tts (body)")?;
 
-            let mut reader = WavReader::new(Cursor::new(wav_data))
-                .with_context(|| "Failed to read as wav file")?;
-            let spec = reader.spec();
-            if sample_rate.is_none() {
-                sample_rate = Some(spec.sample_rate);
-            }
-
-            for sample in reader.samples::<i16>() {
-                all_samples.push(sample?);
-            }
+            parts_audio.push(Audio::from_wav(&wav_data)?);
         }
 
-        let spec = hound::WavSpec {
-            channels: 1,
-            sample_rate: sample_rate.unwrap_or(24000),
-            bits_per_sample: 16,
-            sample_format: hound::SampleFormat::Int,
-        };
-        let mut cursor = Cursor::new(Vec::new());
-        {
-            let mut writer =
-                WavWriter::new(&mut cursor, spec).with_context(|| "Failed to create wav")?;
-            for sample in all_samples {
-                let sample = f32::from(sample) * volume;
-                let sample = sample.min(f32::from(i16::MAX)).max(f32::from(i16::MIN));
-                #[allow(clippy::cast_possible_truncation)]
-                writer.write_sample(sample as i16)?;
-            }
-            writer.finalize()?;
+        Ok(Audio::concat(parts_audio)?.gain(volume))
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            params: NativeParams {
+                speed: true,
+                pitch: false,
+                intonation: false,
+                volume: true,
+            },
+            ssml: false,
+            per_style_volume: true,
         }
+    }
 
-        Ok(cursor.into_inner())
+    fn max_chars(&self) -> Option<usize> {
+        (self.inner.max_chars != 0).then_some(self.inner.max_chars)
+    }
+
+    async fn health_check(&self) -> Option<Result<()>> {
+        let url = self.inner.url.clone().tap_mut(|u| {
+            u.path_segments_mut().unwrap().push("v1").push("voices");
+        });
+
+        Some(probe(&self.inner.client, url).await)
     }
 
     async fn styles(&self) -> Result<Vec<CharacterView>> {
-        Ok(self
+        let api_voices = self.inner.url.clone().tap_mut(|u| {
+            u.path_segments_mut().unwrap().push("v1").push("voices");
+        });
+
+        let voices: api::Voices = self
             .inner
+            .client
+            .get(api_voices)
+            .send()
+            .await
+            .context("Failed to get /v1/voices")?
+            .error_for_status()
+            .context("Failed to get /v1/voices")?
+            .json()
+            .await
+            .context("Failed to parse /v1/voices")?;
+
+        Ok(voices
             .voices
             .iter()
             .map(|voice| CharacterView {
@@ -199,7 +199,11 @@ impl TtsService for OmniVoice {
                     icon: vec![],
                     name: "default".to_string(),
                     id: voice.voice_id.clone(),
+                    language: None,
+                    gender: None,
                 }],
+                language: None,
+                gender: None,
             })
             .collect())
     }
@@ -0,0 +1,327 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use futures::channel::mpsc::{UnboundedReceiver, UnboundedSender, unbounded};
+use futures::{StreamExt, stream};
+use prometheus::IntGauge;
+use serenity::{
+    http::Http,
+    model::{channel::Message, id::GuildId},
+};
+use songbird::Songbird;
+use songbird::tracks::{Track, TrackQueue, TrackResult};
+use tokio::task::JoinHandle;
+
+use crate::guild_config;
+use crate::metrics::METRICS;
+use crate::model::{TimeStretchConfig, TtsStyle};
+use crate::tts::TtsServices;
+use crate::wavsource::WavSource;
+
+pub struct SpeechRequest {
+    pub style: TtsStyle,
+    pub text: String,
+    /// The message to reply to when the synthesis fails.
+    pub reply_to: Option<Message>,
+}
+
+/// Attached to every track of a request so that all of its chunks can be skipped at once.
+#[derive(Default)]
+pub struct SpeechTrack {
+    skipped: AtomicBool,
+}
+
+/// Counts a request in `pending_requests` until every chunk of it is enqueued or dropped.
+struct Pending(IntGauge);
+
+impl Pending {
+    fn new(guild_id: GuildId) -> Self {
+        let gauge = METRICS
+            .pending_requests
+            .with_label_values(&[&guild_id.to_string()]);
+        gauge.inc();
+        Self(gauge)
+    }
+}
+
+impl Drop for Pending {
+    fn drop(&mut self) {
+        self.0.dec();
+    }
+}
+
+struct Chunk {
+    request: Arc<SpeechRequest>,
+    track: Arc<SpeechTrack>,
+    _pending: Arc<Pending>,
+    /// Taken when the request is dequeued so that a reload doesn't change it mid-message.
+    timestretch_config: TimeStretchConfig,
+    index: usize,
+    text: String,
+}
+
+/// Per-guild synthesis queues.
+///
+/// Long requests are split into chunks which are played as soon as each is ready. Up to
+/// `concurrency` chunks of a guild are synthesized at once, but they are always enqueued into
+/// the songbird `TrackQueue` in the order they were submitted.
+pub struct Pipeline {
+    tts_services: TtsServices,
+    songbird: Arc<Songbird>,
+    concurrency: usize,
+    workers: Mutex<HashMap<GuildId, Worker<(SpeechRequest, Pending)>>>,
+}
+
+struct Worker<T> {
+    /// `None` once the worker is told to stop.
+    tx: Option<UnboundedSender<T>>,
+    handle: JoinHandle<()>,
+}
+
+impl Pipeline {
+    pub fn new(tts_services: TtsServices, songbird: Arc<Songbird>, concurrency: usize) -> Self {
+        Self {
+            tts_services,
+            songbird,
+            concurrency: concurrency.max(1),
+            workers: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// `http` is used to reply when the synthesis fails.
+    pub fn submit(&self, http: &Arc<Http>, guild_id: GuildId, request: SpeechRequest) {
+        let request = (request, Pending::new(guild_id));
+
+        send_or_spawn(&mut self.workers.lock().unwrap(), guild_id, request, |rx| {
+            worker(
+                http.clone(),
+                self.songbird.clone(),
+                guild_id,
+                self.tts_services.clone(),
+                self.concurrency,
+                rx,
+            )
+        });
+    }
+
+    /// Stops the worker of the guild after the pending requests are processed.
+    pub fn remove(&self, guild_id: GuildId) {
+        stop(&mut self.workers.lock().unwrap(), guild_id);
+    }
+}
+
+/// Sends `request` to the worker of the guild, or starts a new one by `spawn` if there is none.
+///
+/// A new worker waits for the previous one to drain, so that the requests of a guild are
+/// processed in order even if the bot rejoins meanwhile.
+fn send_or_spawn<T, F>(
+    workers: &mut HashMap<GuildId, Worker<T>>,
+    guild_id: GuildId,
+    request: T,
+    spawn: impl FnOnce(UnboundedReceiver<T>) -> F,
+) where
+    T: Send + 'static,
+    F: Future<Output = ()> + Send + 'static,
+{
+    // The worker may have exited, start a new one in that case.
+    let request = match workers.get(&guild_id) {
+        Some(Worker { tx: Some(tx), .. }) => match tx.unbounded_send(request) {
+            Ok(()) => return,
+            Err(e) => e.into_inner(),
+        },
+        _ => request,
+    };
+
+    let previous = workers.remove(&guild_id).map(|worker| worker.handle);
+
+    let (tx, rx) = unbounded();
+    tx.unbounded_send(request).unwrap();
+
+    let task = spawn(rx);
+    let handle = tokio::spawn(async move {
+        if let Some(previous) = previous {
+            previous.await.ok();
+        }
+
+        task.await;
+    });
+
+    workers.insert(
+        guild_id,
+        Worker {
+            tx: Some(tx),
+            handle,
+        },
+    );
+}
+
+fn stop<T>(workers: &mut HashMap<GuildId, Worker<T>>, guild_id: GuildId) {
+    if let Some(worker) = workers.get_mut(&guild_id) {
+        worker.tx = None;
+    }
+}
+
+async fn worker(
+    http: Arc<Http>,
+    songbird: Arc<Songbird>,
+    guild_id: GuildId,
+    tts_services: TtsServices,
+    concurrency: usize,
+    rx: UnboundedReceiver<(SpeechRequest, Pending)>,
+) {
+    rx.then(|(request, pending)| {
+        let tts_services = tts_services.clone();
+
+        async move {
+            let chunks = tts_services.split(&request.style, &request.text).await;
+            let request = Arc::new(request);
+            let track = Arc::new(SpeechTrack::default());
+            let pending = Arc::new(pending);
+            let timestretch_config = guild_config(guild_id).timestretch;
+
+            stream::iter(
+                chunks
+                    .into_iter()
+                    .enumerate()
+                    .map(move |(index, text)| Chunk {
+                        request: request.clone(),
+                        track: track.clone(),
+                        _pending: pending.clone(),
+                        timestretch_config,
+                        index,
+                        text,
+                    }),
+            )
+        }
+    })
+    .flatten()
+    .map(|chunk| {
+        let tts_services = tts_services.clone();
+
+        async move {
+            let audio = tts_services.tts(&chunk.request.style, &chunk.text).await;
+            (chunk, audio)
+        }
+    })
+    .buffered(concurrency)
+    .fold(0.0, |start, (chunk, audio)| {
+        let http = http.clone();
+        let songbird = songbird.clone();
+
+        async move {
+            let start = if chunk.index == 0 { 0.0 } else { start };
+
+            // Skipped or a previous chunk has failed
+            if chunk.track.skipped.load(Ordering::Relaxed) {
+                return start;
+            }
+
+            let audio = audio.and_then(|audio| {
+                let duration = audio.duration();
+                let (source, sample_rate) =
+                    WavSource::new(audio, &chunk.timestretch_config, start)?;
+                Ok((source, sample_rate, duration))
+            });
+
+            let (source, sample_rate, duration) = match audio {
+                Ok(v) => v,
+                Err(e) => {
+                    chunk.track.skipped.store(true, Ordering::Relaxed);
+
+                    if let Some(msg) = &chunk.request.reply_to {
+                        msg.reply(&http, &format!("Error: Failed to synthesise a message {e}"))
+                            .await
+                            .ok();
+                    }
+                    return start;
+                }
+            };
+
+            // The bot may have left while synthesizing.
+            let Some(handler) = songbird.get(guild_id) else {
+                return start + duration;
+            };
+
+            handler
+                .lock()
+                .await
+                .enqueue(Track::new_with_data(
+                    songbird::input::RawAdapter::new(source, sample_rate, 1).into(),
+                    chunk.track.clone(),
+                ))
+                .await;
+
+            start + duration
+        }
+    })
+    .await;
+}
+
+/// Skips the current message including its remaining chunks.
+pub fn skip(queue: &TrackQueue) -> TrackResult<()> {
+    if let Some(current) = queue.current() {
+        let track = current.data::<SpeechTrack>();
+        track.skipped.store(true, Ordering::Relaxed);
+
+        queue.modify_queue(|queued| {
+            // The current one is stopped by `skip`.
+            let mut i = 1;
+
+            while i < queued.len() {
+                if Arc::ptr_eq(&queued[i].data::<SpeechTrack>(), &track) {
+                    queued.remove(i).unwrap().stop().ok();
+                } else {
+                    i += 1;
+                }
+            }
+        });
+    }
+
+    queue.skip()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_worker_order() {
+        let guild_id = GuildId::new(1);
+        let order = Arc::new(Mutex::new(vec![]));
+        let mut workers = HashMap::new();
+
+        let spawn = |rx: UnboundedReceiver<u32>| {
+            let order = order.clone();
+
+            rx.for_each(move |i| {
+                let order = order.clone();
+
+                async move {
+                    // The requests before the rejoin are slow.
+                    if i < 10 {
+                        tokio::time::sleep(Duration::from_millis(20)).await;
+                    }
+
+                    order.lock().unwrap().push(i);
+                }
+            })
+        };
+
+        send_or_spawn(&mut workers, guild_id, 1, spawn);
+        send_or_spawn(&mut workers, guild_id, 2, spawn);
+
+        // Rejoined while the first worker is still draining.
+        stop(&mut workers, guild_id);
+        send_or_spawn(&mut workers, guild_id, 10, spawn);
+        send_or_spawn(&mut workers, guild_id, 11, spawn);
+
+        let worker = workers.remove(&guild_id).unwrap();
+        drop(worker.tx);
+        worker.handle.await.unwrap();
+
+        assert_eq!(*order.lock().unwrap(), [1, 2, 10, 11]);
+    }
+}
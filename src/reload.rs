@@ -0,0 +1,222 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use notify::{RecursiveMode, Watcher};
+use tokio::sync::Mutex;
+
+use crate::model::{LiveConfig, TtsConfig, TtsServiceConfig};
+use crate::tts::TtsServices;
+
+/// Wait for the editor to finish writing before reading the file.
+const DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// Services to (re)build and to remove to go from `old` to `new`.
+fn diff_services<'a>(
+    old: &HashMap<String, TtsServiceConfig>,
+    new: &'a HashMap<String, TtsServiceConfig>,
+) -> (Vec<(&'a String, &'a TtsServiceConfig)>, Vec<String>) {
+    let build = new
+        .iter()
+        .filter(|(id, config)| old.get(*id) != Some(*config))
+        .collect();
+
+    let remove = old
+        .keys()
+        .filter(|id| !new.contains_key(*id))
+        .cloned()
+        .collect();
+
+    (build, remove)
+}
+
+/// Re-applies the TTS config file to the running bot.
+///
+/// Only `tts_services` and the options in [`LiveConfig`] are reloaded, the other options take
+/// effect after a restart.
+pub struct Reloader {
+    path: PathBuf,
+    tts_services: TtsServices,
+    live_config: &'static arc_swap::ArcSwap<LiveConfig>,
+    /// The services currently registered, by the config they were built from.
+    applied: Mutex<HashMap<String, TtsServiceConfig>>,
+}
+
+impl Reloader {
+    pub fn new(
+        path: PathBuf,
+        tts_services: TtsServices,
+        live_config: &'static arc_swap::ArcSwap<LiveConfig>,
+        applied: HashMap<String, TtsServiceConfig>,
+    ) -> Self {
+        Self {
+            path,
+            tts_services,
+            live_config,
+            applied: Mutex::new(applied),
+        }
+    }
+
+    /// Each service is swapped in as soon as it is built, so requests may see some services of
+    /// the new config and some of the old one until the reload completes. The [`LiveConfig`] is
+    /// swapped last.
+    pub async fn reload(&self) -> Result<()> {
+        let config = TtsConfig::new(&self.path)?;
+        let mut applied = self.applied.lock().await;
+
+        let (build, remove) = diff_services(&applied, &config.tts_services);
+
+        for service_id in remove {
+            self.tts_services.unregister(&service_id).await;
+            applied.remove(&service_id);
+            tracing::info!("Removed service {service_id}");
+        }
+
+        // A service which fails to build keeps its previous instance until a retry succeeds.
+        for (service_id, service_config) in build {
+            let action = if applied.contains_key(service_id) {
+                "Rebuilt"
+            } else {
+                "Added"
+            };
+
+            let id = service_id.clone();
+            let config = service_config.clone();
+
+            if self
+                .tts_services
+                .register_or_retry(service_id, move || config.build(&id))
+                .await
+            {
+                tracing::info!("{action} service {service_id}");
+            }
+
+            applied.insert(service_id.clone(), service_config.clone());
+        }
+
+        let live_config = LiveConfig::from(&config);
+
+        if !self
+            .tts_services
+            .is_available(
+                &live_config.default_style.service_id,
+                &live_config.default_style.style_id,
+            )
+            .await
+        {
+            tracing::warn!(
+                "default_style {}/{} is not available",
+                live_config.default_style.service_id,
+                live_config.default_style.style_id,
+            );
+        }
+
+        self.live_config.store(Arc::new(live_config));
+
+        Ok(())
+    }
+
+    /// Reloads when the file changes or on SIGHUP.
+    pub fn spawn(self) -> Result<()> {
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+
+        // Watch the directory since editors and Kubernetes ConfigMaps replace the file instead of
+        // writing to it.
+        let dir = match self.path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+            _ => PathBuf::from("."),
+        };
+
+        let watch_tx = tx.clone();
+        let mut watcher = notify::recommended_watcher(move |event: notify::Result<_>| {
+            if let Ok(notify::Event { kind, .. }) = event
+                && !kind.is_access()
+            {
+                watch_tx.send(false).ok();
+            }
+        })
+        .context("Failed to create a file watcher")?;
+
+        watcher
+            .watch(&dir, RecursiveMode::NonRecursive)
+            .with_context(|| format!("Failed to watch {}", dir.display()))?;
+
+        #[cfg(unix)]
+        {
+            use tokio::signal::unix::{SignalKind, signal};
+
+            let mut hangup = signal(SignalKind::hangup()).context("Failed to listen SIGHUP")?;
+
+            tokio::spawn(async move {
+                while hangup.recv().await.is_some() {
+                    tracing::info!("Received SIGHUP, reloading");
+                    tx.send(true).ok();
+                }
+            });
+        }
+
+        tokio::spawn(async move {
+            // Keep the watcher alive as long as the task.
+            let _watcher = watcher;
+            let mut last = std::fs::read_to_string(&self.path).ok();
+
+            while let Some(mut forced) = rx.recv().await {
+                tokio::time::sleep(DEBOUNCE).await;
+                while let Ok(v) = rx.try_recv() {
+                    forced |= v;
+                }
+
+                // Other files in the directory and touching without a change are ignored, unless
+                // the reload is requested by SIGHUP.
+                let current = std::fs::read_to_string(&self.path).ok();
+                if !forced && (current.is_none() || current == last) {
+                    continue;
+                }
+                last = current;
+
+                match self.reload().await {
+                    Ok(()) => tracing::info!("Reloaded {}", self.path.display()),
+                    Err(e) => tracing::warn!("Failed to reload {}: {e:#}", self.path.display()),
+                }
+            }
+        });
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn service(url: &str) -> TtsServiceConfig {
+        TtsServiceConfig::Voicevox(
+            toml::from_str(&format!("url = \"{url}\"")).expect("Invalid VOICEVOX setting"),
+        )
+    }
+
+    #[test]
+    fn test_diff_services() {
+        let old = HashMap::from([
+            ("kept".to_string(), service("http://kept/")),
+            ("changed".to_string(), service("http://old/")),
+            ("removed".to_string(), service("http://removed/")),
+        ]);
+
+        let new = HashMap::from([
+            ("kept".to_string(), service("http://kept/")),
+            ("changed".to_string(), service("http://new/")),
+            ("added".to_string(), service("http://added/")),
+        ]);
+
+        let (build, remove) = diff_services(&old, &new);
+
+        let mut build: Vec<_> = build.into_iter().map(|(id, _)| id.as_str()).collect();
+        build.sort_unstable();
+
+        assert_eq!(build, vec!["added", "changed"]);
+        assert_eq!(remove, vec!["removed".to_string()]);
+    }
+}
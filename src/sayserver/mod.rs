@@ -1,15 +1,15 @@
 use std::collections::HashMap;
-use std::io::Cursor;
 use std::sync::Arc;
 
 use anyhow::{Context, Result};
 use async_trait::async_trait;
-use hound::{WavReader, WavWriter};
 use reqwest::header::{HeaderMap, HeaderName};
 use serde::Deserialize;
 use tap::Tap;
 
-use crate::tts::{CharacterView, StyleView, TtsService, split_long_text};
+use crate::audio::Audio;
+use crate::model::TtsParams;
+use crate::tts::{CharacterView, StyleView, TtsService, probe, split_long_text};
 
 mod api;
 
@@ -29,7 +29,7 @@ fn default_max_chars() -> usize {
     500
 }
 
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Deserialize, Debug, Clone, PartialEq)]
 pub struct Setting {
     pub url: reqwest::Url,
     #[serde(default = "default_headers")]
@@ -48,7 +48,6 @@ struct SayServerInner {
     url: reqwest::Url,
     master_volume: f32,
     allowed_voices: Vec<String>,
-    voices: Vec<api::Voice>,
     max_chars: usize,
 }
 
@@ -59,7 +58,7 @@ pub struct SayServer {
 }
 
 impl SayServer {
-    pub async fn new(setting: &Setting) -> Result<Self> {
+    pub fn new(setting: &Setting) -> Result<Self> {
         let mut headers = HeaderMap::new();
 
         for (key, value) in &setting.headers {
@@ -69,31 +68,16 @@ impl SayServer {
             );
         }
 
-        let api_voices = setting.url.clone().tap_mut(|u| {
-            u.path_segments_mut().unwrap().push("api").push("voices");
-        });
-
         let client = reqwest::ClientBuilder::new()
             .default_headers(headers)
             .user_agent("discord-tts-sayserver/0.0.0")
             .build()
             .unwrap();
 
-        let voices = client
-            .get(api_voices)
-            .send()
-            .await
-            .context("Failed to get /api/voices")?
-            .error_for_status()
-            .context("Failed to get /api/voices")?;
-
-        let voices = voices.json().await.context("Failed to parse /api/voices")?;
-
         Ok(SayServer {
             inner: Arc::new(SayServerInner {
                 url: setting.url.clone(),
                 master_volume: setting.master_volume,
-                voices,
                 client,
                 max_chars: setting.max_chars,
                 allowed_voices: setting.allowed_voices.clone(),
@@ -104,7 +88,7 @@ impl SayServer {
 
 #[async_trait]
 impl TtsService for SayServer {
-    async fn tts(&self, style_id: &str, text: &str) -> Result<Vec<u8>> {
+    async fn tts(&self, style_id: &str, text: &str, _params: &TtsParams) -> Result<Audio> {
         let api_tts = self.inner.url.clone().tap_mut(|u| {
             u.path_segments_mut().unwrap().push("api").push("synthesis");
         });
@@ -115,8 +99,7 @@ impl TtsService for SayServer {
             split_long_text(text, self.inner.max_chars)
         };
 
-        let mut all_samples: Vec<i16> = Vec::new();
-        let mut sample_rate = None;
+        let mut parts_audio = vec![];
 
         for part in parts {
             let query = api::TtsRequest {
@@ -140,46 +123,45 @@ impl TtsService for SayServer {
                 .await
                 .context("Failed to post /api/synthesis (body)")?;
 
-            let mut reader = WavReader::new(Cursor::new(wav_data))
-                .with_context(|| "Failed to read as wav file")?;
-            let spec = reader.spec();
-            if sample_rate.is_none() {
-                sample_rate = Some(spec.sample_rate);
-            }
-
-            for sample in reader.samples::<i16>() {
-                all_samples.push(sample?);
-            }
+            parts_audio.push(Audio::from_wav(&wav_data)?);
         }
 
-        let spec = hound::WavSpec {
-            channels: 1,
-            sample_rate: sample_rate.unwrap_or(22050),
-            bits_per_sample: 16,
-            sample_format: hound::SampleFormat::Int,
-        };
+        Ok(Audio::concat(parts_audio)?.gain(self.inner.master_volume))
+    }
 
-        let mut cursor = Cursor::new(Vec::new());
+    fn max_chars(&self) -> Option<usize> {
+        (self.inner.max_chars != 0).then_some(self.inner.max_chars)
+    }
 
-        {
-            let mut writer =
-                WavWriter::new(&mut cursor, spec).with_context(|| "Failed to create wav")?;
-            for sample in all_samples {
-                let sample = f32::from(sample) * self.inner.master_volume;
-                let sample = sample.min(f32::from(i16::MAX)).max(f32::from(i16::MIN));
-                #[allow(clippy::cast_possible_truncation)]
-                writer.write_sample(sample as i16)?;
-            }
-            writer.finalize()?;
-        }
+    async fn health_check(&self) -> Option<Result<()>> {
+        let url = self.inner.url.clone().tap_mut(|u| {
+            u.path_segments_mut().unwrap().push("api").push("voices");
+        });
 
-        Ok(cursor.into_inner())
+        Some(probe(&self.inner.client, url).await)
     }
 
     async fn styles(&self) -> Result<Vec<CharacterView>> {
+        let api_voices = self.inner.url.clone().tap_mut(|u| {
+            u.path_segments_mut().unwrap().push("api").push("voices");
+        });
+
+        let voices: Vec<api::Voice> = self
+            .inner
+            .client
+            .get(api_voices)
+            .send()
+            .await
+            .context("Failed to get /api/voices")?
+            .error_for_status()
+            .context("Failed to get /api/voices")?
+            .json()
+            .await
+            .context("Failed to parse /api/voices")?;
+
         let mut styles = vec![];
 
-        for voice in &self.inner.voices {
+        for voice in &voices {
             if !self.inner.allowed_voices.contains(&voice.id) {
                 continue;
             }
@@ -188,6 +170,8 @@ impl TtsService for SayServer {
                 icon: vec![],
                 name: voice.name.clone(),
                 id: voice.id.clone(),
+                language: None,
+                gender: None,
             });
         }
 
@@ -195,6 +179,8 @@ impl TtsService for SayServer {
             name: "macOS say".to_string(),
             policy: "Apple macOS利用規約に則り、ご利用ください。".to_string(),
             styles,
+            language: None,
+            gender: None,
         }])
     }
 }
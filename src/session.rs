@@ -0,0 +1,118 @@
+use serenity::{
+    client::Context,
+    model::{
+        id::{ChannelId, GuildId},
+        prelude::Mentionable,
+    },
+};
+use songbird::{CoreEvent, error::JoinResult};
+
+use crate::db::{INMEMORY_DB, PERSISTENT_DB, Session};
+use crate::locale::Text;
+use crate::songbird_handler::DriverDisconnectNotifier;
+
+/// Joins the voice channel and links it to the text channel.
+pub async fn connect(
+    ctx: &Context,
+    guild_id: GuildId,
+    voice_channel_id: ChannelId,
+    text_channel_id: ChannelId,
+) -> JoinResult<()> {
+    let manager = songbird::get(ctx)
+        .await
+        .expect("Songbird is not initialized");
+
+    if let Some(h) = manager.get(guild_id) {
+        h.lock().await.join(voice_channel_id).await?;
+    } else {
+        let h = manager.join(guild_id, voice_channel_id).await?;
+
+        h.lock().await.add_global_event(
+            CoreEvent::DriverDisconnect.into(),
+            DriverDisconnectNotifier::new(manager),
+        );
+    }
+
+    INMEMORY_DB.store_instance(guild_id, text_channel_id);
+    PERSISTENT_DB.store_session(&Session {
+        guild: guild_id,
+        voice_channel: voice_channel_id,
+        text_channel: text_channel_id,
+    });
+
+    Ok(())
+}
+
+/// Whether anyone other than bots is in the voice channel.
+fn has_humans(ctx: &Context, guild_id: GuildId, voice_channel_id: ChannelId) -> bool {
+    let Some(guild) = ctx.cache.guild(guild_id) else {
+        return false;
+    };
+
+    let current_user = ctx.cache.current_user().id;
+
+    guild
+        .voice_states
+        .values()
+        .filter(|state| state.channel_id == Some(voice_channel_id))
+        .filter(|state| state.user_id != current_user)
+        .any(|state| {
+            state
+                .member
+                .as_ref()
+                .or_else(|| guild.members.get(&state.user_id))
+                .is_none_or(|member| !member.user.bot)
+        })
+}
+
+/// Rejoins the sessions which were active before the restart.
+///
+/// Sessions whose voice channel is empty or can no longer be joined are discarded.
+pub async fn restore(ctx: &Context) {
+    let manager = songbird::get(ctx)
+        .await
+        .expect("Songbird is not initialized");
+
+    for session in PERSISTENT_DB.get_sessions() {
+        // Already rejoined by an earlier cache_ready.
+        if manager.get(session.guild).is_some() {
+            continue;
+        }
+
+        if !has_humans(ctx, session.guild, session.voice_channel) {
+            PERSISTENT_DB.remove_session(session.guild);
+            continue;
+        }
+
+        if let Err(e) = connect(
+            ctx,
+            session.guild,
+            session.voice_channel,
+            session.text_channel,
+        )
+        .await
+        {
+            tracing::warn!("Failed to restore the session in {}: {e}", session.guild);
+            manager.remove(session.guild).await.ok();
+            PERSISTENT_DB.remove_session(session.guild);
+            continue;
+        }
+
+        tracing::info!("Restored the session in {}", session.guild);
+
+        session
+            .text_channel
+            .say(
+                &ctx.http,
+                crate::guild_config(session.guild).texts().format(
+                    Text::Reconnected,
+                    &[
+                        ("text", &session.text_channel.mention().to_string()),
+                        ("voice", &session.voice_channel.mention().to_string()),
+                    ],
+                ),
+            )
+            .await
+            .ok();
+    }
+}
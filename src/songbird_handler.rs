@@ -1,12 +1,88 @@
 use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
 
 use serenity::async_trait;
+use serenity::model::id::GuildId;
+use songbird::events::context_data::DisconnectReason;
+use songbird::id::ChannelId;
+use songbird::model::CloseCode;
 use songbird::{Event, EventContext, EventHandler as VoiceEventHandler, Songbird};
 
-use crate::db::INMEMORY_DB;
+use crate::db::{INMEMORY_DB, PERSISTENT_DB};
+
+const RECONNECT_ATTEMPTS: u32 = 5;
+const RECONNECT_INITIAL_DELAY: Duration = Duration::from_secs(2);
+const RECONNECT_MAX_DELAY: Duration = Duration::from_mins(1);
+
+/// Whether the connection may come back by joining the same channel again.
+///
+/// Kicks, channel deletions and rate limits are not, as Discord asks not to reconnect.
+fn is_recoverable(reason: DisconnectReason) -> bool {
+    match reason {
+        // `None` is a closure without a close frame, e.g. the network went down.
+        DisconnectReason::Io
+        | DisconnectReason::TimedOut
+        | DisconnectReason::Internal
+        | DisconnectReason::WsClosed(None) => true,
+        DisconnectReason::WsClosed(Some(code)) => {
+            code.should_resume() || code == CloseCode::ServerNotFound
+        }
+        _ => false,
+    }
+}
 
 pub struct DriverDisconnectNotifier {
-    pub songbird_manager: Arc<Songbird>,
+    songbird_manager: Arc<Songbird>,
+    /// Set while reconnecting, so that the failures of the attempts are not handled again.
+    reconnecting: Arc<AtomicBool>,
+}
+
+impl DriverDisconnectNotifier {
+    pub fn new(songbird_manager: Arc<Songbird>) -> Self {
+        Self {
+            songbird_manager,
+            reconnecting: Arc::default(),
+        }
+    }
+}
+
+/// Drops the text channel link and the call, which also discards the queue.
+async fn teardown(songbird_manager: &Songbird, guild_id: GuildId) {
+    INMEMORY_DB.destroy_instance(guild_id);
+    PERSISTENT_DB.remove_session(guild_id);
+    songbird_manager.remove(guild_id).await.ok();
+}
+
+/// Rejoins the channel with backoff. The call, and so the queue, is kept while retrying.
+async fn reconnect(songbird_manager: Arc<Songbird>, guild_id: GuildId, channel_id: ChannelId) {
+    let mut delay = RECONNECT_INITIAL_DELAY;
+
+    for attempt in 1..=RECONNECT_ATTEMPTS {
+        tokio::time::sleep(delay).await;
+        delay = (delay * 2).min(RECONNECT_MAX_DELAY);
+
+        // Left by a command meanwhile.
+        let Some(call) = songbird_manager.get(guild_id) else {
+            return;
+        };
+
+        let join = call.lock().await.join(channel_id).await;
+
+        match join {
+            Ok(join) => match join.await {
+                Ok(()) => {
+                    tracing::info!("Reconnected to {guild_id} (attempt {attempt})");
+                    return;
+                }
+                Err(e) => tracing::warn!("Failed to reconnect to {guild_id}: {e}"),
+            },
+            Err(e) => tracing::warn!("Failed to reconnect to {guild_id}: {e}"),
+        }
+    }
+
+    tracing::warn!("Gave up reconnecting to {guild_id}");
+    teardown(&songbird_manager, guild_id).await;
 }
 
 #[async_trait]
@@ -16,14 +92,69 @@ impl VoiceEventHandler for DriverDisconnectNotifier {
             return None;
         };
 
-        println!(
-            "Connection to {} is dropped ctx.reason is {:?}",
-            ctx.guild_id, ctx.reason
+        if self.reconnecting.load(Ordering::Relaxed) {
+            return None;
+        }
+
+        let guild_id = GuildId::new(ctx.guild_id.0.get());
+
+        tracing::info!(
+            "Connection to {guild_id} is dropped ({:?}, {:?})",
+            ctx.kind,
+            ctx.reason
         );
 
-        INMEMORY_DB.destroy_instance(ctx.guild_id.0.into());
-        self.songbird_manager.remove(ctx.guild_id).await.unwrap();
+        match ctx.reason {
+            // Superseded by another join.
+            Some(DisconnectReason::AttemptDiscarded) => {}
+            Some(reason) if is_recoverable(reason) => {
+                let songbird_manager = self.songbird_manager.clone();
+                let reconnecting = self.reconnecting.clone();
+                let channel_id = ctx.channel_id;
+
+                reconnecting.store(true, Ordering::Relaxed);
+
+                tokio::spawn(async move {
+                    reconnect(songbird_manager, guild_id, channel_id).await;
+                    reconnecting.store(false, Ordering::Relaxed);
+                });
+            }
+            // Requested by the bot, which is either a leave or a move to another channel.
+            None | Some(DisconnectReason::Requested) => {
+                let moved = match self.songbird_manager.get(guild_id) {
+                    Some(call) => call.lock().await.current_channel().is_some(),
+                    None => false,
+                };
+
+                if !moved {
+                    teardown(&self.songbird_manager, guild_id).await;
+                }
+            }
+            Some(_) => teardown(&self.songbird_manager, guild_id).await,
+        }
 
         None
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_recoverable() {
+        assert!(is_recoverable(DisconnectReason::Io));
+        assert!(is_recoverable(DisconnectReason::WsClosed(None)));
+        assert!(is_recoverable(DisconnectReason::WsClosed(Some(
+            CloseCode::VoiceServerCrash
+        ))));
+
+        assert!(!is_recoverable(DisconnectReason::ProtocolViolation));
+        assert!(!is_recoverable(DisconnectReason::WsClosed(Some(
+            CloseCode::Disconnected
+        ))));
+        assert!(!is_recoverable(DisconnectReason::WsClosed(Some(
+            CloseCode::CallTerminated
+        ))));
+    }
+}
@@ -0,0 +1,166 @@
+use std::collections::BTreeMap;
+
+use anyhow::Result;
+use unicode_width::UnicodeWidthStr;
+
+use crate::model::{CatalogFormat, CheckArgs, Cli, TtsConfig, TtsStyle};
+use crate::tts::{CharacterView, Gender, TtsServices};
+
+/// The styles the config refers to, with where they are referred from.
+fn referenced_styles(tts_config: &TtsConfig) -> Vec<(String, &TtsStyle)> {
+    let mut styles = vec![("default_style".to_string(), &tts_config.default_style)];
+
+    for (i, fallback) in tts_config.fallbacks.iter().enumerate() {
+        styles.extend(
+            fallback
+                .to
+                .iter()
+                .map(|style| (format!("fallbacks[{i}]"), style)),
+        );
+    }
+
+    styles
+}
+
+fn format_table(catalogs: &BTreeMap<String, Vec<CharacterView>>) -> String {
+    let mut rows = vec![[
+        "SERVICE".to_string(),
+        "CHARACTER".to_string(),
+        "STYLE ID".to_string(),
+        "NAME".to_string(),
+        "LANGUAGE".to_string(),
+        "GENDER".to_string(),
+    ]];
+
+    for (service_id, characters) in catalogs {
+        for character in characters {
+            for style in &character.styles {
+                rows.push([
+                    service_id.clone(),
+                    character.name.clone(),
+                    style.id.clone(),
+                    style.name.clone(),
+                    character.style_language(style).unwrap_or("-").to_string(),
+                    match character.style_gender(style) {
+                        Some(Gender::Female) => "female",
+                        Some(Gender::Male) => "male",
+                        None => "-",
+                    }
+                    .to_string(),
+                ]);
+            }
+        }
+    }
+
+    let mut widths = [0; 6];
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.width());
+        }
+    }
+
+    rows.iter()
+        .map(|row| {
+            row.iter()
+                .zip(widths)
+                // `format!` pads by chars, which misaligns full-width ones.
+                .map(|(cell, width)| format!("{cell}{}", " ".repeat(width - cell.width())))
+                .collect::<Vec<_>>()
+                .join("  ")
+                .trim_end()
+                .to_string()
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+async fn validate(tts_config: &TtsConfig, tts_services: &TtsServices) -> Vec<String> {
+    let mut problems: Vec<_> = tts_services
+        .pending()
+        .into_iter()
+        .map(|service_id| format!("{service_id} is unreachable"))
+        .collect();
+
+    for (referrer, style) in referenced_styles(tts_config) {
+        if !tts_services
+            .is_available(&style.service_id, &style.style_id)
+            .await
+        {
+            problems.push(format!(
+                "{referrer}: {}/{} is not available",
+                style.service_id, style.style_id
+            ));
+        }
+    }
+
+    problems
+}
+
+pub async fn run(cli: &Cli, args: &CheckArgs) -> Result<()> {
+    let tts_config = TtsConfig::new(&cli.tts_config_path)?;
+    let tts_services = crate::create_tts_services(&tts_config, None).await;
+
+    let problems = validate(&tts_config, &tts_services).await;
+
+    let catalogs: BTreeMap<_, _> = tts_services.styles().await.into_iter().collect();
+
+    match args.catalog {
+        Some(CatalogFormat::Json) => println!("{}", serde_json::to_string_pretty(&catalogs)?),
+        Some(CatalogFormat::Table) => println!("{}", format_table(&catalogs)),
+        None => {}
+    }
+
+    // The report goes to stderr so that the catalog can be piped.
+    for (service_id, characters) in &catalogs {
+        let count: usize = characters.iter().map(|c| c.styles.len()).sum();
+        eprintln!("{service_id}: {count} styles");
+    }
+
+    for problem in &problems {
+        eprintln!("Error: {problem}");
+    }
+
+    if !problems.is_empty() {
+        anyhow::bail!(
+            "{} problems found in {}",
+            problems.len(),
+            cli.tts_config_path.display()
+        );
+    }
+
+    eprintln!("{} is valid", cli.tts_config_path.display());
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tts::StyleView;
+
+    #[test]
+    fn test_format_table() {
+        let catalogs = BTreeMap::from([(
+            "VOICEVOX".to_string(),
+            vec![CharacterView {
+                name: "ずんだもん".to_string(),
+                policy: String::new(),
+                styles: vec![StyleView {
+                    icon: vec![],
+                    name: "ノーマル".to_string(),
+                    id: "3".to_string(),
+                    language: None,
+                    gender: None,
+                }],
+                language: Some("ja".to_string()),
+                gender: Some(Gender::Female),
+            }],
+        )]);
+
+        assert_eq!(
+            format_table(&catalogs),
+            "SERVICE   CHARACTER   STYLE ID  NAME      LANGUAGE  GENDER\n\
+             VOICEVOX  ずんだもん  3         ノーマル  ja        female"
+        );
+    }
+}
@@ -0,0 +1,48 @@
+use std::sync::Arc;
+
+use anyhow::Result;
+use serenity::model::id::GuildId;
+
+use crate::db::PersistentDB;
+use crate::dictionary::Dictionary;
+use crate::filter::{FilterInput, FilterOptions, filter_traced};
+use crate::model::{Cli, FilterArgs, LiveConfig, TtsConfig};
+
+pub fn run(cli: &Cli, args: &FilterArgs) -> Result<()> {
+    let tts_config = TtsConfig::new(&cli.tts_config_path)?;
+    let mut live_config = LiveConfig::from(&tts_config);
+
+    let dictionary = match args.guild.map(GuildId::from) {
+        Some(guild_id) => {
+            let db = PersistentDB::open_read_only(&cli.database_path)?;
+            live_config = live_config.with_guild(&db.get_guild_settings(guild_id));
+            db.get_dictionary(guild_id)
+        }
+        None => Arc::new(Dictionary::new(&[])?),
+    };
+
+    let input = FilterInput {
+        image_count: args.images,
+        file_count: args.files,
+        forwarded: args.forwarded,
+        ..FilterInput::from_text(&args.text)
+    };
+
+    let options = FilterOptions {
+        config: &live_config.filter,
+        texts: live_config
+            .catalog
+            .localized(args.locale.unwrap_or(live_config.locale)),
+        suppress_prefix: &live_config.suppress_prefix,
+        dictionary: &dictionary,
+    };
+
+    println!("{:<16} {:?}", "input", args.text);
+
+    filter_traced(&input, &options, &mut |stage, output| match output {
+        Some(output) => println!("{stage:<16} {output:?}"),
+        None => println!("{stage:<16} (dropped)"),
+    });
+
+    Ok(())
+}
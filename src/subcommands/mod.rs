@@ -0,0 +1,15 @@
+use anyhow::Result;
+
+use crate::model::{Cli, Command};
+
+mod check;
+mod filter;
+mod synth;
+
+pub async fn run(cli: &Cli, command: &Command) -> Result<()> {
+    match command {
+        Command::Synth(args) => synth::run(cli, args).await,
+        Command::Check(args) => check::run(cli, args).await,
+        Command::Filter(args) => filter::run(cli, args),
+    }
+}
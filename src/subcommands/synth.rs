@@ -0,0 +1,70 @@
+use anyhow::{Context, Result};
+
+use crate::audio::Audio;
+use crate::dictionary::Dictionary;
+use crate::filter::{FilterInput, FilterOptions, filter_input};
+use crate::model::{Cli, LiveConfig, SynthArgs, TtsConfig, TtsParams, TtsStyle};
+use crate::timestretch::apply_time_stretch;
+
+pub async fn run(cli: &Cli, args: &SynthArgs) -> Result<()> {
+    let tts_config = TtsConfig::new(&cli.tts_config_path)?;
+    let live_config = LiveConfig::from(&tts_config);
+
+    let tts_services = crate::create_tts_services(&tts_config, None).await;
+
+    if !tts_services.is_available(&args.service, &args.style).await {
+        anyhow::bail!("{}/{} is not available", args.service, args.style);
+    }
+
+    let text = if args.filter {
+        filter_input(
+            &FilterInput::from_text(&args.text),
+            &FilterOptions {
+                config: &live_config.filter,
+                texts: live_config.texts(),
+                suppress_prefix: &live_config.suppress_prefix,
+                dictionary: &Dictionary::new(&[])?,
+            },
+        )
+        .context("The text is filtered out")?
+    } else {
+        args.text.clone()
+    };
+
+    let style = TtsStyle {
+        service_id: args.service.clone(),
+        style_id: args.style.clone(),
+        params: TtsParams::default(),
+    };
+
+    let mut parts = vec![];
+
+    // Split as in a voice channel, since the backends may reject long text.
+    for chunk in tts_services.split(&style, &text).await {
+        parts.push(tts_services.tts(&style, &chunk).await?);
+    }
+
+    let mut audio = Audio::concat(parts)?;
+
+    if args.timestretch {
+        audio = audio.into_mono();
+        audio.samples = apply_time_stretch(
+            &audio.samples,
+            1,
+            audio.sample_rate,
+            &live_config.timestretch,
+            0.0,
+        )?;
+    }
+
+    std::fs::write(&args.output, audio.to_wav()?)
+        .with_context(|| format!("Failed to write {}", args.output.display()))?;
+
+    println!(
+        "Wrote {:.1}s to {}",
+        audio.duration(),
+        args.output.display()
+    );
+
+    Ok(())
+}
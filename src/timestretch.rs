@@ -1,5 +1,6 @@
 //! Time-stretching via rubato 1.x asynchronous resampler (sinc interpolation).
 
+use anyhow::{Context, Result};
 use rubato::audioadapter_buffers::direct::SequentialSliceOfVecs;
 use rubato::{
     Adjustable, Async, FixedAsync, Indexing, Resampler, SincInterpolationParameters,
@@ -7,13 +8,28 @@ use rubato::{
 };
 
 /// Applies time-stretching acceleration to the input audio.
+///
+/// `start` is the duration in seconds of the audio already played before the input, so that
+/// the ramp continues across chunks of a message.
 #[allow(clippy::too_many_lines)]
 pub fn apply_time_stretch(
     input_samples: &[i16],
     channels: usize,
     input_sample_rate: u32,
     config: &crate::model::TimeStretchConfig,
-) -> Vec<i16> {
+    start: f64,
+) -> Result<Vec<i16>> {
+    let range = crate::model::TimeStretchConfig::SPEED_RANGE;
+
+    if !range.contains(&config.target_speed) {
+        anyhow::bail!(
+            "target_speed must be between {} and {}, but it is {}",
+            range.start(),
+            range.end(),
+            config.target_speed
+        );
+    }
+
     let params = SincInterpolationParameters {
         sinc_len: 256,
         f_cutoff: Some(0.95),
@@ -34,7 +50,7 @@ pub fn apply_time_stretch(
         channels,
         FixedAsync::Input,
     )
-    .expect("failed to create resampler");
+    .context("Failed to create resampler")?;
 
     let min_allowed_ratio = base_ratio / max_relative_ratio;
     let max_allowed_ratio = base_ratio * max_relative_ratio;
@@ -62,7 +78,7 @@ pub fn apply_time_stretch(
 
     while input_frames[0].len() >= resampler.input_frames_next() {
         #[allow(clippy::cast_precision_loss)]
-        let processed_seconds = processed_frames as f64 / f64::from(input_sample_rate);
+        let processed_seconds = start + processed_frames as f64 / f64::from(input_sample_rate);
 
         let progress = if processed_seconds < config.initial_delay {
             0.0
@@ -76,7 +92,7 @@ pub fn apply_time_stretch(
 
         resampler
             .set_resample_ratio(clamped_ratio, true)
-            .expect("set_resample_ratio");
+            .context("Failed to set resample ratio")?;
 
         let chunk: Vec<Vec<f32>> = (0..channels)
             .map(|c| input_frames[c].drain(0..chunk_size).collect())
@@ -93,7 +109,7 @@ pub fn apply_time_stretch(
 
         let (frames_read, frames_written) = resampler
             .process_into_buffer(&input_adapter, &mut output_adapter, Some(&indexing))
-            .expect("resampling failed");
+            .context("Failed to resample")?;
 
         processed_frames += frames_read as u64;
 
@@ -137,7 +153,7 @@ pub fn apply_time_stretch(
 
         let (_frames_read, frames_written) = resampler
             .process_into_buffer(&input_adapter, &mut output_adapter, Some(&indexing))
-            .expect("resampling failed");
+            .context("Failed to resample")?;
 
         #[allow(clippy::needless_range_loop)]
         for i in 0..frames_written {
@@ -153,5 +169,5 @@ pub fn apply_time_stretch(
         }
     }
 
-    output_audio
+    Ok(output_audio)
 }
@@ -1,26 +1,77 @@
 use std::collections::HashMap;
-use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
-use anyhow::Result;
+use anyhow::{Context, Result};
 use async_trait::async_trait;
 use derivative::Derivative;
+use serde::Serialize;
 use tokio::sync::RwLock;
 
-#[derive(Clone, Debug)]
+use crate::audio::Audio;
+use crate::cache::{TtsCache, cache_key};
+use crate::health::{CircuitBreaker, HealthConfig};
+use crate::metrics::METRICS;
+use crate::model::{FallbackConfig, TtsParams, TtsStyle};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Gender {
+    Female,
+    Male,
+}
+
+impl Gender {
+    /// Parses the notations used by the backends, such as `f`, `Male` and `female`.
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "f" | "female" => Some(Self::Female),
+            "m" | "male" => Some(Self::Male),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for Gender {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Female => write!(f, "Female"),
+            Self::Male => write!(f, "Male"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
 pub struct StyleView {
+    #[serde(skip)]
     pub icon: Vec<u8>,
     pub name: String,
     pub id: String,
+    /// BCP 47 language tag such as `ja` or `en-US`.
+    pub language: Option<String>,
+    pub gender: Option<Gender>,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize)]
 pub struct CharacterView {
     pub name: String,
     pub policy: String,
     pub styles: Vec<StyleView>,
+    /// Applies to the styles which have no tag of their own.
+    pub language: Option<String>,
+    pub gender: Option<Gender>,
 }
 
-pub static EMPTY_WAVE: [u8; 44] = [0x52, 0x49, 0x46, 0x46, 0x24, 0x00, 0x00, 0x00, 0x57, 0x41, 0x56, 0x45, 0x66, 0x6d, 0x74, 0x20, 0x10, 0x00, 0x00, 0x00, 0x01, 0x00, 0x01, 0x00, 0x44, 0xac, 0x00, 0x00, 0x88, 0x58, 0x01, 0x00, 0x02, 0x00, 0x10, 0x00, 0x64, 0x61, 0x74, 0x61, 0x00, 0x00, 0x00, 0x00];
+impl CharacterView {
+    pub fn style_language<'a>(&'a self, style: &'a StyleView) -> Option<&'a str> {
+        style.language.as_deref().or(self.language.as_deref())
+    }
+
+    pub fn style_gender(&self, style: &StyleView) -> Option<Gender> {
+        style.gender.or(self.gender)
+    }
+}
 
 pub fn split_long_text(text: &str, max_length: usize) -> Vec<String> {
     // Regex for whitespace including Zero Width No-Break Space and No-Break Space
@@ -81,87 +132,40 @@ pub fn split_long_text(text: &str, max_length: usize) -> Vec<String> {
     result
 }
 
-pub fn convert_mp3_to_wav(mp3_data: Vec<u8>, gain: f32) -> anyhow::Result<Vec<u8>> {
-    use std::io::Cursor;
-    use symphonia::core::codecs::audio::AudioDecoderOptions;
-    use symphonia::core::formats::FormatOptions;
-    use symphonia::core::formats::TrackType;
-    use symphonia::core::formats::probe::Hint;
-    use symphonia::core::io::{MediaSourceStream, MediaSourceStreamOptions};
-    use symphonia::core::meta::MetadataOptions;
-
-    let mss = MediaSourceStream::new(
-        Box::new(Cursor::new(mp3_data)),
-        MediaSourceStreamOptions::default(),
-    );
-    let mut hint = Hint::new();
-    hint.with_extension("mp3");
-
-    let probed = symphonia::default::get_probe().probe(
-        &hint,
-        mss,
-        FormatOptions::default(),
-        MetadataOptions::default(),
-    )?;
-
-    let mut format = probed;
-    let track = format
-        .default_track(TrackType::Audio)
-        .ok_or_else(|| anyhow::anyhow!("No audio track found"))?;
-
-    let mut decoder = symphonia::default::get_codecs().make_audio_decoder(
-        track.codec_params.as_ref().unwrap().audio().unwrap(),
-        &AudioDecoderOptions::default(),
-    )?;
-
-    let track_id = track.id;
-    let spec = hound::WavSpec {
-        channels: 1,        // Google TTS is usually mono
-        sample_rate: 24000, // Google TTS is usually 24kHz
-        bits_per_sample: 16,
-        sample_format: hound::SampleFormat::Int,
-    };
-
-    let mut wav_cursor = Cursor::new(Vec::new());
-    let mut wav_writer = hound::WavWriter::new(&mut wav_cursor, spec)?;
-
-    loop {
-        let packet = match format.next_packet() {
-            Ok(Some(packet)) => packet,
-            Ok(None) => break, // End of stream
-            Err(e) => return Err(e.into()),
-        };
-
-        if packet.track_id != track_id {
-            continue;
-        }
+/// Which of the [`TtsParams`] a backend applies by itself.
+///
+/// Parameters the backend doesn't handle are emulated by [`apply_params_fallback`] where possible.
+#[allow(clippy::struct_excessive_bools)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NativeParams {
+    pub speed: bool,
+    pub pitch: bool,
+    pub intonation: bool,
+    pub volume: bool,
+}
 
-        match decoder.decode(&packet) {
-            Ok(decoded) => {
-                // Copy audio samples in interleaved format and process them
-                let num_samples = decoded.samples_interleaved();
-                let mut samples = vec![0f32; num_samples];
-                decoded.copy_to_slice_interleaved(&mut samples);
+/// Emulates the speed and volume parameters that the backend doesn't support natively.
+///
+/// The speed is changed by relabelling the sample rate, so it shifts the pitch as well.
+/// Pitch and intonation cannot be emulated and are ignored.
+pub fn apply_params_fallback(mut audio: Audio, params: &TtsParams, native: NativeParams) -> Audio {
+    if (params.pitch.is_some() && !native.pitch)
+        || (params.intonation.is_some() && !native.intonation)
+    {
+        tracing::debug!("Pitch and intonation are not supported by this backend, ignored");
+    }
 
-                // Process samples (convert to mono i16)
-                for chunk in samples.chunks(decoded.num_planes()) {
-                    // Average channels to mono
-                    #[allow(clippy::cast_precision_loss)]
-                    let mono_sample = chunk.iter().sum::<f32>() / chunk.len() as f32;
-                    let sample = mono_sample * gain * f32::from(i16::MAX);
-                    let sample = sample.min(f32::from(i16::MAX)).max(f32::from(i16::MIN));
+    let speed = params.speed.filter(|_| !native.speed).unwrap_or(1.0);
+    let volume = params.volume.filter(|_| !native.volume).unwrap_or(1.0);
 
-                    #[allow(clippy::cast_possible_truncation)]
-                    wav_writer.write_sample(sample as i16)?;
-                }
-            }
-            Err(symphonia::core::errors::Error::DecodeError(_)) => {} // Skip decode errors
-            Err(e) => return Err(e.into()),
-        }
+    #[allow(clippy::cast_precision_loss)]
+    #[allow(clippy::cast_possible_truncation)]
+    #[allow(clippy::cast_sign_loss)]
+    {
+        audio.sample_rate = (audio.sample_rate as f32 * speed).round() as u32;
     }
 
-    wav_writer.finalize()?;
-    Ok(wav_cursor.into_inner())
+    audio.gain(volume)
 }
 
 #[cfg(test)]
@@ -202,6 +206,393 @@ mod tests {
         assert_eq!(parts[1], "い".repeat(100));
     }
 
+    #[test]
+    fn test_params_fallback() {
+        let audio = Audio {
+            samples: vec![1000, -1000],
+            sample_rate: 24000,
+            channels: 1,
+        };
+
+        let params = TtsParams {
+            speed: Some(1.5),
+            volume: Some(0.5),
+            ..TtsParams::default()
+        };
+
+        let processed = apply_params_fallback(audio.clone(), &params, NativeParams::default());
+        assert_eq!(processed.sample_rate, 36000);
+        assert_eq!(processed.samples, vec![500, -500]);
+
+        let native = NativeParams {
+            speed: true,
+            volume: true,
+            ..NativeParams::default()
+        };
+        assert_eq!(apply_params_fallback(audio.clone(), &params, native), audio);
+    }
+
+    #[derive(Debug)]
+    struct MockService {
+        audio: Option<Audio>,
+    }
+
+    #[async_trait]
+    impl TtsService for MockService {
+        async fn tts(&self, _style_id: &str, _text: &str, _params: &TtsParams) -> Result<Audio> {
+            self.audio.clone().context("Mock failure")
+        }
+
+        async fn styles(&self) -> Result<Vec<CharacterView>> {
+            Ok(vec![])
+        }
+    }
+
+    fn style(service_id: &str, style_id: &str) -> TtsStyle {
+        TtsStyle {
+            service_id: service_id.to_string(),
+            style_id: style_id.to_string(),
+            params: TtsParams::default(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_fallback_chain() {
+        let services = TtsServices::new(
+            None,
+            vec![
+                FallbackConfig {
+                    service_id: "broken".to_string(),
+                    style_id: None,
+                    to: vec![style("missing", "0"), style("ok", "0")],
+                },
+                FallbackConfig {
+                    service_id: "broken".to_string(),
+                    style_id: Some("1".to_string()),
+                    to: vec![style("missing", "1")],
+                },
+            ],
+            None,
+            HealthConfig::default(),
+        );
+
+        services
+            .replace("broken", Box::new(MockService { audio: None }))
+            .await
+            .unwrap();
+        services
+            .replace(
+                "ok",
+                Box::new(MockService {
+                    audio: Some(Audio::silence()),
+                }),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(
+            services.tts(&style("broken", "0"), "text").await.unwrap(),
+            Audio::silence()
+        );
+
+        // The per-style chain takes precedence over the per-service one.
+        assert!(services.tts(&style("broken", "1"), "text").await.is_err());
+        assert!(services.tts(&style("missing", "0"), "text").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_circuit_breaker() {
+        let services = TtsServices::new(
+            None,
+            vec![FallbackConfig {
+                service_id: "broken".to_string(),
+                style_id: None,
+                to: vec![style("ok", "0")],
+            }],
+            None,
+            HealthConfig {
+                failure_threshold: 2,
+                ..HealthConfig::default()
+            },
+        );
+
+        services
+            .replace("broken", Box::new(MockService { audio: None }))
+            .await
+            .unwrap();
+        services
+            .replace(
+                "ok",
+                Box::new(MockService {
+                    audio: Some(Audio::silence()),
+                }),
+            )
+            .await
+            .unwrap();
+
+        assert!(!services.is_down("broken"));
+
+        for _ in 0..2 {
+            assert!(
+                services
+                    .tts_once(&style("broken", "0"), "text")
+                    .await
+                    .is_err()
+            );
+        }
+
+        assert!(services.is_down("broken"));
+
+        // Rejected without reaching the backend during the cooldown.
+        let error = services
+            .tts_once(&style("broken", "0"), "text")
+            .await
+            .unwrap_err();
+        assert_eq!(error.to_string(), "'broken' is down");
+
+        // The fallbacks still cover the styles of the service while it is down.
+        assert_eq!(
+            services.tts(&style("broken", "0"), "text").await.unwrap(),
+            Audio::silence()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_down_without_fallbacks() {
+        let services = TtsServices::new(
+            None,
+            vec![FallbackConfig {
+                service_id: "covered".to_string(),
+                style_id: None,
+                to: vec![style("mock", "0")],
+            }],
+            None,
+            HealthConfig {
+                failure_threshold: 1,
+                ..HealthConfig::default()
+            },
+        );
+
+        for service_id in ["mock", "covered"] {
+            services
+                .replace(
+                    service_id,
+                    Box::new(CatalogMock {
+                        style_ids: Arc::new(Mutex::new(vec!["0"])),
+                    }),
+                )
+                .await
+                .unwrap();
+
+            services.record(service_id, Err(&anyhow::anyhow!("Connection refused")));
+        }
+
+        // Without fallbacks, the callers switch to the default style instead of failing.
+        assert!(!services.is_available("mock", "0").await);
+        assert!(services.is_available("covered", "0").await);
+    }
+
+    #[derive(Debug)]
+    struct HangingService;
+
+    #[async_trait]
+    impl TtsService for HangingService {
+        async fn tts(&self, _style_id: &str, _text: &str, _params: &TtsParams) -> Result<Audio> {
+            std::future::pending().await
+        }
+
+        async fn styles(&self) -> Result<Vec<CharacterView>> {
+            Ok(vec![])
+        }
+    }
+
+    #[tokio::test]
+    async fn test_replace_during_synthesis() {
+        let services = TtsServices::new(None, vec![], None, HealthConfig::default());
+
+        services
+            .replace("hanging", Box::new(HangingService))
+            .await
+            .unwrap();
+
+        let synthesis = tokio::spawn({
+            let services = services.clone();
+            async move { services.tts(&style("hanging", "0"), "text").await }
+        });
+        tokio::time::sleep(Duration::from_millis(10)).await;
+
+        // The running request keeps the old instance without blocking the swap.
+        tokio::time::timeout(
+            Duration::from_secs(1),
+            services.replace("hanging", Box::new(MockService { audio: None })),
+        )
+        .await
+        .expect("replace waited for the running synthesis")
+        .unwrap();
+
+        synthesis.abort();
+    }
+
+    #[derive(Debug)]
+    struct GatedService {
+        gate: Arc<tokio::sync::Notify>,
+    }
+
+    #[async_trait]
+    impl TtsService for GatedService {
+        async fn tts(&self, _style_id: &str, _text: &str, _params: &TtsParams) -> Result<Audio> {
+            self.gate.notified().await;
+            Ok(Audio::silence())
+        }
+
+        async fn styles(&self) -> Result<Vec<CharacterView>> {
+            Ok(vec![])
+        }
+    }
+
+    #[tokio::test]
+    async fn test_replace_invalidates_cache() {
+        let cache = TtsCache::new(&toml::from_str("").unwrap(), None);
+        let services = TtsServices::new(Some(cache), vec![], None, HealthConfig::default());
+        let gate = Arc::new(tokio::sync::Notify::new());
+
+        services
+            .replace("gated", Box::new(GatedService { gate: gate.clone() }))
+            .await
+            .unwrap();
+        services
+            .replace(
+                "other",
+                Box::new(MockService {
+                    audio: Some(Audio::silence()),
+                }),
+            )
+            .await
+            .unwrap();
+
+        services.tts(&style("other", "0"), "text").await.unwrap();
+
+        let synthesis = tokio::spawn({
+            let services = services.clone();
+            async move { services.tts(&style("gated", "0"), "text").await }
+        });
+        tokio::time::sleep(Duration::from_millis(10)).await;
+
+        services
+            .replace("gated", Box::new(MockService { audio: None }))
+            .await
+            .unwrap();
+        gate.notify_one();
+        synthesis.await.unwrap().unwrap();
+
+        // Audio of the old instance isn't cached, and the other services keep theirs.
+        let cache = services.inner.cache.as_ref().unwrap();
+        assert!(
+            cache
+                .get(&cache_key(&style("gated", "0"), "text"))
+                .await
+                .is_none()
+        );
+        assert!(
+            cache
+                .get(&cache_key(&style("other", "0"), "text"))
+                .await
+                .is_some()
+        );
+    }
+
+    #[derive(Debug)]
+    struct CatalogMock {
+        style_ids: Arc<Mutex<Vec<&'static str>>>,
+    }
+
+    #[async_trait]
+    impl TtsService for CatalogMock {
+        async fn tts(&self, _style_id: &str, _text: &str, _params: &TtsParams) -> Result<Audio> {
+            Ok(Audio::silence())
+        }
+
+        async fn styles(&self) -> Result<Vec<CharacterView>> {
+            Ok(vec![CharacterView {
+                name: "Mock".to_string(),
+                policy: String::new(),
+                styles: self
+                    .style_ids
+                    .lock()
+                    .unwrap()
+                    .iter()
+                    .map(|id| StyleView {
+                        icon: vec![],
+                        name: (*id).to_string(),
+                        id: (*id).to_string(),
+                        language: None,
+                        gender: None,
+                    })
+                    .collect(),
+                language: None,
+                gender: None,
+            }])
+        }
+    }
+
+    #[tokio::test]
+    async fn test_refresh() {
+        let services = TtsServices::new(None, vec![], None, HealthConfig::default());
+        let style_ids = Arc::new(Mutex::new(vec!["0", "1"]));
+
+        services
+            .replace(
+                "mock",
+                Box::new(CatalogMock {
+                    style_ids: style_ids.clone(),
+                }),
+            )
+            .await
+            .unwrap();
+
+        *style_ids.lock().unwrap() = vec!["1", "2"];
+
+        let report = services.refresh().await;
+        assert_eq!(report.refreshed, 1);
+        assert_eq!(report.added, 1);
+        assert_eq!(report.removed, vec![("mock".to_string(), "0".to_string())]);
+
+        assert!(!services.is_available("mock", "0").await);
+        assert!(services.is_available("mock", "2").await);
+
+        assert_eq!(services.refresh_service("mock").await.refreshed, 1);
+        assert_eq!(services.refresh_service("unknown").await.refreshed, 0);
+    }
+
+    #[tokio::test]
+    async fn test_pending_service() {
+        let services = TtsServices::new(None, vec![], None, HealthConfig::default());
+
+        assert!(
+            !services
+                .register_or_retry("unreachable", || anyhow::bail!("Connection refused"))
+                .await
+        );
+        assert_eq!(services.pending(), vec!["unreachable".to_string()]);
+
+        services.unregister("unreachable").await;
+        assert!(services.pending().is_empty());
+
+        assert!(
+            services
+                .register_or_retry("ok", || Ok(Box::new(MockService { audio: None })))
+                .await
+        );
+        assert!(services.pending().is_empty());
+    }
+
+    #[test]
+    fn test_gender_parse() {
+        assert_eq!(Gender::parse("f"), Some(Gender::Female));
+        assert_eq!(Gender::parse("Male"), Some(Gender::Male));
+        assert_eq!(Gender::parse("Neutral"), None);
+    }
+
     #[test]
     fn test_split_too_long_word() {
         let text = "a".repeat(300);
@@ -211,17 +602,102 @@ mod tests {
         assert_eq!(parts[1], "a".repeat(100));
     }
 }
+
+/// What a backend supports, so that features can be offered conditionally.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Capabilities {
+    pub params: NativeParams,
+    /// The text may contain SSML markup.
+    pub ssml: bool,
+    /// The volume can be configured per style.
+    pub per_style_volume: bool,
+}
+
 #[async_trait]
 pub trait TtsService: std::fmt::Debug + Send + Sync {
-    async fn tts(&self, style_id: &str, text: &str) -> Result<Vec<u8>>;
+    async fn tts(&self, style_id: &str, text: &str, params: &TtsParams) -> Result<Audio>;
     async fn styles(&self) -> Result<Vec<CharacterView>>;
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities::default()
+    }
+
+    /// The length the backend splits text into, if it does.
+    fn max_chars(&self) -> Option<usize> {
+        None
+    }
+
+    /// Checks that the backend is reachable. `None` if it has no cheap endpoint to probe, in
+    /// which case its health is judged by the synthesis requests only.
+    async fn health_check(&self) -> Option<Result<()>> {
+        None
+    }
+}
+
+/// A health check which only expects a successful status from `url`.
+pub async fn probe(client: &reqwest::Client, url: reqwest::Url) -> Result<()> {
+    client
+        .get(url)
+        .send()
+        .await
+        .context("Failed to probe (send)")?
+        .error_for_status()
+        .context("Failed to probe (status)")?;
+
+    Ok(())
 }
 
 #[derive(Derivative)]
 #[derivative(Debug)]
 struct TtsServicesInner {
-    #[allow(clippy::type_complexity)]
-    services: RwLock<HashMap<String, (Box<dyn TtsService>, Vec<CharacterView>)>>,
+    services: RwLock<HashMap<String, Registered>>,
+    next_generation: AtomicU64,
+    cache: Option<TtsCache>,
+    fallbacks: Vec<FallbackConfig>,
+    timeout: Option<Duration>,
+    health_config: HealthConfig,
+    health: Mutex<HashMap<String, CircuitBreaker>>,
+    refreshing: tokio::sync::Mutex<()>,
+    pending: Mutex<HashMap<String, tokio::task::AbortHandle>>,
+}
+
+#[derive(Debug)]
+struct Registered {
+    service: Arc<dyn TtsService>,
+    styles: Vec<CharacterView>,
+    /// Distinguishes the instances registered one after another under the same id.
+    generation: u64,
+}
+
+const RETRY_INITIAL_DELAY: Duration = Duration::from_secs(5);
+const RETRY_MAX_DELAY: Duration = Duration::from_mins(5);
+
+#[derive(Debug, Default)]
+pub struct RefreshReport {
+    pub refreshed: usize,
+    pub added: usize,
+    /// `(service_id, style_id)` of the styles which are no longer offered.
+    pub removed: Vec<(String, String)>,
+    pub failed: Vec<(String, anyhow::Error)>,
+}
+
+impl RefreshReport {
+    pub fn log(&self) {
+        for (service_id, style_id) in &self.removed {
+            tracing::warn!("{service_id}/{style_id} has disappeared from the catalog");
+        }
+
+        for (service_id, e) in &self.failed {
+            tracing::warn!("Failed to refresh the catalog of {service_id}: {e:#}");
+        }
+
+        tracing::info!(
+            "Refreshed {} catalogs: {} styles added, {} removed",
+            self.refreshed,
+            self.added,
+            self.removed.len(),
+        );
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -230,10 +706,23 @@ pub struct TtsServices {
 }
 
 impl TtsServices {
-    pub fn new() -> Self {
+    pub fn new(
+        cache: Option<TtsCache>,
+        fallbacks: Vec<FallbackConfig>,
+        timeout: Option<Duration>,
+        health_config: HealthConfig,
+    ) -> Self {
         Self {
             inner: Arc::new(TtsServicesInner {
                 services: RwLock::new(HashMap::new()),
+                next_generation: AtomicU64::new(0),
+                cache,
+                fallbacks,
+                timeout,
+                health_config,
+                health: Mutex::new(HashMap::new()),
+                refreshing: tokio::sync::Mutex::new(()),
+                pending: Mutex::new(HashMap::new()),
             }),
         }
     }
@@ -243,47 +732,579 @@ impl TtsServices {
 
         let mut styles = HashMap::new();
 
-        for (id, (_service, service_styles)) in services.iter() {
-            styles.insert(id.clone(), service_styles.clone());
+        for (id, registered) in services.iter() {
+            styles.insert(id.clone(), registered.styles.clone());
         }
 
         styles
     }
 
-    pub async fn register(&self, service_id: &str, service: Box<dyn TtsService>) -> Result<()> {
-        let mut services = self.inner.services.write().await;
+    /// Registers `service`, or swaps it in place of the one registered as `service_id`.
+    ///
+    /// Requests already running keep using the old instance until they complete, as they don't
+    /// hold the lock while the backend is working, but their audio isn't cached. The cached audio
+    /// of the replaced instance is dropped.
+    pub async fn replace(&self, service_id: &str, service: Box<dyn TtsService>) -> Result<()> {
+        let styles = service.styles().await?;
 
-        if services.get(service_id).is_some() {
-            anyhow::bail!("'{service_id}' is already taken");
-        }
+        let replaced = self
+            .inner
+            .services
+            .write()
+            .await
+            .insert(
+                service_id.to_owned(),
+                Registered {
+                    service: Arc::from(service),
+                    styles,
+                    generation: self.inner.next_generation.fetch_add(1, Ordering::Relaxed),
+                },
+            )
+            .is_some();
 
-        let styles = service.styles().await?;
+        self.inner.health.lock().unwrap().remove(service_id);
 
-        services.insert(service_id.to_owned(), (service, styles));
+        // The cached audio may have been synthesized with the old settings.
+        if replaced && let Some(cache) = &self.inner.cache {
+            cache.clear_service(service_id).await;
+        }
 
         Ok(())
     }
 
-    pub async fn is_available(&self, service_id: &str, style_id: &str) -> bool {
+    /// Registers the service made by `build`. If it fails, the service is left pending and
+    /// retried in the background with exponential backoff until it succeeds or is unregistered.
+    ///
+    /// Returns `false` if the service is pending.
+    pub async fn register_or_retry<F>(&self, service_id: &str, build: F) -> bool
+    where
+        F: Fn() -> Result<Box<dyn TtsService>> + Send + Sync + 'static,
+    {
+        self.cancel_retry(service_id);
+
+        let error = match self.try_register(service_id, &build).await {
+            Ok(()) => return true,
+            Err(e) => e,
+        };
+
+        tracing::warn!("'{service_id}' is pending, retrying in the background: {error:#}");
+
+        let services = self.clone();
+        let id = service_id.to_string();
+
+        let task = tokio::spawn(async move {
+            let mut delay = RETRY_INITIAL_DELAY;
+
+            loop {
+                tokio::time::sleep(delay).await;
+
+                match services.try_register(&id, &build).await {
+                    Ok(()) => break,
+                    Err(e) => {
+                        delay = (delay * 2).min(RETRY_MAX_DELAY);
+                        tracing::debug!("'{id}' is still pending, retrying in {delay:?}: {e:#}");
+                    }
+                }
+            }
+
+            // A later call may have replaced this task with its own.
+            let mut pending = services.inner.pending.lock().unwrap();
+            if pending
+                .get(&id)
+                .is_some_and(|task| task.id() == tokio::task::id())
+            {
+                pending.remove(&id);
+            }
+            drop(pending);
+
+            tracing::info!("'{id}' is now available");
+        });
+
+        self.inner
+            .pending
+            .lock()
+            .unwrap()
+            .insert(service_id.to_string(), task.abort_handle());
+
+        false
+    }
+
+    async fn try_register<F>(&self, service_id: &str, build: &F) -> Result<()>
+    where
+        F: Fn() -> Result<Box<dyn TtsService>>,
+    {
+        let service = build()?;
+
+        let result = match self.inner.timeout {
+            Some(timeout) => tokio::time::timeout(timeout, self.replace(service_id, service))
+                .await
+                .context("Timed out")?,
+            None => self.replace(service_id, service).await,
+        };
+
+        result.with_context(|| format!("Failed to register service {service_id}"))
+    }
+
+    fn cancel_retry(&self, service_id: &str) {
+        if let Some(task) = self.inner.pending.lock().unwrap().remove(service_id) {
+            task.abort();
+        }
+    }
+
+    /// Services which failed to initialize and are being retried.
+    pub fn pending(&self) -> Vec<String> {
+        let mut pending: Vec<_> = self.inner.pending.lock().unwrap().keys().cloned().collect();
+        pending.sort();
+        pending
+    }
+
+    pub async fn unregister(&self, service_id: &str) -> bool {
+        self.cancel_retry(service_id);
+        self.inner.health.lock().unwrap().remove(service_id);
+        self.inner
+            .services
+            .write()
+            .await
+            .remove(service_id)
+            .is_some()
+    }
+
+    /// Splits text the same way the backend of `style` does, so that each chunk can be played
+    /// as soon as it is synthesized.
+    pub async fn split(&self, style: &TtsStyle, text: &str) -> Vec<String> {
+        let services = self.inner.services.read().await;
+
+        match services
+            .get(&style.service_id)
+            .and_then(|registered| registered.service.max_chars())
+        {
+            Some(max_chars) => split_long_text(text, max_chars),
+            None => vec![text.to_string()],
+        }
+    }
+
+    pub async fn capabilities(&self, service_id: &str) -> Option<Capabilities> {
+        let services = self.inner.services.read().await;
+
+        services
+            .get(service_id)
+            .map(|registered| registered.service.capabilities())
+    }
+
+    /// Language of the style if the catalog of its service tells it.
+    pub async fn style_language(&self, style: &TtsStyle) -> Option<String> {
         let services = self.inner.services.read().await;
+        let characters = &services.get(&style.service_id)?.styles;
 
-        let Some((_service, styles)) = services.get(service_id) else {
-            return false;
+        characters.iter().find_map(|character| {
+            character
+                .styles
+                .iter()
+                .find(|s| s.id == style.style_id)
+                .and_then(|s| character.style_language(s))
+                .map(str::to_string)
+        })
+    }
+
+    /// Languages of the styles of the service, without duplicates.
+    pub async fn languages(&self, service_id: &str) -> Vec<String> {
+        let services = self.inner.services.read().await;
+
+        let Some(characters) = services.get(service_id).map(|r| &r.styles) else {
+            return vec![];
         };
 
-        styles
+        let mut languages: Vec<String> = characters
             .iter()
-            .flat_map(|s| s.styles.iter())
-            .any(|style| style.id == style_id)
+            .flat_map(|character| {
+                character
+                    .styles
+                    .iter()
+                    .filter_map(|style| character.style_language(style))
+            })
+            .map(str::to_string)
+            .collect();
+
+        languages.sort();
+        languages.dedup();
+        languages
     }
 
-    pub async fn tts(&self, service_id: &str, style_id: &str, text: &str) -> Result<Vec<u8>> {
-        let services = self.inner.services.read().await;
+    /// Whether the circuit breaker of the service is open.
+    pub fn is_down(&self, service_id: &str) -> bool {
+        self.inner
+            .health
+            .lock()
+            .unwrap()
+            .get(service_id)
+            .is_some_and(CircuitBreaker::is_down)
+    }
+
+    fn record(&self, service_id: &str, result: Result<(), &anyhow::Error>) {
+        let mut health = self.inner.health.lock().unwrap();
+        let breaker = health.entry(service_id.to_string()).or_default();
+        let now = Instant::now();
+
+        match result {
+            Ok(()) => {
+                let downtime = breaker.downtime(now);
+
+                if breaker.record_success() {
+                    tracing::info!(
+                        "'{service_id}' has recovered after {}s",
+                        downtime.unwrap_or_default().as_secs()
+                    );
+                }
+            }
+            Err(e) => {
+                if breaker.record_failure(&self.inner.health_config, now) {
+                    tracing::warn!("'{service_id}' is marked down: {e:#}");
+                }
+            }
+        }
+    }
+
+    /// Re-queries the catalog of every service and swaps them in.
+    ///
+    /// A service whose catalog can't be fetched keeps the previous one.
+    pub async fn refresh(&self) -> RefreshReport {
+        self.refresh_matching(None).await
+    }
+
+    /// Re-queries the catalog of a single service.
+    pub async fn refresh_service(&self, service_id: &str) -> RefreshReport {
+        self.refresh_matching(Some(service_id)).await
+    }
+
+    /// The services to work with outside of the lock, so that a reload isn't blocked meanwhile.
+    async fn services_matching(
+        &self,
+        only: Option<&str>,
+    ) -> Vec<(String, Arc<dyn TtsService>, u64)> {
+        self.inner
+            .services
+            .read()
+            .await
+            .iter()
+            .filter(|(id, _)| only.is_none_or(|only| only == id.as_str()))
+            .map(|(id, registered)| {
+                (
+                    id.clone(),
+                    registered.service.clone(),
+                    registered.generation,
+                )
+            })
+            .collect()
+    }
+
+    async fn refresh_matching(&self, only: Option<&str>) -> RefreshReport {
+        let _guard = self.inner.refreshing.lock().await;
+
+        let services = self.services_matching(only).await;
+
+        let fetched = futures::future::join_all(services.into_iter().map(
+            |(id, service, generation)| async move {
+                let styles = match self.inner.timeout {
+                    Some(timeout) => tokio::time::timeout(timeout, service.styles())
+                        .await
+                        .unwrap_or_else(|_| Err(anyhow::anyhow!("Timed out"))),
+                    None => service.styles().await,
+                };
+
+                // The instance may be replaced by a reload meanwhile.
+                (id, generation, styles)
+            },
+        ))
+        .await;
+
+        let mut report = RefreshReport::default();
+        let mut services = self.inner.services.write().await;
+
+        for (service_id, generation, styles) in fetched {
+            let Some(Registered {
+                styles: current,
+                generation: current_generation,
+                ..
+            }) = services.get_mut(&service_id)
+            else {
+                continue;
+            };
+
+            if *current_generation != generation {
+                continue;
+            }
+
+            let styles = match styles {
+                Ok(v) => v,
+                Err(e) => {
+                    report.failed.push((service_id, e));
+                    continue;
+                }
+            };
+
+            let ids = |characters: &[CharacterView]| -> Vec<String> {
+                characters
+                    .iter()
+                    .flat_map(|c| c.styles.iter().map(|s| s.id.clone()))
+                    .collect()
+            };
+
+            let old_ids = ids(current);
+            let new_ids = ids(&styles);
 
-        let Some((service, _styles)) = services.get(service_id) else {
-            anyhow::bail!("'{service_id}' is not registered");
+            report.added += new_ids.iter().filter(|id| !old_ids.contains(id)).count();
+            report.removed.extend(
+                old_ids
+                    .into_iter()
+                    .filter(|id| !new_ids.contains(id))
+                    .map(|id| (service_id.clone(), id)),
+            );
+
+            *current = styles;
+            report.refreshed += 1;
+        }
+
+        report
+    }
+
+    /// Refreshes the catalogs periodically until the process exits.
+    pub fn spawn_catalog_refresh(&self, interval: Duration) {
+        if interval.is_zero() {
+            return;
+        }
+
+        let services = self.clone();
+
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(interval);
+
+            // The catalogs have just been fetched by `register`.
+            interval.tick().await;
+
+            loop {
+                interval.tick().await;
+                services.refresh().await.log();
+            }
+        });
+    }
+
+    /// Probes every service once.
+    pub async fn check_health(&self) {
+        let services = self.services_matching(None).await;
+
+        let results =
+            futures::future::join_all(services.into_iter().map(|(id, service, _)| async move {
+                let result = match self.inner.timeout {
+                    Some(timeout) => tokio::time::timeout(timeout, service.health_check())
+                        .await
+                        .unwrap_or_else(|_| Some(Err(anyhow::anyhow!("Health check timed out")))),
+                    None => service.health_check().await,
+                };
+
+                (id, result)
+            }))
+            .await;
+
+        for (service_id, result) in results {
+            if let Some(result) = result {
+                self.record(&service_id, result.as_ref().copied());
+            }
+        }
+    }
+
+    /// Probes the services periodically until the process exits.
+    pub fn spawn_health_checks(&self) {
+        if self.inner.health_config.interval == 0 {
+            return;
+        }
+
+        let services = self.clone();
+        let interval = Duration::from_secs(self.inner.health_config.interval);
+
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(interval);
+
+            loop {
+                interval.tick().await;
+                services.check_health().await;
+            }
+        });
+    }
+
+    /// Whether the style is in the catalog of the service and can be synthesized.
+    ///
+    /// A style whose service is rejecting requests while it is down is unavailable, so that the
+    /// callers switch to a default style, unless it has fallbacks which [`TtsServices::tts`] walks.
+    pub async fn is_available(&self, service_id: &str, style_id: &str) -> bool {
+        {
+            let services = self.inner.services.read().await;
+
+            let Some(Registered { styles, .. }) = services.get(service_id) else {
+                return false;
+            };
+
+            if !styles
+                .iter()
+                .flat_map(|s| s.styles.iter())
+                .any(|style| style.id == style_id)
+            {
+                return false;
+            }
+        }
+
+        let rejects = self
+            .inner
+            .health
+            .lock()
+            .unwrap()
+            .get(service_id)
+            .is_some_and(|breaker| breaker.rejects(&self.inner.health_config, Instant::now()));
+
+        !rejects
+            || !self
+                .fallbacks(&TtsStyle {
+                    service_id: service_id.to_string(),
+                    style_id: style_id.to_string(),
+                    params: TtsParams::default(),
+                })
+                .is_empty()
+    }
+
+    /// Returns the fallback chain for `style`. A per-style entry takes precedence over a per-service one.
+    fn fallbacks(&self, style: &TtsStyle) -> Vec<TtsStyle> {
+        let fallbacks = &self.inner.fallbacks;
+
+        let chain = fallbacks
+            .iter()
+            .find(|f| {
+                f.service_id == style.service_id && f.style_id.as_ref() == Some(&style.style_id)
+            })
+            .or_else(|| {
+                fallbacks
+                    .iter()
+                    .find(|f| f.service_id == style.service_id && f.style_id.is_none())
+            });
+
+        chain
+            .map(|chain| {
+                chain
+                    .to
+                    .iter()
+                    .map(|to| TtsStyle {
+                        params: style.params,
+                        ..to.clone()
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    pub async fn tts(&self, style: &TtsStyle, text: &str) -> Result<Audio> {
+        let mut error = match self.tts_once(style, text).await {
+            Ok(audio) => return Ok(audio),
+            Err(e) => e,
         };
 
-        service.tts(style_id, text).await
+        let mut failed = style.clone();
+
+        for fallback in self.fallbacks(style) {
+            tracing::warn!(
+                "Synthesis with {}/{} failed: {error:#}, falling back to {}/{}",
+                failed.service_id,
+                failed.style_id,
+                fallback.service_id,
+                fallback.style_id,
+            );
+
+            match self.tts_once(&fallback, text).await {
+                Ok(audio) => {
+                    tracing::info!(
+                        "Fallback {}/{} was used instead of {}/{}",
+                        fallback.service_id,
+                        fallback.style_id,
+                        style.service_id,
+                        style.style_id,
+                    );
+                    return Ok(audio);
+                }
+                Err(e) => {
+                    error = e;
+                    failed = fallback;
+                }
+            }
+        }
+
+        Err(error)
+    }
+
+    async fn tts_once(&self, style: &TtsStyle, text: &str) -> Result<Audio> {
+        let key = cache_key(style, text);
+
+        if let Some(cache) = &self.inner.cache {
+            let audio = cache.get(&key).await;
+
+            METRICS
+                .cache_requests
+                .with_label_values(&[if audio.is_some() { "hit" } else { "miss" }])
+                .inc();
+
+            if let Some(audio) = audio {
+                return Ok(audio);
+            }
+        }
+
+        // Cloned out so that the lock isn't held while the backend is working.
+        let (service, generation) = match self.inner.services.read().await.get(&style.service_id) {
+            Some(registered) => (registered.service.clone(), registered.generation),
+            None => anyhow::bail!("'{}' is not registered", style.service_id),
+        };
+
+        if let Some(breaker) = self.inner.health.lock().unwrap().get_mut(&style.service_id)
+            && !breaker.allow(&self.inner.health_config, Instant::now())
+        {
+            anyhow::bail!("'{}' is down", style.service_id);
+        }
+
+        let started = Instant::now();
+        let audio = service.tts(&style.style_id, text, &style.params);
+
+        let audio = match self.inner.timeout {
+            Some(timeout) => tokio::time::timeout(timeout, audio)
+                .await
+                .with_context(|| format!("'{}' timed out", style.service_id))
+                .and_then(|audio| audio),
+            None => audio.await,
+        };
+
+        self.record(&style.service_id, audio.as_ref().map(|_| ()));
+
+        let labels = [style.service_id.as_str()];
+        if audio.is_ok() {
+            METRICS
+                .synthesis_duration
+                .with_label_values(&labels)
+                .observe(started.elapsed().as_secs_f64());
+        } else {
+            METRICS.synthesis_errors.with_label_values(&labels).inc();
+        }
+
+        let audio = audio?;
+
+        let audio = apply_params_fallback(audio, &style.params, service.capabilities().params);
+
+        if let Some(cache) = &self.inner.cache {
+            // Held while putting, so that `replace` clears the entries of the old instance after
+            // this is put, or this sees the new generation and is skipped.
+            let services = self.inner.services.read().await;
+
+            if services
+                .get(&style.service_id)
+                .is_some_and(|registered| registered.generation == generation)
+            {
+                cache.put(&key, audio.clone()).await;
+            }
+        }
+
+        Ok(audio)
     }
 }
@@ -1,5 +1,5 @@
 use std::collections::HashMap;
-use std::sync::Arc;
+use std::sync::{Arc, RwLock};
 
 use anyhow::{Context, Result};
 use async_trait::async_trait;
@@ -8,7 +8,9 @@ use reqwest::header::{HeaderMap, HeaderName};
 use serde::Deserialize;
 use tap::Tap;
 
-use crate::tts::{CharacterView, StyleView, TtsService};
+use crate::audio::Audio;
+use crate::model::TtsParams;
+use crate::tts::{Capabilities, CharacterView, StyleView, TtsService};
 
 mod api;
 
@@ -24,7 +26,7 @@ fn default_headers() -> HashMap<String, String> {
     HashMap::new()
 }
 
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Deserialize, Debug, Clone, PartialEq)]
 pub struct Setting {
     pub url: reqwest::Url,
     #[serde(default = "default_headers")]
@@ -39,7 +41,8 @@ pub struct Setting {
 struct VoiceroidInner {
     client: reqwest::Client,
     url: reqwest::Url,
-    voices: Vec<api::Voice>,
+    /// Refreshed by `styles`.
+    voices: RwLock<Vec<api::Voice>>,
     master_volume: f64,
     character_volume: HashMap<String, f64>,
 }
@@ -50,11 +53,7 @@ pub struct Voiceroid {
 }
 
 impl Voiceroid {
-    pub async fn new(setting: &Setting) -> Result<Self> {
-        let api_voices = setting.url.clone().tap_mut(|u| {
-            u.path_segments_mut().unwrap().push("api").push("voices");
-        });
-
+    pub fn new(setting: &Setting) -> Result<Self> {
         let mut headers = HeaderMap::new();
 
         for (key, value) in &setting.headers {
@@ -70,22 +69,12 @@ impl Voiceroid {
             .build()
             .unwrap();
 
-        let voices = client
-            .get(api_voices)
-            .send()
-            .await
-            .context("Failed to get /api/voices")?
-            .error_for_status()
-            .context("Failed to get /api/voices")?;
-
-        let voices = voices.json().await.context("Failed to parse /api/voices")?;
-
         Ok(Voiceroid {
             inner: Arc::new(VoiceroidInner {
                 url: setting.url.clone(),
                 master_volume: setting.master_volume,
                 character_volume: setting.character_volume.clone(),
-                voices,
+                voices: RwLock::new(vec![]),
                 client,
             }),
         })
@@ -94,7 +83,7 @@ impl Voiceroid {
 
 #[async_trait]
 impl TtsService for Voiceroid {
-    async fn tts(&self, style_id: &str, text: &str) -> Result<Vec<u8>> {
+    async fn tts(&self, style_id: &str, text: &str, _params: &TtsParams) -> Result<Audio> {
         let api_tts = self.inner.url.clone().tap_mut(|u| {
             u.path_segments_mut().unwrap().push("api").push("tts");
         });
@@ -108,17 +97,18 @@ impl TtsService for Voiceroid {
             .copied()
             .unwrap_or(1.0);
 
-        let voice = self
-            .inner
-            .voices
-            .iter()
-            .find(|v| v.id == voice_id)
-            .context("Invalid CharacterID")?;
-
-        let mut is_kansai = match voice.dialect.as_str() {
-            "Standard" => false,
-            "Kansai" => true,
-            _ => unreachable!(),
+        let mut is_kansai = {
+            let voices = self.inner.voices.read().unwrap();
+            let voice = voices
+                .iter()
+                .find(|v| v.id == voice_id)
+                .context("Invalid CharacterID")?;
+
+            match voice.dialect.as_str() {
+                "Standard" => false,
+                "Kansai" => true,
+                _ => unreachable!(),
+            }
         };
 
         if style == "alt" {
@@ -148,13 +138,35 @@ impl TtsService for Voiceroid {
             .await
             .context("Failed to post /api/tts (body)")?;
 
-        Ok(resp.to_vec())
+        Audio::from_wav(&resp)
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            per_style_volume: true,
+            ..Capabilities::default()
+        }
     }
 
     async fn styles(&self) -> Result<Vec<CharacterView>> {
-        Ok(self
+        let api_voices = self.inner.url.clone().tap_mut(|u| {
+            u.path_segments_mut().unwrap().push("api").push("voices");
+        });
+
+        let voices: Vec<api::Voice> = self
             .inner
-            .voices
+            .client
+            .get(api_voices)
+            .send()
+            .await
+            .context("Failed to get /api/voices")?
+            .error_for_status()
+            .context("Failed to get /api/voices")?
+            .json()
+            .await
+            .context("Failed to parse /api/voices")?;
+
+        let styles = voices
             .iter()
             .map(|voice| {
                 let (normal, alt) = match voice.dialect.as_str() {
@@ -169,20 +181,30 @@ impl TtsService for Voiceroid {
                     name: normal.to_string(),
                     id: format!("{}/normal", voice.id),
                     icon: icon.clone(),
+                    language: None,
+                    gender: None,
                 };
 
                 let alt = StyleView {
                     name: format!("{alt} (強制)"),
                     id: format!("{}/alt", voice.id),
                     icon: icon.clone(),
+                    language: None,
+                    gender: None,
                 };
 
                 CharacterView {
                     name: voice.name.clone(),
                     policy: "VOICEROID利用規約に則り、ご利用ください。".to_string(),
                     styles: vec![normal, alt],
+                    language: Some("ja".to_string()),
+                    gender: None,
                 }
             })
-            .collect())
+            .collect();
+
+        *self.inner.voices.write().unwrap() = voices;
+
+        Ok(styles)
     }
 }
@@ -1,11 +1,9 @@
 use std::collections::HashMap;
-use std::io::Cursor;
 use std::sync::Arc;
 
 use anyhow::{Context, Result};
 use async_trait::async_trait;
 use futures::future;
-use hound::WavReader;
 use json::JsonValue;
 use reqwest::{
     Url,
@@ -14,7 +12,11 @@ use reqwest::{
 use serde::Deserialize;
 use tap::Tap;
 
-use crate::tts::{CharacterView, StyleView, TtsService, split_long_text};
+use crate::audio::Audio;
+use crate::model::TtsParams;
+use crate::tts::{
+    Capabilities, CharacterView, NativeParams, StyleView, TtsService, probe, split_long_text,
+};
 
 mod api;
 
@@ -30,7 +32,7 @@ fn default_max_chars() -> usize {
     0
 }
 
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Deserialize, Debug, Clone, PartialEq)]
 pub struct Setting {
     pub url: reqwest::Url,
     #[serde(default = "default_headers")]
@@ -56,7 +58,7 @@ struct VoicevoxInner {
 
 #[async_trait]
 impl TtsService for Voicevox {
-    async fn tts(&self, style_id: &str, text: &str) -> Result<Vec<u8>> {
+    async fn tts(&self, style_id: &str, text: &str, params: &TtsParams) -> Result<Audio> {
         // VOICEVOX may run out of VRAM with long text, so split it into smaller chunks
         // max_chars = 0 means no limit (don't split)
         let parts = if self.inner.max_chars == 0 {
@@ -64,8 +66,7 @@ impl TtsService for Voicevox {
         } else {
             split_long_text(text, self.inner.max_chars)
         };
-        let mut all_samples: Vec<i16> = Vec::new();
-        let mut sample_rate = 24000u32;
+        let mut parts_audio = vec![];
 
         for part in parts {
             let url = self.inner.host.clone().tap_mut(|u| {
@@ -98,10 +99,28 @@ impl TtsService for Voicevox {
 
             let query_text = match json::parse(&query_text).context("Failed to parse query")? {
                 JsonValue::Object(mut obj) => {
-                    obj.insert(
-                        "volumeScale",
-                        JsonValue::Number(self.inner.master_volume.into()),
-                    );
+                    let volume = self.inner.master_volume * f64::from(params.volume.unwrap_or(1.0));
+                    obj.insert("volumeScale", JsonValue::Number(volume.into()));
+
+                    if let Some(speed) = params.speed {
+                        let base = obj.get("speedScale").and_then(JsonValue::as_f64);
+                        let speed = base.unwrap_or(1.0) * f64::from(speed);
+                        obj.insert("speedScale", JsonValue::Number(speed.into()));
+                    }
+
+                    if let Some(intonation) = params.intonation {
+                        let base = obj.get("intonationScale").and_then(JsonValue::as_f64);
+                        let intonation = base.unwrap_or(1.0) * f64::from(intonation);
+                        obj.insert("intonationScale", JsonValue::Number(intonation.into()));
+                    }
+
+                    // pitchScale is a shift of the log-F0, so a ratio becomes its logarithm.
+                    if let Some(pitch) = params.pitch {
+                        let base = obj.get("pitchScale").and_then(JsonValue::as_f64);
+                        let pitch = base.unwrap_or(0.0) + f64::from(pitch).ln();
+                        obj.insert("pitchScale", JsonValue::Number(pitch.into()));
+                    }
+
                     json::stringify(obj)
                 }
                 _ => anyhow::bail!("Non-object JSON is coming"),
@@ -124,35 +143,35 @@ impl TtsService for Voicevox {
                 .await
                 .context("Failed to post /synthesis (body)")?;
 
-            // Read WAV and collect samples
-            let mut reader = WavReader::new(Cursor::new(wav_data))?;
-            let spec = reader.spec();
-            if all_samples.is_empty() {
-                sample_rate = spec.sample_rate;
-            }
-
-            for sample in reader.samples::<i16>() {
-                all_samples.push(sample?);
-            }
+            parts_audio.push(Audio::from_wav(&wav_data)?);
         }
 
-        // Write combined samples to WAV
-        let spec = hound::WavSpec {
-            channels: 1,
-            sample_rate,
-            bits_per_sample: 16,
-            sample_format: hound::SampleFormat::Int,
-        };
-        let mut cursor = Cursor::new(Vec::new());
-        {
-            let mut writer = hound::WavWriter::new(&mut cursor, spec)?;
-            for sample in all_samples {
-                writer.write_sample(sample)?;
-            }
-            writer.finalize()?;
+        Ok(Audio::concat(parts_audio)?)
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            params: NativeParams {
+                speed: true,
+                pitch: true,
+                intonation: true,
+                volume: true,
+            },
+            ssml: false,
+            per_style_volume: false,
         }
+    }
+
+    fn max_chars(&self) -> Option<usize> {
+        (self.inner.max_chars != 0).then_some(self.inner.max_chars)
+    }
+
+    async fn health_check(&self) -> Option<Result<()>> {
+        let url = self.inner.host.clone().tap_mut(|u| {
+            u.path_segments_mut().unwrap().push("version");
+        });
 
-        Ok(cursor.into_inner())
+        Some(probe(&self.inner.client, url).await)
     }
 
     async fn styles(&self) -> Result<Vec<CharacterView>> {
@@ -218,6 +237,8 @@ impl TtsService for Voicevox {
                             icon: style_info.icon.bin,
                             id: format!("{}", style_info.id),
                             name: style.name,
+                            language: None,
+                            gender: None,
                         }
                     })
                     .collect();
@@ -234,6 +255,8 @@ impl TtsService for Voicevox {
                     name: speaker.name,
                     policy,
                     styles: speaker_styles,
+                    language: Some("ja".to_string()),
+                    gender: None,
                 })
             })
             .collect()
@@ -8,6 +8,8 @@ use reqwest::header::{HeaderMap, HeaderName};
 use serde::Deserialize;
 use tap::Tap;
 
+use crate::audio::Audio;
+use crate::model::TtsParams;
 use crate::tts::{CharacterView, StyleView, TtsService, split_long_text};
 
 mod api;
@@ -28,7 +30,7 @@ fn default_max_chars() -> usize {
     500
 }
 
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Deserialize, Debug, Clone, PartialEq)]
 pub struct Setting {
     pub url: reqwest::Url,
     #[serde(default = "default_headers")]
@@ -86,7 +88,7 @@ impl Volcengine {
 
 #[async_trait]
 impl TtsService for Volcengine {
-    async fn tts(&self, style_id: &str, text: &str) -> Result<Vec<u8>> {
+    async fn tts(&self, style_id: &str, text: &str, _params: &TtsParams) -> Result<Audio> {
         let api_tts = self.inner.url.clone().tap_mut(|u| {
             u.path_segments_mut()
                 .unwrap()
@@ -135,10 +137,14 @@ impl TtsService for Volcengine {
         }
 
         if combined_audio.is_empty() {
-            return Ok(crate::tts::EMPTY_WAVE.to_vec());
+            return Ok(Audio::silence());
         }
 
-        crate::tts::convert_mp3_to_wav(combined_audio, self.inner.master_volume)
+        Ok(Audio::from_mp3(combined_audio)?.gain(self.inner.master_volume))
+    }
+
+    fn max_chars(&self) -> Option<usize> {
+        (self.inner.max_chars != 0).then_some(self.inner.max_chars)
     }
 
     async fn styles(&self) -> Result<Vec<CharacterView>> {
@@ -149,6 +155,8 @@ impl TtsService for Volcengine {
                 icon: vec![],
                 name: name.clone(),
                 id: name.clone(),
+                language: None,
+                gender: None,
             });
         }
 
@@ -156,6 +164,8 @@ impl TtsService for Volcengine {
             name: "Volcengine Translate (火山翻译)".to_string(),
             policy: "火山翻译 ToS".to_string(),
             styles,
+            language: None,
+            gender: None,
         }])
     }
 }
@@ -1,9 +1,9 @@
 use std::io::{Read, Result, Seek, SeekFrom};
 
-use hound::WavReader;
 // Use symphonia-core 0.5.5 for MediaSource (required by songbird 0.6.0)
 use symphonia_core_0_5::io::MediaSource;
 
+use crate::audio::Audio;
 use crate::timestretch::apply_time_stretch;
 
 pub struct WavSource<'a> {
@@ -20,19 +20,18 @@ fn completion_2x(cum: &mut i16, v: i16) -> Option<[i16; 2]> {
 }
 
 impl WavSource<'_> {
-    pub fn new<R: Seek + Read>(
-        reader: &mut R,
+    /// Creates a mono source from the audio. Returns the source and its sample rate.
+    pub fn new(
+        audio: Audio,
         config: &crate::model::TimeStretchConfig,
-    ) -> (Self, u32) {
-        let mut wave = WavReader::new(reader).unwrap();
-        let data: Vec<i16> = wave.samples().map(|v| v.unwrap()).collect();
+        start: f64,
+    ) -> anyhow::Result<(Self, u32)> {
+        let audio = audio.into_mono();
+        let sample_rate = audio.sample_rate;
 
-        let sample_rate = wave.spec().sample_rate;
-        let channels = wave.spec().channels as usize;
+        let data = apply_time_stretch(&audio.samples, 1, sample_rate, config, start)?;
 
-        let data = apply_time_stretch(&data, channels, sample_rate, config);
-
-        if sample_rate <= 24000 {
+        Ok(if sample_rate <= 24000 {
             (
                 Self {
                     iterator: Box::new(
@@ -54,7 +53,7 @@ impl WavSource<'_> {
                 },
                 sample_rate,
             )
-        }
+        })
     }
 }
 
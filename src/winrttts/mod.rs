@@ -1,6 +1,6 @@
 use std::collections::BTreeMap;
 use std::collections::HashMap;
-use std::sync::Arc;
+use std::sync::{Arc, RwLock};
 
 use anyhow::{Context, Result};
 use async_trait::async_trait;
@@ -8,7 +8,9 @@ use reqwest::header::{HeaderMap, HeaderName};
 use serde::Deserialize;
 use tap::Tap;
 
-use crate::tts::{CharacterView, StyleView, TtsService};
+use crate::audio::Audio;
+use crate::model::TtsParams;
+use crate::tts::{Capabilities, CharacterView, Gender, StyleView, TtsService};
 
 mod api;
 
@@ -28,7 +30,7 @@ fn default_policy() -> String {
     "Microsoft Windows利用規約に則り、ご利用ください".to_string()
 }
 
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Deserialize, Debug, Clone, PartialEq)]
 pub struct Setting {
     pub url: reqwest::Url,
 
@@ -47,10 +49,10 @@ pub struct Setting {
 
 #[derive(Debug)]
 struct WinRTTTSInner {
-    registry_base_path: Option<String>,
+    /// Taken from the voice list, which is refreshed by `styles`.
+    registry_base_path: RwLock<Option<String>>,
     client: reqwest::Client,
     url: reqwest::Url,
-    voices: Vec<api::Voice>,
     master_volume: f32,
     character_volume: HashMap<String, f32>,
     policy: String,
@@ -62,11 +64,7 @@ pub struct WinRTTTS {
 }
 
 impl WinRTTTS {
-    pub async fn new(setting: &Setting) -> Result<Self> {
-        let api_voices = setting.url.clone().tap_mut(|u| {
-            u.path_segments_mut().unwrap().push("api").push("voices");
-        });
-
+    pub fn new(setting: &Setting) -> Result<Self> {
         let mut headers = HeaderMap::new();
 
         for (key, value) in &setting.headers {
@@ -82,24 +80,42 @@ impl WinRTTTS {
             .build()
             .unwrap();
 
-        let voices = client
+        Ok(WinRTTTS {
+            inner: Arc::new(WinRTTTSInner {
+                registry_base_path: RwLock::new(None),
+                url: setting.url.clone(),
+                master_volume: setting.master_volume,
+                character_volume: setting.character_volume.clone(),
+                policy: setting.policy.clone(),
+                client,
+            }),
+        })
+    }
+
+    async fn voices(&self) -> Result<Vec<api::Voice>> {
+        let api_voices = self.inner.url.clone().tap_mut(|u| {
+            u.path_segments_mut().unwrap().push("api").push("voices");
+        });
+
+        let mut voices: Vec<api::Voice> = self
+            .inner
+            .client
             .get(api_voices)
             .send()
             .await
             .context("Failed to get /api/voices")?
             .error_for_status()
-            .context("Failed to get /api/voices")?;
-
-        let mut voices: Vec<api::Voice> =
-            voices.json().await.context("Failed to parse /api/voices")?;
+            .context("Failed to get /api/voices")?
+            .json()
+            .await
+            .context("Failed to parse /api/voices")?;
 
         let first_voice = voices.first().context("Failed to get first voice")?;
 
         let registry_base_path = first_voice
             .id
             .rsplit_once('\\')
-            .map(|(registry_base_path, _name)| Some(registry_base_path.to_string()))
-            .unwrap_or(None);
+            .map(|(registry_base_path, _name)| registry_base_path.to_string());
 
         for voice in &mut voices {
             if let Some(registry_base_path) = &registry_base_path {
@@ -113,30 +129,20 @@ impl WinRTTTS {
                 }
 
                 voice.id = name.to_string();
-            } else {
-                if voice.id.contains('\\') {
-                    anyhow::bail!("Registry base path is inconsistent");
-                }
+            } else if voice.id.contains('\\') {
+                anyhow::bail!("Registry base path is inconsistent");
             }
         }
 
-        Ok(WinRTTTS {
-            inner: Arc::new(WinRTTTSInner {
-                registry_base_path,
-                url: setting.url.clone(),
-                master_volume: setting.master_volume,
-                character_volume: setting.character_volume.clone(),
-                policy: setting.policy.clone(),
-                voices,
-                client,
-            }),
-        })
+        *self.inner.registry_base_path.write().unwrap() = registry_base_path;
+
+        Ok(voices)
     }
 }
 
 #[async_trait]
 impl TtsService for WinRTTTS {
-    async fn tts(&self, style_id: &str, text: &str) -> Result<Vec<u8>> {
+    async fn tts(&self, style_id: &str, text: &str, _params: &TtsParams) -> Result<Audio> {
         let api_tts = self.inner.url.clone().tap_mut(|u| {
             u.path_segments_mut().unwrap().push("api").push("tts");
         });
@@ -154,8 +160,10 @@ impl TtsService for WinRTTTS {
             voice_id: self
                 .inner
                 .registry_base_path
+                .read()
+                .unwrap()
                 .as_ref()
-                .map(|p| format!("{p}\\{}", style_id))
+                .map(|p| format!("{p}\\{style_id}"))
                 .unwrap_or(style_id.to_string()),
         };
 
@@ -175,26 +183,31 @@ impl TtsService for WinRTTTS {
             .await
             .context("Failed to post /api/tts (body)")?;
 
-        Ok(resp.to_vec())
+        Audio::from_wav(&resp)
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            per_style_volume: true,
+            ..Capabilities::default()
+        }
     }
 
     async fn styles(&self) -> Result<Vec<CharacterView>> {
+        let voices = self.voices().await?;
         let mut styles: BTreeMap<String, Vec<StyleView>> = BTreeMap::new();
 
-        styles.extend(
-            self.inner
-                .voices
-                .iter()
-                .map(|v| (v.language.clone(), vec![])),
-        );
+        styles.extend(voices.iter().map(|v| (v.language.clone(), vec![])));
 
-        for voice in &self.inner.voices {
+        for voice in &voices {
             let target = styles.get_mut(&voice.language).unwrap();
 
             target.push(StyleView {
                 name: voice.display_name.clone(),
                 id: voice.id.clone(),
                 icon: vec![],
+                language: Some(voice.language.clone()),
+                gender: Gender::parse(&voice.gender),
             });
         }
 
@@ -202,8 +215,10 @@ impl TtsService for WinRTTTS {
             .into_iter()
             .map(|(language, styles)| CharacterView {
                 name: language.clone(),
-                policy: self.inner.policy.to_string(),
+                policy: self.inner.policy.clone(),
                 styles,
+                language: Some(language),
+                gender: None,
             })
             .collect())
     }